@@ -1,11 +1,28 @@
 #!/bin/bash
-# This script used to create formatting.txt file for --help.
-
-echo -e "\e[1;4mFormatting:\e[0m"
-echo -e "  \e[1m%x %X\e[0m The x-coordinate of the selection"
-echo -e "  \e[1m%y %Y\e[0m The y-coordinate of the selection"
-echo -e "  \e[1m%w %W\e[0m The width of the selection"
-echo -e "  \e[1m%h %H\e[0m The height of the selection"
-echo -e "  \e[1m%o   \e[0m The name of output"
-echo -e "  \e[1m%n   \e[0m Newline char ('\\\\n')"
+# This script used to create formatting.txt file for --help. Formatting section is generated
+# from `rect_fmt::SPECIFIERS` at runtime.
 
+echo -e "\e[1;4mRegion selector keys:\e[0m"
+echo -e "  \e[1m1\e[0m-\e[1m4\e[0m          Select top left, top right, bottom left or bottom right quarter of screen"
+echo -e "  \e[1m5\e[0m \e[1m6\e[0m          Select left or right half of screen"
+echo -e "  \e[1m0\e[0m            Select whole screen"
+echo -e "  \e[1mEnter\e[0m        Confirm selected preset (capture whole screen if nothing is selected)"
+echo -e "  \e[1mEsc\e[0m          Drop selection in progress, or exit if there is none"
+echo -e "  \e[1mSpace\e[0m        Hold while dragging to move selection instead of resizing it"
+echo -e "  \e[1mShift\e[0m        Hold while dragging to keep selection square"
+echo -e "  \e[1mCtrl\e[0m         Hold to ignore --snap and --snap-windows"
+echo -e "  \e[1mArrows\e[0m       Move corner under pointer by 1 pixel while dragging"
+echo -e "  \e[1mShift\e[0m+\e[1mArrows\e[0m Move corner under pointer by 10 pixels while dragging"
+echo -e "  \e[1mCtrl\e[0m+\e[1mArrows\e[0m  Move whole selection while dragging (by 10 pixels with Shift)"
+echo -e "  \e[1mF\e[0m            Hide overlay and capture fresh frame of screen (before selection is started)"
+echo -e "  \e[1mZ\e[0m            Toggle magnifier around pointer"
+echo
+echo -e "\e[1;4mExit codes:\e[0m"
+echo -e "  \e[1m0\e[0m Success"
+echo -e "  \e[1m1\e[0m Selection canceled or timed out"
+echo -e "  \e[1m2\e[0m Wayland error (connection, protocol or capture failure)"
+echo -e "  \e[1m3\e[0m Failed to encode or save image"
+echo -e "  \e[1m4\e[0m Invalid arguments or config file"
+echo -e "  \e[1m5\e[0m Compositor does not support required protocol (or window IPC for --active-window)"
+echo -e "  \e[1m6\e[0m No outputs found"
+echo -e "  \e[1m7\e[0m Output file already exists (with --no-clobber)"
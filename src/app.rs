@@ -1,11 +1,14 @@
 use base::BaseApp;
 use enum_dispatch::enum_dispatch;
+use picker::OutputPickerApp;
+use screenshot::CaptureEvent;
 use screenshot::ScreenshotApp;
 use selection::SelectionApp;
 use smithay_client_toolkit::{
     compositor::{CompositorHandler, CompositorState},
     delegate_compositor, delegate_keyboard, delegate_layer, delegate_output, delegate_pointer,
-    delegate_registry, delegate_seat, delegate_shm,
+    delegate_registry, delegate_seat, delegate_shm, delegate_touch,
+    globals::GlobalData,
     output::{OutputHandler, OutputState},
     registry::{ProvidesRegistryState, RegistryState},
     registry_handlers,
@@ -14,6 +17,7 @@ use smithay_client_toolkit::{
         pointer::{
             cursor_shape::CursorShapeManager, PointerEvent, PointerEventKind, PointerHandler,
         },
+        touch::TouchHandler,
         Capability, SeatHandler, SeatState,
     },
     shell::{
@@ -26,21 +30,78 @@ use smithay_client_toolkit::{
     shm::{slot::SlotPool, CreatePoolError, Shm, ShmHandler},
 };
 use wayland_client::{
+    event_created_child,
     globals::{registry_queue_init, BindError, GlobalError, GlobalList},
-    protocol::{wl_keyboard, wl_output, wl_pointer, wl_seat, wl_surface},
-    ConnectError, Connection, Dispatch, DispatchError, EventQueue, QueueHandle,
+    protocol::{wl_keyboard, wl_output, wl_pointer, wl_seat, wl_surface, wl_touch},
+    ConnectError, Connection, Dispatch, DispatchError, EventQueue, Proxy, QueueHandle,
+};
+use wayland_protocols::ext::{
+    image_capture_source::v1::client::{
+        ext_image_capture_source_v1::ExtImageCaptureSourceV1,
+        ext_output_image_capture_source_manager_v1::ExtOutputImageCaptureSourceManagerV1,
+    },
+    image_copy_capture::v1::client::{
+        ext_image_copy_capture_frame_v1::{self, ExtImageCopyCaptureFrameV1},
+        ext_image_copy_capture_manager_v1::ExtImageCopyCaptureManagerV1,
+        ext_image_copy_capture_session_v1::{self, ExtImageCopyCaptureSessionV1},
+    },
+};
+use wayland_protocols::wp::cursor_shape::v1::client::wp_cursor_shape_device_v1::{
+    Shape, WpCursorShapeDeviceV1,
+};
+use wayland_protocols::wp::tablet::zv2::client::{
+    zwp_tablet_manager_v2::ZwpTabletManagerV2,
+    zwp_tablet_pad_group_v2::{self, ZwpTabletPadGroupV2},
+    zwp_tablet_pad_ring_v2::ZwpTabletPadRingV2,
+    zwp_tablet_pad_strip_v2::ZwpTabletPadStripV2,
+    zwp_tablet_pad_v2::{self, ZwpTabletPadV2},
+    zwp_tablet_seat_v2::{self, ZwpTabletSeatV2},
+    zwp_tablet_tool_v2::{self, ZwpTabletToolV2},
+    zwp_tablet_v2::ZwpTabletV2,
 };
 use wayland_protocols_wlr::screencopy::v1::client::{
-    zwlr_screencopy_frame_v1::ZwlrScreencopyFrameV1,
+    zwlr_screencopy_frame_v1::{self, ZwlrScreencopyFrameV1},
     zwlr_screencopy_manager_v1::ZwlrScreencopyManagerV1,
 };
 
-use crate::points::{Point, PointInt};
+use std::{
+    os::fd::BorrowedFd,
+    time::{Duration, Instant},
+};
+
+use rustix::event::{PollFd, PollFlags};
+use wayland_client::backend::WaylandError;
+
+use crate::points::{Point, PointInt, Rectangle};
 
 pub mod base;
+mod font;
+pub mod picker;
 pub mod screenshot;
 pub mod selection;
 
+/// Keyboard focus requested by overlay surface.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, clap::ValueEnum)]
+pub enum KeyboardMode {
+    /// Grab keyboard while overlay is shown
+    #[default]
+    Exclusive,
+    /// Receive keyboard focus like usual window, compositor shortcuts keep working
+    OnDemand,
+    /// Never receive keyboard input, selection can only be canceled with right click
+    None,
+}
+
+impl From<KeyboardMode> for KeyboardInteractivity {
+    fn from(mode: KeyboardMode) -> Self {
+        match mode {
+            KeyboardMode::Exclusive => Self::Exclusive,
+            KeyboardMode::OnDemand => Self::OnDemand,
+            KeyboardMode::None => Self::None,
+        }
+    }
+}
+
 pub struct WaylandAppManager {
     globals: GlobalList,
     event_queue: EventQueue<WaylandApp>,
@@ -55,17 +116,25 @@ pub struct WaylandApp {
 
 pub struct WaylandContext(WaylandContextKind);
 
+#[allow(clippy::large_enum_variant)] // NOTE: context is allocated once and only upgraded in place
 enum WaylandContextKind {
     __Nil,
 
     Base(WaylandContextBase),
     Partial(WaylandContextPartial),
     Full(WaylandContextFull),
+    /// Base context with overlays of [`OutputPickerApp`], it is turned back into base once
+    /// output is picked
+    Picker(WaylandContextPicker),
 }
 
 pub struct WaylandContextBase {
     pub registry_state: RegistryState,
     pub output_state: OutputState,
+
+    /// Output to capture, first one unless other is selected with
+    /// [`WaylandAppManager::select_output`]
+    pub output: Option<wl_output::WlOutput>,
 }
 
 pub struct WaylandContextPartial {
@@ -75,6 +144,8 @@ pub struct WaylandContextPartial {
     pub pool: SlotPool,
 
     pub logical_size: Point,
+    /// Only this region of output (in logical coordinates) is copied, if compositor supports it
+    pub region: Option<Rectangle>,
 }
 
 pub struct WaylandContextFull {
@@ -82,10 +153,159 @@ pub struct WaylandContextFull {
 
     pub seat_state: SeatState,
     pub shape_manager: Option<CursorShapeManager>,
+    /// Cursor shape device of pointer over overlay and serial of its enter, see
+    /// [`WaylandContextFull::set_cursor_shape`]
+    cursor: Option<(WpCursorShapeDeviceV1, u32)>,
+    pub keyboard: Option<wl_keyboard::WlKeyboard>,
+    pub pointer: Option<wl_pointer::WlPointer>,
+    pub touch: Option<wl_touch::WlTouch>,
+    /// Currently held keyboard modifiers
+    pub modifiers: Modifiers,
+    /// Id and last position of touch point which acts as pointer, other touch points are ignored
+    touch_point: Option<(i32, Point)>,
+    /// Only input of this seat is used if it is set, otherwise input of the first seat with
+    /// keyboard or pointer is used
+    pub seat: Option<wl_seat::WlSeat>,
+    /// Seat is requested by name, input is bound once it is known, see
+    /// [`WaylandAppManager::initialize_full`]
+    seat_requested: bool,
+    /// Bound only if compositor supports tablets
+    tablet_manager: Option<ZwpTabletManagerV2>,
+    /// Tablets of the seat which input is used
+    tablet_seat: Option<ZwpTabletSeatV2>,
+    /// Tablet tool in proximity of overlay, it acts as pointer. Other tools are ignored until it
+    /// leaves
+    tablet_tool: Option<TabletTool>,
+
+    /// Kept to create overlay again, see [`WaylandAppManager::await_overlay`]
+    compositor: CompositorState,
+    layer_shell: LayerShell,
+    pub layer: LayerSurface,
+    /// Overlay got its first configure
+    configured: bool,
+    /// Overlay got keyboard focus at least once
+    keyboard_entered: bool,
+}
+
+/// Tablet tool which acts as pointer, see [`WaylandContextFull::tablet_tool`].
+struct TabletTool {
+    tool: ZwpTabletToolV2,
+    /// Last position of tool, it comes with the first motion after proximity in
+    last: Option<Point>,
+    /// Cursor shape device of tool and serial of its proximity in, like
+    /// [`WaylandContextFull::cursor`]
+    cursor: Option<(WpCursorShapeDeviceV1, u32)>,
+}
+
+impl TabletTool {
+    /// Destroy cursor shape device of tool once it leaves overlay.
+    fn leave(self) {
+        if let Some((device, _)) = self.cursor {
+            device.destroy();
+        }
+    }
+}
+
+pub struct WaylandContextPicker {
+    pub base: WaylandContextBase,
+
+    pub shm: Shm,
+    pub pool: SlotPool,
+
+    pub seat_state: SeatState,
     pub keyboard: Option<wl_keyboard::WlKeyboard>,
     pub pointer: Option<wl_pointer::WlPointer>,
 
+    /// Overlay on every output
+    pub layers: Vec<PickerLayer>,
+    /// Output which overlay is under pointer
+    pub hovered: Option<wl_output::WlOutput>,
+}
+
+pub struct PickerLayer {
+    pub output: wl_output::WlOutput,
     pub layer: LayerSurface,
+    /// Size of overlay from the last configure, [`None`] until it is configured
+    pub size: Option<(u32, u32)>,
+}
+
+impl WaylandContextPicker {
+    /// Use pointer or keyboard of `seat` unless it is already bound.
+    fn bind_input(
+        &mut self,
+        qh: &QueueHandle<WaylandApp>,
+        seat: &wl_seat::WlSeat,
+        capability: Capability,
+    ) {
+        if capability == Capability::Keyboard && self.keyboard.is_none() {
+            self.keyboard = self.seat_state.get_keyboard(qh, seat, None).ok();
+        }
+        if capability == Capability::Pointer && self.pointer.is_none() {
+            self.pointer = self.seat_state.get_pointer(qh, seat).ok();
+        }
+    }
+
+    /// Release input, so it is not delivered twice once overlay of region selector binds it
+    /// again. Releasing requires version 3 of seat.
+    fn release_input(&mut self) {
+        if let Some(keyboard) = self.keyboard.take().filter(|k| k.version() >= 3) {
+            keyboard.release();
+        }
+        if let Some(pointer) = self.pointer.take().filter(|p| p.version() >= 3) {
+            pointer.release();
+        }
+    }
+}
+
+impl WaylandContextFull {
+    /// Set shape of pointer (and of tablet tool) over overlay. Nothing happens if compositor does
+    /// not support cursor shapes or pointer is not over overlay.
+    pub fn set_cursor_shape(&self, shape: Shape) {
+        let tool = self
+            .tablet_tool
+            .as_ref()
+            .and_then(|tool| tool.cursor.as_ref());
+        for (device, serial) in self.cursor.iter().chain(tool) {
+            device.set_shape(*serial, shape);
+        }
+    }
+
+    /// Use `capability` of `seat` unless it is already bound.
+    fn bind_input(
+        &mut self,
+        qh: &QueueHandle<WaylandApp>,
+        seat: &wl_seat::WlSeat,
+        capability: Capability,
+    ) {
+        // Tablets are not a capability of seat, they are bound with the first one
+        if let (Some(manager), None) = (&self.tablet_manager, &self.tablet_seat) {
+            self.tablet_seat = Some(manager.get_tablet_seat(seat, qh, ()));
+        }
+
+        if capability == Capability::Keyboard && self.keyboard.is_none() {
+            let keyboard = self
+                .seat_state
+                .get_keyboard(qh, seat, None)
+                .expect("Failed to create keyboard");
+            self.keyboard = Some(keyboard);
+        }
+
+        if capability == Capability::Pointer && self.pointer.is_none() {
+            let pointer = self
+                .seat_state
+                .get_pointer(qh, seat)
+                .expect("Failed to create pointer");
+            self.pointer = Some(pointer);
+        }
+
+        if capability == Capability::Touch && self.touch.is_none() {
+            let touch = self
+                .seat_state
+                .get_touch(qh, seat)
+                .expect("Failed to create touch");
+            self.touch = Some(touch);
+        }
+    }
 }
 
 impl WaylandContext {
@@ -94,6 +314,7 @@ impl WaylandContext {
             WaylandContextKind::Base(v) => v,
             WaylandContextKind::Partial(v) => &v.base,
             WaylandContextKind::Full(v) => &v.partial.base,
+            WaylandContextKind::Picker(v) => &v.base,
 
             WaylandContextKind::__Nil => unreachable!(),
         }
@@ -104,6 +325,7 @@ impl WaylandContext {
             WaylandContextKind::Base(v) => v,
             WaylandContextKind::Partial(v) => &mut v.base,
             WaylandContextKind::Full(v) => &mut v.partial.base,
+            WaylandContextKind::Picker(v) => &mut v.base,
 
             WaylandContextKind::__Nil => unreachable!(),
         }
@@ -111,7 +333,7 @@ impl WaylandContext {
 
     pub fn partial(&self) -> Option<&WaylandContextPartial> {
         match &self.0 {
-            WaylandContextKind::Base(_) => None,
+            WaylandContextKind::Base(_) | WaylandContextKind::Picker(_) => None,
             WaylandContextKind::Partial(v) => Some(v),
             WaylandContextKind::Full(v) => Some(&v.partial),
 
@@ -121,7 +343,7 @@ impl WaylandContext {
 
     pub fn partial_mut(&mut self) -> Option<&mut WaylandContextPartial> {
         match &mut self.0 {
-            WaylandContextKind::Base(_) => None,
+            WaylandContextKind::Base(_) | WaylandContextKind::Picker(_) => None,
             WaylandContextKind::Partial(v) => Some(v),
             WaylandContextKind::Full(v) => Some(&mut v.partial),
 
@@ -142,12 +364,28 @@ impl WaylandContext {
             _ => None,
         }
     }
+
+    pub fn picker(&self) -> Option<&WaylandContextPicker> {
+        match &self.0 {
+            WaylandContextKind::Picker(v) => Some(v),
+            _ => None,
+        }
+    }
+
+    pub fn picker_mut(&mut self) -> Option<&mut WaylandContextPicker> {
+        match &mut self.0 {
+            WaylandContextKind::Picker(v) => Some(v),
+            _ => None,
+        }
+    }
 }
 
 #[enum_dispatch(WaylandAppState)]
 #[allow(clippy::enum_variant_names)] // NOTE: it may removed in future and %App will renamed to %
+#[allow(clippy::large_enum_variant)] // NOTE: state is allocated once and only replaced by next one
 pub enum AppState {
     BaseApp,
+    OutputPickerApp,
     ScreenshotApp,
     SelectionApp,
 }
@@ -161,13 +399,11 @@ pub enum StatePhase {
 pub trait WaylandAppState {
     fn current_phase(&self) -> StatePhase;
 
-    fn zwlr_screencopy_frame_event<U>(
+    /// Event of frame copied with `zwlr_screencopy_frame_v1` or ext image copy capture.
+    fn capture_event(
         &mut self,
         _context: &mut WaylandContext,
-        _proxy: &ZwlrScreencopyFrameV1,
-        _event: <ZwlrScreencopyFrameV1 as wayland_client::Proxy>::Event,
-        _data: &U,
-        _conn: &Connection,
+        _event: CaptureEvent,
         _qh: &QueueHandle<WaylandApp>,
     ) {
     }
@@ -202,6 +438,50 @@ pub trait WaylandAppState {
         _qh: &QueueHandle<WaylandApp>,
     ) {
     }
+    fn on_right_press(
+        &mut self,
+        _context: &mut WaylandContext,
+        _pos: Point,
+        _qh: &QueueHandle<WaylandApp>,
+    ) {
+    }
+    fn on_middle_press(
+        &mut self,
+        _context: &mut WaylandContext,
+        _pos: Point,
+        _qh: &QueueHandle<WaylandApp>,
+    ) {
+    }
+    fn on_middle_release(
+        &mut self,
+        _context: &mut WaylandContext,
+        _pos: Point,
+        _qh: &QueueHandle<WaylandApp>,
+    ) {
+    }
+
+    /// Tablet tool came into proximity of surface, its position comes with the next motion
+    fn on_tool_enter(
+        &mut self,
+        _context: &mut WaylandContext,
+        _tool: &ZwpTabletToolV2,
+        _serial: u32,
+        _qh: &QueueHandle<WaylandApp>,
+    ) {
+    }
+
+    /// Touch point acting as pointer is taken by compositor (e.g. for gesture) while it is
+    /// down, release will never come
+    fn on_touch_cancel(&mut self, _context: &mut WaylandContext, _qh: &QueueHandle<WaylandApp>) {}
+
+    /// Mouse wheel is scrolled by `notches`, negative values are scrolls up
+    fn on_scroll(
+        &mut self,
+        _context: &mut WaylandContext,
+        _notches: i32,
+        _qh: &QueueHandle<WaylandApp>,
+    ) {
+    }
 
     fn on_key_press(
         &mut self,
@@ -219,7 +499,15 @@ pub trait WaylandAppState {
     ) {
     }
 
+    /// Overlay lost keyboard focus, e.g. user switched to another window
+    fn on_focus_lost(&mut self, _context: &mut WaylandContext, _qh: &QueueHandle<WaylandApp>) {}
+
     fn on_redraw(&mut self, _context: &mut WaylandContext, _qh: &QueueHandle<WaylandApp>) {}
+
+    /// Layer surface is configured, redraws by default
+    fn on_configure(&mut self, context: &mut WaylandContext, qh: &QueueHandle<WaylandApp>) {
+        self.on_redraw(context, qh)
+    }
 }
 
 pub trait WaylandAppStateFromPrevious: Sized {
@@ -247,11 +535,26 @@ impl WaylandAppManager {
             ctx: WaylandContext(WaylandContextKind::Base(WaylandContextBase {
                 registry_state,
                 output_state,
+                output: None,
             })),
         };
 
         event_queue.roundtrip(&mut app).map_err(Error::Dispatch)?;
 
+        let base = app.ctx.base_mut();
+        base.output = base.output_state.outputs().next();
+        for output in base.output_state.outputs() {
+            if let Some(info) = base.output_state.info(&output) {
+                log::debug!(
+                    "output {}: logical position {:?}, logical size {:?}, scale {}",
+                    info.name.as_deref().unwrap_or("<unknown>"),
+                    info.logical_position,
+                    info.logical_size,
+                    info.scale_factor
+                );
+            }
+        }
+
         Ok(Self {
             app,
             event_queue,
@@ -260,8 +563,158 @@ impl WaylandAppManager {
         })
     }
 
-    pub fn initialize_partial(&mut self) -> Result<(), Error> {
-        let Some(output) = self.app.ctx.base().output_state.outputs().next() else {
+    /// Wait until compositor announces output if there is none yet. Returns [`Error::NoOutput`]
+    /// if it does not happen before `deadline`.
+    pub fn wait_for_output(&mut self, deadline: Instant) -> Result<(), Error> {
+        let start = Instant::now();
+        loop {
+            let base = self.app.ctx.base_mut();
+            if base.output.is_none() {
+                base.output = base.output_state.outputs().find(|o| {
+                    base.output_state
+                        .info(o)
+                        .is_some_and(|i| i.logical_size.is_some())
+                });
+            }
+            if base.output.is_some() {
+                log::debug!("output found after {:?}", start.elapsed());
+                return Ok(());
+            }
+
+            if !self.dispatch_before(deadline)? {
+                return Err(Error::NoOutput);
+            }
+        }
+    }
+
+    /// Capture output with `name` instead of the first one.
+    pub fn select_output(&mut self, name: &str) -> Result<(), Error> {
+        let base = self.app.ctx.base_mut();
+        let output = base
+            .output_state
+            .outputs()
+            .find(|o| {
+                base.output_state
+                    .info(o)
+                    .is_some_and(|i| i.name.as_deref() == Some(name))
+            })
+            .ok_or_else(|| Error::UnknownOutput(name.to_owned()))?;
+
+        base.output = Some(output);
+        log::debug!("selected output {name}");
+
+        Ok(())
+    }
+
+    /// Show overlay with letter and name on every output and capture the one user clicks (or
+    /// presses letter of). Does nothing if there is only one output, returns
+    /// [`Error::Canceled`] if picking is canceled with Escape or right click.
+    pub fn pick_output(&mut self, keyboard: KeyboardMode) -> Result<(), Error> {
+        let outputs: Vec<_> = self.app.ctx.base().output_state.outputs().collect();
+        if outputs.len() < 2 {
+            return Ok(());
+        }
+
+        let seat_state = SeatState::new(&self.globals, &self.qh);
+        let compositor =
+            CompositorState::bind(&self.globals, &self.qh).map_err(Error::Compositor)?;
+        let layer_shell = LayerShell::bind(&self.globals, &self.qh).map_err(Error::LayerShell)?;
+        let shm = Shm::bind(&self.globals, &self.qh).map_err(Error::Shm)?;
+        // Grows as overlays are configured
+        let pool = SlotPool::new(4, &shm).map_err(Error::CreatePool)?;
+
+        log::debug!("picking one of {} outputs", outputs.len());
+        let layers = outputs
+            .into_iter()
+            .map(|output| {
+                let layer = layer_shell.create_layer_surface(
+                    &self.qh,
+                    compositor.create_surface(&self.qh),
+                    Layer::Overlay,
+                    Some("prtsc-wayland"),
+                    Some(&output),
+                );
+                layer.set_anchor(Anchor::all());
+                layer.set_exclusive_zone(-1);
+                layer.set_keyboard_interactivity(keyboard.into());
+                layer.commit();
+
+                PickerLayer {
+                    output,
+                    layer,
+                    size: None,
+                }
+            })
+            .collect();
+
+        let WaylandContext(WaylandContextKind::Base(base)) =
+            std::mem::replace(&mut self.app.ctx, WaylandContext(WaylandContextKind::__Nil))
+        else {
+            panic!("output can be picked only before partial context is initialized");
+        };
+        self.app.ctx = WaylandContext(WaylandContextKind::Picker(WaylandContextPicker {
+            base,
+            shm,
+            pool,
+            seat_state,
+            keyboard: None,
+            pointer: None,
+            layers,
+            hovered: None,
+        }));
+
+        // NOTE: picker is not a step of `next_app`, it starts and ends with base app
+        let AppState::BaseApp(prev) =
+            std::mem::replace(&mut self.app.state, AppState::BaseApp(BaseApp))
+        else {
+            panic!("output can be picked only from base app");
+        };
+        self.app.state = OutputPickerApp::from_previous(
+            &mut self.app.ctx,
+            prev,
+            &self.globals,
+            &mut self.event_queue,
+        )?
+        .into();
+        let result = self.dispatch_until_done();
+
+        let picked = match std::mem::replace(&mut self.app.state, AppState::BaseApp(BaseApp)) {
+            AppState::OutputPickerApp(app) => app.picked,
+            _ => unreachable!("picker is replaced only here"),
+        };
+        let WaylandContext(WaylandContextKind::Picker(mut picker)) =
+            std::mem::replace(&mut self.app.ctx, WaylandContext(WaylandContextKind::__Nil))
+        else {
+            unreachable!("picker context is replaced only here");
+        };
+        picker.release_input();
+        // Overlays are destroyed with context, they must be gone before output is captured
+        self.app.ctx = WaylandContext(WaylandContextKind::Base(picker.base));
+        self.event_queue
+            .roundtrip(&mut self.app)
+            .map_err(Error::Dispatch)?;
+
+        result?;
+        let output = picked.ok_or(Error::Canceled)?;
+        if let Some(name) = self
+            .app
+            .ctx
+            .base()
+            .output_state
+            .info(&output)
+            .and_then(|i| i.name)
+        {
+            log::debug!("picked output {name}");
+        }
+        self.app.ctx.base_mut().output = Some(output);
+
+        Ok(())
+    }
+
+    /// Bind shared memory for copy of output. If `region` is set, only it is copied when
+    /// compositor supports it and pool is sized for it instead of the whole output.
+    pub fn initialize_partial(&mut self, region: Option<Rectangle>) -> Result<(), Error> {
+        let Some(output) = self.app.ctx.base().output.clone() else {
             return Err(Error::NoOutput);
         };
 
@@ -278,8 +731,13 @@ impl WaylandAppManager {
         };
 
         let shm = Shm::bind(&self.globals, &self.qh).map_err(Error::Shm)?;
-        let pool = SlotPool::new(logical_size.x as usize * logical_size.y as usize * 4, &shm)
-            .map_err(Error::CreatePool)?;
+        // Pool grows if compositor does not copy just the region
+        let (width, height) = match &region {
+            Some(rect) => (rect.width, rect.height),
+            None => (logical_size.x, logical_size.y),
+        };
+        let pool =
+            SlotPool::new(width as usize * height as usize * 4, &shm).map_err(Error::CreatePool)?;
 
         let WaylandContext(WaylandContextKind::Base(base)) =
             std::mem::replace(&mut self.app.ctx, WaylandContext(WaylandContextKind::__Nil))
@@ -289,52 +747,182 @@ impl WaylandAppManager {
         self.app.ctx = WaylandContext(WaylandContextKind::Partial(WaylandContextPartial {
             base,
             logical_size,
+            region,
             shm,
             pool,
         }));
+        log::debug!("partial context initialized");
 
         Ok(())
     }
 
-    pub fn initialize_full(&mut self) -> Result<(), Error> {
+    /// Create overlay surface with `keyboard` interactivity. If `seat_name` is set, only input of
+    /// seat with this name is used, [`Error::UnknownSeat`] is returned if there is no such seat.
+    pub fn initialize_full(
+        &mut self,
+        seat_name: Option<&str>,
+        keyboard: KeyboardMode,
+    ) -> Result<(), Error> {
         let seat_state = SeatState::new(&self.globals, &self.qh);
         let shape_manager = CursorShapeManager::bind(&self.globals, &self.qh).ok();
+        let tablet_manager = self
+            .globals
+            .bind::<ZwpTabletManagerV2, _, _>(&self.qh, 1..=1, ())
+            .ok();
 
         let compositor =
             CompositorState::bind(&self.globals, &self.qh).map_err(Error::Compositor)?;
         let layer_shell = LayerShell::bind(&self.globals, &self.qh).map_err(Error::LayerShell)?;
 
-        let surface = compositor.create_surface(&self.qh);
-
         let WaylandContext(WaylandContextKind::Partial(partial)) =
             std::mem::replace(&mut self.app.ctx, WaylandContext(WaylandContextKind::__Nil))
         else {
             panic!("attempt to initialize full context on non-partial context (uninitialized partial or double-initialized full)");
         };
         let size = partial.logical_size.clone();
+        // Overlay is shown on captured output, not on the one compositor chooses (usually the
+        // focused one)
+        let output = partial.base.output.clone();
+        log::debug!(
+            "creating layer surface {}x{}, cursor shape manager {}, tablet manager {}",
+            size.x,
+            size.y,
+            if shape_manager.is_some() {
+                "found"
+            } else {
+                "not found"
+            },
+            if tablet_manager.is_some() {
+                "found"
+            } else {
+                "not found"
+            }
+        );
 
-        let layer = layer_shell.create_layer_surface(
+        let layer = create_overlay(
             &self.qh,
-            surface,
-            Layer::Overlay,
-            Some("prtsc-wayland"),
-            None,
+            &compositor,
+            &layer_shell,
+            output.as_ref(),
+            &size,
+            keyboard.into(),
         );
-        layer.set_anchor(Anchor::all());
-        layer.set_exclusive_zone(-1);
-        layer.set_keyboard_interactivity(KeyboardInteractivity::Exclusive);
-        layer.set_size(size.x, size.y);
-        layer.commit();
 
         self.app.ctx = WaylandContext(WaylandContextKind::Full(WaylandContextFull {
             partial,
             seat_state,
             shape_manager,
+            cursor: None,
             keyboard: None,
             pointer: None,
+            touch: None,
+            touch_point: None,
+            modifiers: Modifiers::default(),
+            seat: None,
+            seat_requested: seat_name.is_some(),
+            tablet_manager,
+            tablet_seat: None,
+            tablet_tool: None,
+            compositor,
+            layer_shell,
             layer,
+            configured: false,
+            keyboard_entered: false,
         }));
 
+        if let Some(name) = seat_name {
+            // Name is sent after capabilities, so input can't be bound as capabilities arrive.
+            // Surface is not committed yet, so configure is not lost in this roundtrip.
+            self.event_queue
+                .roundtrip(&mut self.app)
+                .map_err(Error::Dispatch)?;
+
+            let ctx = self.app.ctx.full_mut().expect("initialized above");
+            let (seat, info) = ctx
+                .seat_state
+                .seats()
+                .filter_map(|seat| Some((seat.clone(), ctx.seat_state.info(&seat)?)))
+                .find(|(_, info)| info.name.as_deref() == Some(name))
+                .ok_or_else(|| Error::UnknownSeat(name.to_owned()))?;
+            log::debug!(
+                "selected seat {name}: keyboard {}, pointer {}",
+                info.has_keyboard,
+                info.has_pointer
+            );
+
+            if info.has_keyboard {
+                ctx.bind_input(&self.qh, &seat, Capability::Keyboard);
+            }
+            if info.has_pointer {
+                ctx.bind_input(&self.qh, &seat, Capability::Pointer);
+            }
+            if info.has_touch {
+                ctx.bind_input(&self.qh, &seat, Capability::Touch);
+            }
+            ctx.seat = Some(seat);
+        }
+
+        self.app
+            .ctx
+            .full()
+            .expect("initialized above")
+            .layer
+            .commit();
+
+        Ok(())
+    }
+
+    /// Wait until overlay is configured and focused, if it asked for `keyboard` focus
+    /// [`KeyboardMode::Exclusive`]. Some compositors refuse exclusive focus, so overlay is never
+    /// configured or never gets keyboard. It asks for [`KeyboardMode::OnDemand`] focus then,
+    /// overlay is created again if it is not configured.
+    pub fn await_overlay(&mut self, keyboard: KeyboardMode) -> Result<(), Error> {
+        if keyboard != KeyboardMode::Exclusive {
+            return Ok(());
+        }
+
+        let deadline = Instant::now() + OVERLAY_DEADLINE;
+        let ready = |app: &WaylandApp| {
+            let ctx = app.ctx.full().expect("overlay is created in full context");
+            // Without keyboard focus can't come
+            ctx.configured && (ctx.keyboard_entered || ctx.keyboard.is_none())
+        };
+        while !ready(&self.app) {
+            if let StatePhase::Done = self.app.state.current_phase() {
+                return Ok(());
+            }
+            if !self.dispatch_before(deadline)? {
+                break;
+            }
+        }
+        if ready(&self.app) {
+            return Ok(());
+        }
+
+        let qh = &self.qh;
+        let ctx = self.app.ctx.full_mut().expect("checked above");
+        if ctx.configured {
+            log::warn!(
+                "overlay got no keyboard focus in {OVERLAY_DEADLINE:?}, asking for on-demand focus instead of exclusive"
+            );
+            ctx.layer
+                .set_keyboard_interactivity(KeyboardInteractivity::OnDemand);
+        } else {
+            log::warn!(
+                "overlay is not configured in {OVERLAY_DEADLINE:?}, creating it again with on-demand keyboard focus instead of exclusive"
+            );
+            let output = ctx.partial.base.output.clone();
+            ctx.layer = create_overlay(
+                qh,
+                &ctx.compositor,
+                &ctx.layer_shell,
+                output.as_ref(),
+                &ctx.partial.logical_size,
+                KeyboardInteractivity::OnDemand,
+            );
+        }
+        ctx.layer.commit();
+
         Ok(())
     }
 
@@ -365,85 +953,522 @@ impl WaylandAppManager {
                 .into()
             }
             AppState::SelectionApp(_prev) => panic!("there no next app after selection app"),
+            AppState::OutputPickerApp(_prev) => {
+                panic!("output picker returns to base app in `pick_output`")
+            }
         };
+        log::debug!(
+            "switched to {}",
+            match self.app.state {
+                AppState::BaseApp(_) => "base app",
+                AppState::OutputPickerApp(_) => "output picker app",
+                AppState::ScreenshotApp(_) => "screenshot app",
+                AppState::SelectionApp(_) => "selection app",
+            }
+        );
 
         Ok(())
     }
 
-    pub fn dispatch_until_done(&mut self) -> Result<(), Error> {
-        while let StatePhase::Active = self.app.state.current_phase() {
-            self.event_queue
-                .blocking_dispatch(&mut self.app)
-                .map_err(Error::Dispatch)?;
+    /// Destroy overlay of region selector and release input bound for it, so screen returns to
+    /// normal before captured image is cropped and encoded. Waits until compositor handles it.
+    /// Does nothing if full context is not initialized.
+    pub fn destroy_overlay(&mut self) -> Result<(), Error> {
+        let start = Instant::now();
+        let mut full =
+            match std::mem::replace(&mut self.app.ctx, WaylandContext(WaylandContextKind::__Nil)) {
+                WaylandContext(WaylandContextKind::Full(full)) => full,
+                ctx => {
+                    self.app.ctx = ctx;
+                    return Ok(());
+                }
+            };
+        // Releasing requires version 3 of seat, otherwise input is destroyed with connection
+        if let Some(keyboard) = full.keyboard.take().filter(|k| k.version() >= 3) {
+            keyboard.release();
+        }
+        if let Some(pointer) = full.pointer.take().filter(|p| p.version() >= 3) {
+            pointer.release();
         }
+        if let Some(touch) = full.touch.take().filter(|t| t.version() >= 3) {
+            touch.release();
+        }
+        if let Some((device, _)) = full.cursor.take() {
+            device.destroy();
+        }
+        if let Some(tool) = full.tablet_tool.take() {
+            tool.leave();
+        }
+        if let Some(tablet_seat) = full.tablet_seat.take() {
+            tablet_seat.destroy();
+        }
+        if let Some(tablet_manager) = full.tablet_manager.take() {
+            tablet_manager.destroy();
+        }
+        // Layer surface is destroyed with the rest of full context
+        self.app.ctx = WaylandContext(WaylandContextKind::Partial(full.partial));
+        self.event_queue
+            .roundtrip(&mut self.app)
+            .map_err(Error::Dispatch)?;
+        log::debug!("overlay destroyed in {:?}", start.elapsed());
 
         Ok(())
     }
-}
 
-#[derive(Debug)]
-pub enum Error {
-    Zwlr(BindError),
-    Compositor(BindError),
-    LayerShell(BindError),
-    Shm(BindError),
-    CreatePool(CreatePoolError),
-    Global(GlobalError),
-    Dispatch(DispatchError),
-    Connect(ConnectError),
-    NoOutput,
-    NoOutputInfo,
-    NoOutputLogicalSize,
-}
+    /// Return to base context and app after capture, whether it is finished or failed, so
+    /// another capture can be made on the same connection. Output to capture is the first one
+    /// again.
+    pub fn reset(&mut self) -> Result<(), Error> {
+        self.destroy_overlay()?;
+        self.app.state = AppState::BaseApp(BaseApp);
+        self.app.ctx =
+            match std::mem::replace(&mut self.app.ctx, WaylandContext(WaylandContextKind::__Nil)) {
+                // Pool is sized for captured output, it is allocated again for the next one
+                WaylandContext(WaylandContextKind::Partial(partial)) => {
+                    WaylandContext(WaylandContextKind::Base(partial.base))
+                }
+                ctx => ctx,
+            };
 
-impl<U> Dispatch<ZwlrScreencopyManagerV1, U> for WaylandApp {
-    fn event(
-        _state: &mut Self,
-        _proxy: &ZwlrScreencopyManagerV1,
-        _event: <ZwlrScreencopyManagerV1 as wayland_client::Proxy>::Event,
-        _data: &U,
-        _conn: &Connection,
-        _qhandle: &QueueHandle<Self>,
-    ) {
-    }
-}
+        let base = self.app.ctx.base_mut();
+        base.output = base.output_state.outputs().find(|o| {
+            base.output_state
+                .info(o)
+                .is_some_and(|i| i.logical_size.is_some())
+        });
 
-impl<U> Dispatch<ZwlrScreencopyFrameV1, U> for WaylandApp {
-    fn event(
-        state: &mut Self,
-        proxy: &ZwlrScreencopyFrameV1,
-        event: <ZwlrScreencopyFrameV1 as wayland_client::Proxy>::Event,
-        data: &U,
-        conn: &Connection,
-        qh: &QueueHandle<Self>,
-    ) {
-        state
-            .state
-            .zwlr_screencopy_frame_event(&mut state.ctx, proxy, event, data, conn, qh);
+        Ok(())
     }
-}
 
-impl KeyboardHandler for WaylandApp {
-    fn enter(
-        &mut self,
-        _: &Connection,
-        _: &QueueHandle<Self>,
-        _: &wl_keyboard::WlKeyboard,
-        _surface: &wl_surface::WlSurface,
-        _: u32,
-        _: &[u32],
-        _keysyms: &[Keysym],
-    ) {
+    /// Let user make another selection on the same overlay after completed one is taken, see
+    /// [`SelectionApp::restart`]. Does nothing if region selector is not running.
+    pub fn restart_selection(&mut self, fresh: bool) {
+        if let AppState::SelectionApp(app) = &mut self.app.state {
+            app.restart(&mut self.app.ctx, fresh, &self.qh);
+        }
     }
 
-    fn leave(
-        &mut self,
-        _: &Connection,
+    /// Dispatch events until app is done. Transient dispatch errors (see [`is_transient`]) are
+    /// retried a few times with backoff, others are returned immediately.
+    pub fn dispatch_until_done(&mut self) -> Result<(), Error> {
+        let start = Instant::now();
+        let mut retries = 0;
+        while let StatePhase::Active = self.app.state.current_phase() {
+            match self.event_queue.blocking_dispatch(&mut self.app) {
+                Ok(_) => retries = 0,
+                Err(e) if retry_dispatch(&e, &mut retries) => (),
+                Err(e) => return Err(Error::Dispatch(e)),
+            }
+        }
+        log::debug!("dispatch: {:?} elapsed", start.elapsed());
+
+        Ok(())
+    }
+
+    /// Same as [`Self::dispatch_until_done`], but returns [`Error::Timeout`] if app is still
+    /// active after `deadline`.
+    pub fn dispatch_until_done_or(&mut self, deadline: Instant) -> Result<(), Error> {
+        let start = Instant::now();
+        let mut retries = 0;
+        while let StatePhase::Active = self.app.state.current_phase() {
+            match self.dispatch_before(deadline) {
+                Ok(true) => retries = 0,
+                Ok(false) => {
+                    log::debug!("dispatch: timed out after {:?}", start.elapsed());
+                    return Err(Error::Timeout);
+                }
+                Err(Error::Dispatch(e)) if retry_dispatch(&e, &mut retries) => (),
+                Err(e) => return Err(e),
+            }
+        }
+        log::debug!("dispatch: {:?} elapsed", start.elapsed());
+
+        Ok(())
+    }
+
+    /// Dispatch events until one of `fds` is readable (or hung up) and return its index, so
+    /// outputs stay up to date while nothing is captured.
+    pub fn dispatch_until_readable(&mut self, fds: &[BorrowedFd<'_>]) -> Result<usize, Error> {
+        loop {
+            self.event_queue
+                .dispatch_pending(&mut self.app)
+                .map_err(Error::Dispatch)?;
+            self.event_queue
+                .flush()
+                .map_err(|e| Error::Dispatch(e.into()))?;
+
+            let Some(guard) = self.event_queue.prepare_read() else {
+                // Events were queued in the meantime
+                continue;
+            };
+
+            let ready = {
+                let conn_fd = guard.connection_fd();
+                let mut poll_fds: Vec<_> = fds
+                    .iter()
+                    .map(|fd| PollFd::new(fd, PollFlags::IN))
+                    .collect();
+                poll_fds.push(PollFd::new(&conn_fd, PollFlags::IN | PollFlags::ERR));
+                match rustix::event::poll(&mut poll_fds, -1) {
+                    Ok(_) => (),
+                    Err(rustix::io::Errno::INTR) => continue,
+                    Err(e) => return Err(Error::Dispatch(WaylandError::Io(e.into()).into())),
+                }
+                poll_fds[..fds.len()]
+                    .iter()
+                    .position(|fd| !fd.revents().is_empty())
+            };
+            if let Some(i) = ready {
+                return Ok(i);
+            }
+
+            match guard.read() {
+                Ok(_) => (),
+                Err(WaylandError::Io(e)) if e.kind() == std::io::ErrorKind::WouldBlock => (),
+                Err(e) => return Err(Error::Dispatch(e.into())),
+            }
+        }
+    }
+
+    /// Dispatch events, waiting for them no longer than until `deadline`. Returns `false` if
+    /// deadline was reached before any events arrived.
+    fn dispatch_before(&mut self, deadline: Instant) -> Result<bool, Error> {
+        let dispatched = self
+            .event_queue
+            .dispatch_pending(&mut self.app)
+            .map_err(Error::Dispatch)?;
+        if dispatched > 0 {
+            return Ok(true);
+        }
+
+        self.event_queue
+            .flush()
+            .map_err(|e| Error::Dispatch(e.into()))?;
+
+        let Some(guard) = self.event_queue.prepare_read() else {
+            // Events were queued in the meantime
+            return Ok(true);
+        };
+
+        let timeout = deadline.saturating_duration_since(Instant::now());
+        let timeout = i32::try_from(timeout.as_millis()).unwrap_or(i32::MAX);
+        if timeout == 0 {
+            return Ok(false);
+        }
+
+        let fd = guard.connection_fd();
+        let mut fds = [PollFd::new(&fd, PollFlags::IN | PollFlags::ERR)];
+        match rustix::event::poll(&mut fds, timeout) {
+            Ok(0) => return Ok(false),
+            Ok(_) => (),
+            Err(rustix::io::Errno::INTR) => return Ok(true),
+            Err(e) => return Err(Error::Dispatch(WaylandError::Io(e.into()).into())),
+        }
+
+        match guard.read() {
+            Ok(_) => (),
+            Err(WaylandError::Io(e)) if e.kind() == std::io::ErrorKind::WouldBlock => (),
+            Err(e) => return Err(Error::Dispatch(e.into())),
+        }
+
+        self.event_queue
+            .dispatch_pending(&mut self.app)
+            .map_err(Error::Dispatch)?;
+
+        Ok(true)
+    }
+}
+
+/// How long overlay asking for exclusive keyboard focus may wait for configure and focus, see
+/// [`WaylandAppManager::await_overlay`].
+const OVERLAY_DEADLINE: Duration = Duration::from_secs(1);
+
+/// Overlay layer surface of `size` on `output` with `keyboard` interactivity. It is not
+/// committed yet.
+fn create_overlay(
+    qh: &QueueHandle<WaylandApp>,
+    compositor: &CompositorState,
+    layer_shell: &LayerShell,
+    output: Option<&wl_output::WlOutput>,
+    size: &Point,
+    keyboard: KeyboardInteractivity,
+) -> LayerSurface {
+    let layer = layer_shell.create_layer_surface(
+        qh,
+        compositor.create_surface(qh),
+        Layer::Overlay,
+        Some("prtsc-wayland"),
+        output,
+    );
+    layer.set_anchor(Anchor::all());
+    layer.set_exclusive_zone(-1);
+    layer.set_keyboard_interactivity(keyboard);
+    layer.set_size(size.x, size.y);
+
+    layer
+}
+
+/// Transient dispatch errors in a row which are retried before giving up.
+const DISPATCH_RETRIES: u32 = 3;
+/// Delay before the first retry of dispatch, it is doubled for every next one.
+const DISPATCH_BACKOFF: Duration = Duration::from_millis(20);
+
+/// Whether dispatch failed with `e` may succeed if repeated. Socket reads may be interrupted or
+/// come up empty while compositor reconfigures outputs (e.g. monitor hotplug), but protocol errors
+/// and malformed messages kill the connection.
+fn is_transient(e: &DispatchError) -> bool {
+    match e {
+        DispatchError::Backend(WaylandError::Io(e)) => matches!(
+            e.kind(),
+            std::io::ErrorKind::Interrupted
+                | std::io::ErrorKind::WouldBlock
+                | std::io::ErrorKind::TimedOut
+        ),
+        _ => false,
+    }
+}
+
+/// Sleep before retrying dispatch failed with `e` and count the retry. Returns `false` if `e`
+/// is not transient or there were too many `retries` in a row.
+fn retry_dispatch(e: &DispatchError, retries: &mut u32) -> bool {
+    if !is_transient(e) || *retries >= DISPATCH_RETRIES {
+        return false;
+    }
+
+    let delay = DISPATCH_BACKOFF * 2u32.pow(*retries);
+    log::debug!("dispatch failed: {e}, retrying in {delay:?}");
+    std::thread::sleep(delay);
+    *retries += 1;
+
+    true
+}
+
+#[derive(Debug)]
+pub enum Error {
+    /// Neither wlr screencopy nor ext image copy capture is supported, error is of wlr one
+    Capture(BindError),
+    Compositor(BindError),
+    LayerShell(BindError),
+    Shm(BindError),
+    CreatePool(CreatePoolError),
+    Global(GlobalError),
+    Dispatch(DispatchError),
+    Connect(ConnectError),
+    NoOutput,
+    NoOutputInfo,
+    NoOutputLogicalSize,
+    /// There is no output with requested name
+    UnknownOutput(String),
+    /// There is no seat with requested name
+    UnknownSeat(String),
+    /// Compositor failed to copy output
+    CaptureFailed,
+    /// Requested region does not fit into output
+    InvalidRegion,
+    /// User canceled interactive selection
+    Canceled,
+    /// Interactive selection was not completed in time
+    Timeout,
+    /// Windows of compositor are not known, it has no supported IPC
+    NoWindowSource,
+    /// Compositor IPC failed or nothing is focused
+    NoActiveWindow,
+}
+
+impl<U> Dispatch<ZwlrScreencopyManagerV1, U> for WaylandApp {
+    fn event(
+        _state: &mut Self,
+        _proxy: &ZwlrScreencopyManagerV1,
+        _event: <ZwlrScreencopyManagerV1 as wayland_client::Proxy>::Event,
+        _data: &U,
+        _conn: &Connection,
+        _qhandle: &QueueHandle<Self>,
+    ) {
+    }
+}
+
+impl<U> Dispatch<ZwlrScreencopyFrameV1, U> for WaylandApp {
+    fn event(
+        state: &mut Self,
+        proxy: &ZwlrScreencopyFrameV1,
+        event: <ZwlrScreencopyFrameV1 as wayland_client::Proxy>::Event,
+        _data: &U,
+        _conn: &Connection,
+        qh: &QueueHandle<Self>,
+    ) {
+        let mut forward = |event| state.state.capture_event(&mut state.ctx, event, qh);
+
+        match event {
+            zwlr_screencopy_frame_v1::Event::Buffer {
+                width,
+                height,
+                stride,
+                format,
+            } => {
+                forward(CaptureEvent::BufferSize {
+                    width,
+                    height,
+                    stride: Some(stride),
+                });
+                match format {
+                    wayland_client::WEnum::Value(format) => forward(CaptureEvent::Format(format)),
+                    wayland_client::WEnum::Unknown(id) => {
+                        log::debug!("screencopy buffer has unknown format {id}")
+                    }
+                }
+                // Before version 3 there is no buffer_done event and single buffer is offered
+                if proxy.version() < 3 {
+                    forward(CaptureEvent::BufferDone);
+                }
+            }
+            zwlr_screencopy_frame_v1::Event::BufferDone => forward(CaptureEvent::BufferDone),
+            zwlr_screencopy_frame_v1::Event::Flags { flags }
+                if flags
+                    .into_result()
+                    .is_ok_and(|f| f.contains(zwlr_screencopy_frame_v1::Flags::YInvert)) =>
+            {
+                forward(CaptureEvent::YInvert)
+            }
+            zwlr_screencopy_frame_v1::Event::Ready { .. } => forward(CaptureEvent::Ready),
+            zwlr_screencopy_frame_v1::Event::Failed => forward(CaptureEvent::Failed),
+            _ => (),
+        }
+    }
+}
+
+impl<U> Dispatch<ExtOutputImageCaptureSourceManagerV1, U> for WaylandApp {
+    fn event(
+        _state: &mut Self,
+        _proxy: &ExtOutputImageCaptureSourceManagerV1,
+        _event: <ExtOutputImageCaptureSourceManagerV1 as wayland_client::Proxy>::Event,
+        _data: &U,
+        _conn: &Connection,
+        _qhandle: &QueueHandle<Self>,
+    ) {
+    }
+}
+
+impl<U> Dispatch<ExtImageCaptureSourceV1, U> for WaylandApp {
+    fn event(
+        _state: &mut Self,
+        _proxy: &ExtImageCaptureSourceV1,
+        _event: <ExtImageCaptureSourceV1 as wayland_client::Proxy>::Event,
+        _data: &U,
+        _conn: &Connection,
+        _qhandle: &QueueHandle<Self>,
+    ) {
+    }
+}
+
+impl<U> Dispatch<ExtImageCopyCaptureManagerV1, U> for WaylandApp {
+    fn event(
+        _state: &mut Self,
+        _proxy: &ExtImageCopyCaptureManagerV1,
+        _event: <ExtImageCopyCaptureManagerV1 as wayland_client::Proxy>::Event,
+        _data: &U,
+        _conn: &Connection,
+        _qhandle: &QueueHandle<Self>,
+    ) {
+    }
+}
+
+impl<U> Dispatch<ExtImageCopyCaptureSessionV1, U> for WaylandApp {
+    fn event(
+        state: &mut Self,
+        _proxy: &ExtImageCopyCaptureSessionV1,
+        event: <ExtImageCopyCaptureSessionV1 as wayland_client::Proxy>::Event,
+        _data: &U,
+        _conn: &Connection,
+        qh: &QueueHandle<Self>,
+    ) {
+        let event = match event {
+            ext_image_copy_capture_session_v1::Event::BufferSize { width, height } => {
+                CaptureEvent::BufferSize {
+                    width,
+                    height,
+                    stride: None,
+                }
+            }
+            ext_image_copy_capture_session_v1::Event::ShmFormat { format } => match format {
+                wayland_client::WEnum::Value(format) => CaptureEvent::Format(format),
+                wayland_client::WEnum::Unknown(id) => {
+                    log::trace!("capture session offers unknown format {id}");
+                    return;
+                }
+            },
+            ext_image_copy_capture_session_v1::Event::Done => CaptureEvent::BufferDone,
+            ext_image_copy_capture_session_v1::Event::Stopped => {
+                log::debug!("capture session stopped");
+                CaptureEvent::Failed
+            }
+            _ => return,
+        };
+
+        state.state.capture_event(&mut state.ctx, event, qh);
+    }
+}
+
+impl<U> Dispatch<ExtImageCopyCaptureFrameV1, U> for WaylandApp {
+    fn event(
+        state: &mut Self,
+        _proxy: &ExtImageCopyCaptureFrameV1,
+        event: <ExtImageCopyCaptureFrameV1 as wayland_client::Proxy>::Event,
+        _data: &U,
+        _conn: &Connection,
+        qh: &QueueHandle<Self>,
+    ) {
+        let event = match event {
+            ext_image_copy_capture_frame_v1::Event::Ready => CaptureEvent::Ready,
+            ext_image_copy_capture_frame_v1::Event::Failed { reason } => {
+                log::debug!("capture frame failed: {reason:?}");
+                CaptureEvent::Failed
+            }
+            ext_image_copy_capture_frame_v1::Event::Transform { transform } => {
+                log::trace!("capture frame transform: {transform:?}");
+                return;
+            }
+            _ => return,
+        };
+
+        state.state.capture_event(&mut state.ctx, event, qh);
+    }
+}
+
+impl KeyboardHandler for WaylandApp {
+    fn enter(
+        &mut self,
+        _: &Connection,
         _: &QueueHandle<Self>,
         _: &wl_keyboard::WlKeyboard,
-        _surface: &wl_surface::WlSurface,
+        surface: &wl_surface::WlSurface,
         _: u32,
+        _: &[u32],
+        _keysyms: &[Keysym],
     ) {
+        if let Some(ctx) = self.ctx.full_mut() {
+            if surface == ctx.layer.wl_surface() {
+                ctx.keyboard_entered = true;
+            }
+        }
+    }
+
+    fn leave(
+        &mut self,
+        _: &Connection,
+        qh: &QueueHandle<Self>,
+        _: &wl_keyboard::WlKeyboard,
+        surface: &wl_surface::WlSurface,
+        _: u32,
+    ) {
+        let Some(ctx) = self.ctx.full() else {
+            return;
+        };
+        if surface != ctx.layer.wl_surface() {
+            return;
+        }
+
+        log::debug!("overlay lost keyboard focus");
+        self.state.on_focus_lost(&mut self.ctx, qh);
     }
 
     fn press_key(
@@ -454,6 +1479,10 @@ impl KeyboardHandler for WaylandApp {
         _: u32,
         event: KeyEvent,
     ) {
+        // Keys pressed right before overlay is destroyed may arrive after state is done
+        if let StatePhase::Done = self.state.current_phase() {
+            return;
+        }
         self.state.on_key_press(&mut self.ctx, event, qh);
     }
 
@@ -465,6 +1494,9 @@ impl KeyboardHandler for WaylandApp {
         _: u32,
         event: KeyEvent,
     ) {
+        if let StatePhase::Done = self.state.current_phase() {
+            return;
+        }
         self.state.on_key_release(&mut self.ctx, event, qh);
     }
 
@@ -474,9 +1506,12 @@ impl KeyboardHandler for WaylandApp {
         _qh: &QueueHandle<Self>,
         _keyboard: &wl_keyboard::WlKeyboard,
         _serial: u32,
-        _modifiers: Modifiers,
+        modifiers: Modifiers,
         _layout: u32,
     ) {
+        if let Some(ctx) = self.ctx.full_mut() {
+            ctx.modifiers = modifiers;
+        }
     }
 }
 
@@ -491,18 +1526,51 @@ impl PointerHandler for WaylandApp {
         use PointerEventKind::*;
         for event in events {
             // Ignore events for other surfaces
-            let Some(layer) = self.ctx.full().map(|v| &v.layer) else {
-                return;
-            };
-            if &event.surface != layer.wl_surface() {
-                continue;
+            if let Some(ctx) = self.ctx.picker_mut() {
+                let Some(entry) = ctx
+                    .layers
+                    .iter()
+                    .find(|l| &event.surface == l.layer.wl_surface())
+                else {
+                    continue;
+                };
+                match event.kind {
+                    Enter { .. } => ctx.hovered = Some(entry.output.clone()),
+                    Leave { .. } => ctx.hovered = None,
+                    _ => (),
+                }
+            } else {
+                let Some(layer) = self.ctx.full().map(|v| &v.layer) else {
+                    return;
+                };
+                if &event.surface != layer.wl_surface() {
+                    continue;
+                }
             }
             let pos = Point::new(event.position.0 as PointInt, event.position.1 as PointInt);
             match event.kind {
                 Enter { serial } => {
+                    if let Some(ctx) = self.ctx.full_mut() {
+                        let device = ctx
+                            .shape_manager
+                            .as_ref()
+                            .map(|manager| manager.get_shape_device(pointer, qh));
+                        if let Some((old, _)) = std::mem::replace(
+                            &mut ctx.cursor,
+                            device.map(|device| (device, serial)),
+                        ) {
+                            old.destroy();
+                        }
+                    }
                     self.state
                         .on_mouse_enter(&mut self.ctx, pos, pointer, serial, qh);
                 }
+                Leave { .. } => {
+                    if let Some((device, _)) = self.ctx.full_mut().and_then(|ctx| ctx.cursor.take())
+                    {
+                        device.destroy();
+                    }
+                }
                 Motion { .. } => {
                     self.state.on_mouse_move(&mut self.ctx, pos, qh);
                 }
@@ -512,19 +1580,304 @@ impl PointerHandler for WaylandApp {
                 Release { button: 272, .. } => {
                     self.state.on_mouse_release(&mut self.ctx, pos, qh);
                 }
+                Press { button: 273, .. } => {
+                    self.state.on_right_press(&mut self.ctx, pos, qh);
+                }
+                Press { button: 274, .. } => {
+                    self.state.on_middle_press(&mut self.ctx, pos, qh);
+                }
+                Release { button: 274, .. } => {
+                    self.state.on_middle_release(&mut self.ctx, pos, qh);
+                }
+                // NOTE: continuous scroll (touchpads) has no steps and is ignored
+                Axis { vertical, .. } if vertical.discrete != 0 => {
+                    self.state.on_scroll(&mut self.ctx, vertical.discrete, qh);
+                }
                 _ => {}
             }
         }
     }
 }
 
+/// The first touch point acts as pointer with pressed left button.
+impl TouchHandler for WaylandApp {
+    fn down(
+        &mut self,
+        _conn: &Connection,
+        qh: &QueueHandle<Self>,
+        _touch: &wl_touch::WlTouch,
+        _serial: u32,
+        _time: u32,
+        surface: wl_surface::WlSurface,
+        id: i32,
+        position: (f64, f64),
+    ) {
+        let Some(ctx) = self.ctx.full_mut() else {
+            return;
+        };
+        if &surface != ctx.layer.wl_surface() || ctx.touch_point.is_some() {
+            return;
+        }
+
+        let pos = Point::new(position.0 as PointInt, position.1 as PointInt);
+        ctx.touch_point = Some((id, pos.clone()));
+        self.state.on_mouse_move(&mut self.ctx, pos.clone(), qh);
+        self.state.on_mouse_press(&mut self.ctx, pos, qh);
+    }
+
+    fn up(
+        &mut self,
+        _conn: &Connection,
+        qh: &QueueHandle<Self>,
+        _touch: &wl_touch::WlTouch,
+        _serial: u32,
+        _time: u32,
+        id: i32,
+    ) {
+        let Some(ctx) = self.ctx.full_mut() else {
+            return;
+        };
+        let Some((_, pos)) = ctx.touch_point.take_if(|(active, _)| *active == id) else {
+            return;
+        };
+
+        self.state.on_mouse_release(&mut self.ctx, pos, qh);
+    }
+
+    fn motion(
+        &mut self,
+        _conn: &Connection,
+        qh: &QueueHandle<Self>,
+        _touch: &wl_touch::WlTouch,
+        _time: u32,
+        id: i32,
+        position: (f64, f64),
+    ) {
+        let Some(ctx) = self.ctx.full_mut() else {
+            return;
+        };
+        let Some((_, last)) = ctx.touch_point.as_mut().filter(|(active, _)| *active == id) else {
+            return;
+        };
+
+        let pos = Point::new(position.0 as PointInt, position.1 as PointInt);
+        *last = pos.clone();
+        self.state.on_mouse_move(&mut self.ctx, pos, qh);
+    }
+
+    fn shape(
+        &mut self,
+        _conn: &Connection,
+        _qh: &QueueHandle<Self>,
+        _touch: &wl_touch::WlTouch,
+        _id: i32,
+        _major: f64,
+        _minor: f64,
+    ) {
+    }
+
+    fn orientation(
+        &mut self,
+        _conn: &Connection,
+        _qh: &QueueHandle<Self>,
+        _touch: &wl_touch::WlTouch,
+        _id: i32,
+        _orientation: f64,
+    ) {
+    }
+
+    fn cancel(&mut self, _conn: &Connection, qh: &QueueHandle<Self>, _touch: &wl_touch::WlTouch) {
+        let Some(ctx) = self.ctx.full_mut() else {
+            return;
+        };
+        if ctx.touch_point.take().is_some() {
+            self.state.on_touch_cancel(&mut self.ctx, qh);
+        }
+    }
+}
+
+impl<U> Dispatch<ZwpTabletManagerV2, U> for WaylandApp {
+    fn event(
+        _state: &mut Self,
+        _proxy: &ZwpTabletManagerV2,
+        _event: <ZwpTabletManagerV2 as wayland_client::Proxy>::Event,
+        _data: &U,
+        _conn: &Connection,
+        _qhandle: &QueueHandle<Self>,
+    ) {
+    }
+}
+
+impl<U> Dispatch<ZwpTabletSeatV2, U> for WaylandApp {
+    fn event(
+        _state: &mut Self,
+        _proxy: &ZwpTabletSeatV2,
+        _event: <ZwpTabletSeatV2 as wayland_client::Proxy>::Event,
+        _data: &U,
+        _conn: &Connection,
+        _qhandle: &QueueHandle<Self>,
+    ) {
+    }
+
+    event_created_child!(WaylandApp, ZwpTabletSeatV2, [
+        zwp_tablet_seat_v2::EVT_TABLET_ADDED_OPCODE => (ZwpTabletV2, ()),
+        zwp_tablet_seat_v2::EVT_TOOL_ADDED_OPCODE => (ZwpTabletToolV2, ()),
+        zwp_tablet_seat_v2::EVT_PAD_ADDED_OPCODE => (ZwpTabletPadV2, ()),
+    ]);
+}
+
+/// Tablet tool in proximity of overlay acts as pointer, its tip touching tablet acts as pressed
+/// left button.
+impl<U> Dispatch<ZwpTabletToolV2, U> for WaylandApp {
+    fn event(
+        state: &mut Self,
+        proxy: &ZwpTabletToolV2,
+        event: <ZwpTabletToolV2 as wayland_client::Proxy>::Event,
+        _data: &U,
+        _conn: &Connection,
+        qh: &QueueHandle<Self>,
+    ) {
+        let Some(ctx) = state.ctx.full_mut() else {
+            return;
+        };
+        if let zwp_tablet_tool_v2::Event::ProximityIn {
+            serial, surface, ..
+        } = event
+        {
+            if &surface == ctx.layer.wl_surface() && ctx.tablet_tool.is_none() {
+                let cursor = ctx.shape_manager.as_ref().map(|manager| {
+                    let device = manager.inner().get_tablet_tool_v2(proxy, qh, GlobalData);
+                    (device, serial)
+                });
+                ctx.tablet_tool = Some(TabletTool {
+                    tool: proxy.clone(),
+                    last: None,
+                    cursor,
+                });
+                state.state.on_tool_enter(&mut state.ctx, proxy, serial, qh);
+            }
+            return;
+        }
+        let Some(TabletTool { last, .. }) = ctx
+            .tablet_tool
+            .as_mut()
+            .filter(|active| &active.tool == proxy)
+        else {
+            if let zwp_tablet_tool_v2::Event::Removed = event {
+                proxy.destroy();
+            }
+            return;
+        };
+
+        match event {
+            zwp_tablet_tool_v2::Event::Motion { x, y } => {
+                let pos = Point::new(x as PointInt, y as PointInt);
+                *last = Some(pos.clone());
+                state.state.on_mouse_move(&mut state.ctx, pos, qh);
+            }
+            zwp_tablet_tool_v2::Event::Down { .. } => {
+                if let Some(pos) = last.clone() {
+                    state.state.on_mouse_press(&mut state.ctx, pos, qh);
+                }
+            }
+            zwp_tablet_tool_v2::Event::Up => {
+                if let Some(pos) = last.clone() {
+                    state.state.on_mouse_release(&mut state.ctx, pos, qh);
+                }
+            }
+            zwp_tablet_tool_v2::Event::ProximityOut => {
+                if let Some(tool) = ctx.tablet_tool.take() {
+                    tool.leave();
+                }
+            }
+            zwp_tablet_tool_v2::Event::Removed => {
+                if let Some(tool) = ctx.tablet_tool.take() {
+                    tool.leave();
+                }
+                proxy.destroy();
+            }
+            _ => (),
+        }
+    }
+}
+
+impl<U> Dispatch<ZwpTabletV2, U> for WaylandApp {
+    fn event(
+        _state: &mut Self,
+        _proxy: &ZwpTabletV2,
+        _event: <ZwpTabletV2 as wayland_client::Proxy>::Event,
+        _data: &U,
+        _conn: &Connection,
+        _qhandle: &QueueHandle<Self>,
+    ) {
+    }
+}
+
+/// Buttons, rings and strips of tablet pads are not used.
+impl<U> Dispatch<ZwpTabletPadV2, U> for WaylandApp {
+    fn event(
+        _state: &mut Self,
+        _proxy: &ZwpTabletPadV2,
+        _event: <ZwpTabletPadV2 as wayland_client::Proxy>::Event,
+        _data: &U,
+        _conn: &Connection,
+        _qhandle: &QueueHandle<Self>,
+    ) {
+    }
+
+    event_created_child!(WaylandApp, ZwpTabletPadV2, [
+        zwp_tablet_pad_v2::EVT_GROUP_OPCODE => (ZwpTabletPadGroupV2, ()),
+    ]);
+}
+
+impl<U> Dispatch<ZwpTabletPadGroupV2, U> for WaylandApp {
+    fn event(
+        _state: &mut Self,
+        _proxy: &ZwpTabletPadGroupV2,
+        _event: <ZwpTabletPadGroupV2 as wayland_client::Proxy>::Event,
+        _data: &U,
+        _conn: &Connection,
+        _qhandle: &QueueHandle<Self>,
+    ) {
+    }
+
+    event_created_child!(WaylandApp, ZwpTabletPadGroupV2, [
+        zwp_tablet_pad_group_v2::EVT_RING_OPCODE => (ZwpTabletPadRingV2, ()),
+        zwp_tablet_pad_group_v2::EVT_STRIP_OPCODE => (ZwpTabletPadStripV2, ()),
+    ]);
+}
+
+impl<U> Dispatch<ZwpTabletPadRingV2, U> for WaylandApp {
+    fn event(
+        _state: &mut Self,
+        _proxy: &ZwpTabletPadRingV2,
+        _event: <ZwpTabletPadRingV2 as wayland_client::Proxy>::Event,
+        _data: &U,
+        _conn: &Connection,
+        _qhandle: &QueueHandle<Self>,
+    ) {
+    }
+}
+
+impl<U> Dispatch<ZwpTabletPadStripV2, U> for WaylandApp {
+    fn event(
+        _state: &mut Self,
+        _proxy: &ZwpTabletPadStripV2,
+        _event: <ZwpTabletPadStripV2 as wayland_client::Proxy>::Event,
+        _data: &U,
+        _conn: &Connection,
+        _qhandle: &QueueHandle<Self>,
+    ) {
+    }
+}
+
 impl SeatHandler for WaylandApp {
     fn seat_state(&mut self) -> &mut SeatState {
-        &mut self
-            .ctx
-            .full_mut()
-            .expect("required seat_state on app without seat")
-            .seat_state
+        match &mut self.ctx.0 {
+            WaylandContextKind::Full(ctx) => &mut ctx.seat_state,
+            WaylandContextKind::Picker(ctx) => &mut ctx.seat_state,
+            _ => panic!("required seat_state on app without seat"),
+        }
     }
 
     fn new_seat(&mut self, _: &Connection, _: &QueueHandle<Self>, _: wl_seat::WlSeat) {}
@@ -536,37 +1889,33 @@ impl SeatHandler for WaylandApp {
         seat: wl_seat::WlSeat,
         capability: Capability,
     ) {
+        if let Some(ctx) = self.ctx.picker_mut() {
+            ctx.bind_input(qh, &seat, capability);
+            return;
+        }
         let Some(ctx) = self.ctx.full_mut() else {
             return;
         };
-
-        if capability == Capability::Keyboard && ctx.keyboard.is_none() {
-            let keyboard = ctx
-                .seat_state
-                .get_keyboard(qh, &seat, None)
-                .expect("Failed to create keyboard");
-            ctx.keyboard = Some(keyboard);
+        if ctx.seat_requested && ctx.seat.as_ref() != Some(&seat) {
+            return;
         }
 
-        if capability == Capability::Pointer && ctx.pointer.is_none() {
-            let pointer = ctx
-                .seat_state
-                .get_pointer(qh, &seat)
-                .expect("Failed to create pointer");
-            ctx.pointer = Some(pointer);
-        }
+        ctx.bind_input(qh, &seat, capability);
     }
 
     fn remove_capability(
         &mut self,
         _conn: &Connection,
         _: &QueueHandle<Self>,
-        _: wl_seat::WlSeat,
+        seat: wl_seat::WlSeat,
         capability: Capability,
     ) {
         let Some(ctx) = self.ctx.full_mut() else {
             return;
         };
+        if ctx.seat_requested && ctx.seat.as_ref() != Some(&seat) {
+            return;
+        }
 
         if capability == Capability::Keyboard && ctx.keyboard.is_some() {
             ctx.keyboard.take().unwrap().release();
@@ -575,6 +1924,11 @@ impl SeatHandler for WaylandApp {
         if capability == Capability::Pointer && ctx.pointer.is_some() {
             ctx.pointer.take().unwrap().release();
         }
+
+        if capability == Capability::Touch && ctx.touch.is_some() {
+            ctx.touch.take().unwrap().release();
+            ctx.touch_point = None;
+        }
     }
 
     fn remove_seat(&mut self, _: &Connection, _: &QueueHandle<Self>, _: wl_seat::WlSeat) {}
@@ -606,6 +1960,10 @@ impl CompositorHandler for WaylandApp {
         _surface: &wl_surface::WlSurface,
         _time: u32,
     ) {
+        // Callback of the last frame may be done after overlay is destroyed
+        if let StatePhase::Done = self.state.current_phase() {
+            return;
+        }
         self.state.on_redraw(&mut self.ctx, qh);
     }
 
@@ -637,11 +1995,21 @@ impl LayerShellHandler for WaylandApp {
         &mut self,
         _conn: &Connection,
         qh: &QueueHandle<Self>,
-        _layer: &LayerSurface,
-        _configure: LayerSurfaceConfigure,
+        layer: &LayerSurface,
+        configure: LayerSurfaceConfigure,
         _serial: u32,
     ) {
-        self.state.on_redraw(&mut self.ctx, qh);
+        if let Some(ctx) = self.ctx.picker_mut() {
+            if let Some(entry) = ctx.layers.iter_mut().find(|l| &l.layer == layer) {
+                entry.size = Some(configure.new_size);
+            }
+        }
+        if let Some(ctx) = self.ctx.full_mut() {
+            if &ctx.layer == layer {
+                ctx.configured = true;
+            }
+        }
+        self.state.on_configure(&mut self.ctx, qh);
         // idk what is that lol
     }
 }
@@ -677,17 +2045,19 @@ impl OutputHandler for WaylandApp {
 }
 impl ShmHandler for WaylandApp {
     fn shm_state(&mut self) -> &mut Shm {
-        &mut self
-            .ctx
-            .partial_mut()
-            .expect("required shm_state on app without ShmState")
-            .shm
+        match &mut self.ctx.0 {
+            WaylandContextKind::Partial(ctx) => &mut ctx.shm,
+            WaylandContextKind::Full(ctx) => &mut ctx.partial.shm,
+            WaylandContextKind::Picker(ctx) => &mut ctx.shm,
+            _ => panic!("required shm_state on app without ShmState"),
+        }
     }
 }
 
 delegate_seat!(WaylandApp);
 delegate_keyboard!(WaylandApp);
 delegate_pointer!(WaylandApp);
+delegate_touch!(WaylandApp);
 
 delegate_output!(WaylandApp);
 delegate_compositor!(WaylandApp);
@@ -702,3 +2072,43 @@ impl ProvidesRegistryState for WaylandApp {
     }
     registry_handlers![OutputState, SeatState];
 }
+
+#[cfg(test)]
+mod tests {
+    use std::io;
+
+    use wayland_client::{
+        backend::{protocol::ProtocolError, WaylandError},
+        DispatchError,
+    };
+
+    use super::{is_transient, retry_dispatch, DISPATCH_RETRIES};
+
+    #[test]
+    fn only_io_hiccups_are_transient() {
+        let interrupted =
+            DispatchError::Backend(WaylandError::Io(io::ErrorKind::Interrupted.into()));
+        let broken_pipe =
+            DispatchError::Backend(WaylandError::Io(io::ErrorKind::BrokenPipe.into()));
+        let protocol = DispatchError::Backend(WaylandError::Protocol(ProtocolError {
+            code: 0,
+            object_id: 3,
+            object_interface: "wl_output".into(),
+            message: "invalid object".into(),
+        }));
+
+        assert!(is_transient(&interrupted));
+        assert!(!is_transient(&broken_pipe));
+        assert!(!is_transient(&protocol));
+    }
+
+    #[test]
+    fn retries_are_limited() {
+        let e = DispatchError::Backend(WaylandError::Io(io::ErrorKind::WouldBlock.into()));
+        let mut retries = DISPATCH_RETRIES - 1;
+
+        assert!(retry_dispatch(&e, &mut retries));
+        assert!(!retry_dispatch(&e, &mut retries));
+        assert_eq!(retries, DISPATCH_RETRIES);
+    }
+}
@@ -0,0 +1,152 @@
+//! Tiny bitmap font for text drawn over the frozen screen.
+
+/// Width of glyph in font pixels
+pub const GLYPH_WIDTH: u32 = 3;
+/// Height of glyph in font pixels
+pub const GLYPH_HEIGHT: u32 = 5;
+/// Every font pixel is drawn as `SCALE`x`SCALE` square
+pub const SCALE: u32 = 2;
+/// Space between glyphs and around text, in screen pixels
+pub const SPACING: u32 = 2;
+
+/// Rows of glyph, top to bottom. Highest of three bits is the leftmost pixel.
+fn glyph(c: char) -> [u8; GLYPH_HEIGHT as usize] {
+    match c {
+        '0' => [0b111, 0b101, 0b101, 0b101, 0b111],
+        '1' => [0b010, 0b110, 0b010, 0b010, 0b111],
+        '2' => [0b111, 0b001, 0b111, 0b100, 0b111],
+        '3' => [0b111, 0b001, 0b111, 0b001, 0b111],
+        '4' => [0b101, 0b101, 0b111, 0b001, 0b001],
+        '5' => [0b111, 0b100, 0b111, 0b001, 0b111],
+        '6' => [0b111, 0b100, 0b111, 0b101, 0b111],
+        '7' => [0b111, 0b001, 0b010, 0b010, 0b010],
+        '8' => [0b111, 0b101, 0b111, 0b101, 0b111],
+        '9' => [0b111, 0b101, 0b111, 0b001, 0b111],
+        'x' => [0b000, 0b101, 0b010, 0b101, 0b000],
+        ',' => [0b000, 0b000, 0b000, 0b010, 0b100],
+        '-' => [0b000, 0b000, 0b111, 0b000, 0b000],
+        'A' => [0b010, 0b101, 0b111, 0b101, 0b101],
+        'B' => [0b110, 0b101, 0b110, 0b101, 0b110],
+        'C' => [0b011, 0b100, 0b100, 0b100, 0b011],
+        'D' => [0b110, 0b101, 0b101, 0b101, 0b110],
+        'E' => [0b111, 0b100, 0b110, 0b100, 0b111],
+        'F' => [0b111, 0b100, 0b110, 0b100, 0b100],
+        'G' => [0b011, 0b100, 0b101, 0b101, 0b011],
+        'H' => [0b101, 0b101, 0b111, 0b101, 0b101],
+        'I' => [0b111, 0b010, 0b010, 0b010, 0b111],
+        'J' => [0b001, 0b001, 0b001, 0b101, 0b010],
+        'K' => [0b101, 0b101, 0b110, 0b101, 0b101],
+        'L' => [0b100, 0b100, 0b100, 0b100, 0b111],
+        'M' => [0b101, 0b111, 0b111, 0b101, 0b101],
+        'N' => [0b110, 0b101, 0b101, 0b101, 0b101],
+        'O' => [0b010, 0b101, 0b101, 0b101, 0b010],
+        'P' => [0b110, 0b101, 0b110, 0b100, 0b100],
+        'Q' => [0b010, 0b101, 0b101, 0b110, 0b011],
+        'R' => [0b110, 0b101, 0b110, 0b101, 0b101],
+        'S' => [0b011, 0b100, 0b010, 0b001, 0b110],
+        'T' => [0b111, 0b010, 0b010, 0b010, 0b010],
+        'U' => [0b101, 0b101, 0b101, 0b101, 0b111],
+        'V' => [0b101, 0b101, 0b101, 0b101, 0b010],
+        'W' => [0b101, 0b101, 0b111, 0b111, 0b101],
+        'X' => [0b101, 0b101, 0b010, 0b101, 0b101],
+        'Y' => [0b101, 0b101, 0b010, 0b010, 0b010],
+        'Z' => [0b111, 0b001, 0b010, 0b100, 0b111],
+        // Lowercase letters other than `x` (used in sizes) are drawn as uppercase
+        c if c.is_ascii_lowercase() => glyph(c.to_ascii_uppercase()),
+        _ => [0; GLYPH_HEIGHT as usize],
+    }
+}
+
+/// Size of `text` box (with padding) in screen pixels.
+pub fn text_size(text: &str) -> (u32, u32) {
+    text_size_scaled(text, SCALE)
+}
+
+/// Size of `text` box drawn with [`draw_text_scaled`].
+pub fn text_size_scaled(text: &str, scale: u32) -> (u32, u32) {
+    let chars = text.chars().count() as u32;
+    let spacing = spacing(scale);
+
+    (
+        chars * (GLYPH_WIDTH * scale + spacing) + spacing,
+        GLYPH_HEIGHT * scale + spacing * 2,
+    )
+}
+
+/// Space between glyphs drawn with `scale`, it grows with glyphs.
+fn spacing(scale: u32) -> u32 {
+    SPACING * scale / SCALE
+}
+
+/// Draw white `text` on black box with top left corner at (`x`, `y`) into Xrgb8888 `canvas`
+/// with `width` pixels per row. Box must fit into canvas, see [`text_size`].
+pub fn draw_text(text: &str, x: u32, y: u32, canvas: &mut [u8], width: u32) {
+    draw_text_scaled(text, x, y, SCALE, canvas, width)
+}
+
+/// Same as [`draw_text`], but every font pixel is drawn as `scale`x`scale` square. Box must fit
+/// into canvas, see [`text_size_scaled`].
+pub fn draw_text_scaled(text: &str, x: u32, y: u32, scale: u32, canvas: &mut [u8], width: u32) {
+    let (box_width, box_height) = text_size_scaled(text, scale);
+    let spacing = spacing(scale);
+    for row in y..y + box_height {
+        let start = (row * width + x) as usize * 4;
+        canvas[start..start + box_width as usize * 4].fill(0);
+    }
+
+    for (i, c) in text.chars().enumerate() {
+        let glyph_x = x + spacing + i as u32 * (GLYPH_WIDTH * scale + spacing);
+        let glyph_y = y + spacing;
+
+        for (gy, bits) in glyph(c).into_iter().enumerate() {
+            for gx in 0..GLYPH_WIDTH {
+                if bits & (1 << (GLYPH_WIDTH - 1 - gx)) == 0 {
+                    continue;
+                }
+
+                for dy in 0..scale {
+                    let row = glyph_y + gy as u32 * scale + dy;
+                    let start = (row * width + glyph_x + gx * scale) as usize * 4;
+                    canvas[start..start + scale as usize * 4].fill(255);
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{draw_text, glyph, text_size, text_size_scaled, SCALE, SPACING};
+
+    #[test]
+    fn text_is_drawn_inside_box() {
+        let (width, height) = text_size("1,0");
+        assert_eq!((width, height), (26, 14));
+
+        // One pixel of margin around the box
+        let canvas_width = width + 2;
+        let mut canvas = vec![100; (canvas_width * (height + 2) * 4) as usize];
+        draw_text("1,0", 1, 1, &mut canvas, canvas_width);
+
+        let pixel = |x: u32, y: u32| canvas[((y * canvas_width + x) * 4) as usize];
+        // Margin is untouched, padding is black
+        assert_eq!(pixel(0, 0), 100);
+        assert_eq!(pixel(width + 1, height + 1), 100);
+        assert_eq!(pixel(1, 1), 0);
+        // Top row of '1' is `.#.`
+        let glyph = 1 + SPACING;
+        assert_eq!(pixel(glyph, glyph), 0);
+        assert_eq!(pixel(glyph + SCALE, glyph), 255);
+        assert_eq!(pixel(glyph + SCALE * 2 + 1, glyph + 1), 0);
+    }
+
+    #[test]
+    fn output_names_are_drawable() {
+        assert_eq!(glyph('e'), glyph('E'));
+        assert_ne!(glyph('x'), glyph('X'));
+        assert!("HDMI-A-1".chars().all(|c| glyph(c) != [0; 5]));
+
+        assert_eq!(text_size_scaled("1,0", SCALE), text_size("1,0"));
+        assert_eq!(text_size_scaled("A", 8), (3 * 8 + 8 * 2, 5 * 8 + 8 * 2));
+    }
+}
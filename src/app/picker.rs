@@ -0,0 +1,224 @@
+use smithay_client_toolkit::{
+    seat::keyboard::{KeyEvent, Keysym},
+    shell::WaylandSurface,
+    shm::slot::Buffer,
+};
+use wayland_client::{
+    globals::GlobalList,
+    protocol::{wl_output::WlOutput, wl_shm},
+    EventQueue, QueueHandle,
+};
+
+use super::{
+    font, StatePhase, WaylandApp, WaylandAppState, WaylandAppStateFromPrevious, WaylandContext,
+};
+use crate::points::Point;
+
+/// Overlay is dark translucent, so it is clear which output is which
+const BACKGROUND: [u8; 4] = [0, 0, 0, 0xa0];
+
+/// Overlay on every output with its letter and name, user picks output to capture with click or
+/// letter key.
+pub struct OutputPickerApp {
+    /// Output chosen by user
+    pub picked: Option<WlOutput>,
+    canceled: bool,
+    /// Buffers attached to overlays and their sizes, in order of
+    /// [`super::WaylandContextPicker::layers`]
+    buffers: Vec<Option<(Buffer, (u32, u32))>>,
+}
+
+impl WaylandAppStateFromPrevious for OutputPickerApp {
+    type Previous = super::base::BaseApp;
+
+    fn from_previous(
+        ctx: &mut WaylandContext,
+        _: Self::Previous,
+        _globals: &GlobalList,
+        _event_queue: &mut EventQueue<WaylandApp>,
+    ) -> Result<Self, super::Error> {
+        let layers = ctx
+            .picker()
+            .expect("OutputPickerApp requires picker context")
+            .layers
+            .len();
+
+        Ok(Self {
+            picked: None,
+            canceled: false,
+            buffers: (0..layers).map(|_| None).collect(),
+        })
+    }
+}
+
+impl WaylandAppState for OutputPickerApp {
+    fn current_phase(&self) -> StatePhase {
+        if self.picked.is_some() || self.canceled {
+            StatePhase::Done
+        } else {
+            StatePhase::Active
+        }
+    }
+
+    fn on_mouse_press(
+        &mut self,
+        ctx: &mut WaylandContext,
+        _pos: Point,
+        _qh: &QueueHandle<WaylandApp>,
+    ) {
+        self.picked = ctx
+            .picker()
+            .expect("OutputPickerApp requires picker context")
+            .hovered
+            .clone();
+    }
+
+    fn on_right_press(
+        &mut self,
+        _ctx: &mut WaylandContext,
+        _pos: Point,
+        _qh: &QueueHandle<WaylandApp>,
+    ) {
+        self.canceled = true;
+    }
+
+    fn on_key_press(
+        &mut self,
+        ctx: &mut WaylandContext,
+        event: KeyEvent,
+        _qh: &QueueHandle<WaylandApp>,
+    ) {
+        if event.keysym == Keysym::Escape {
+            self.canceled = true;
+            return;
+        }
+
+        let Some(index) = event
+            .utf8
+            .and_then(|s| s.chars().next())
+            .and_then(|c| letter_index(c.to_ascii_uppercase()))
+        else {
+            return;
+        };
+        let ctx = ctx
+            .picker()
+            .expect("OutputPickerApp requires picker context");
+        self.picked = ctx.layers.get(index).map(|l| l.output.clone());
+    }
+
+    fn on_configure(&mut self, ctx: &mut WaylandContext, _qh: &QueueHandle<WaylandApp>) {
+        let ctx = ctx
+            .picker_mut()
+            .expect("OutputPickerApp requires picker context");
+
+        for (i, entry) in ctx.layers.iter().enumerate() {
+            let Some((width, height)) = entry.size.filter(|&(w, h)| w > 0 && h > 0) else {
+                continue;
+            };
+            // Overlay is redrawn only if its size is changed
+            if self.buffers[i]
+                .as_ref()
+                .is_some_and(|(_, size)| *size == (width, height))
+            {
+                continue;
+            }
+
+            let name = ctx
+                .base
+                .output_state
+                .info(&entry.output)
+                .and_then(|info| info.name)
+                .unwrap_or_default();
+            let label = match letter(i) {
+                Some(letter) => format!("{letter} {name}"),
+                None => name,
+            };
+
+            let (buffer, canvas) = ctx
+                .pool
+                .create_buffer(
+                    width as i32,
+                    height as i32,
+                    width as i32 * 4,
+                    wl_shm::Format::Argb8888,
+                )
+                .expect("failed to create buffer format argb8888");
+            draw_overlay(&label, canvas, width, height);
+
+            let surface = entry.layer.wl_surface();
+            surface.damage_buffer(0, 0, width as i32, height as i32);
+            buffer.attach_to(surface).expect("buffer attach");
+            entry.layer.commit();
+            self.buffers[i] = Some((buffer, (width, height)));
+        }
+    }
+}
+
+/// Letter of `index`-th output, [`None`] if there are more outputs than letters.
+fn letter(index: usize) -> Option<char> {
+    (b'A'..=b'Z').nth(index).map(char::from)
+}
+
+/// Index of output with uppercase `letter`.
+fn letter_index(letter: char) -> Option<usize> {
+    letter
+        .is_ascii_uppercase()
+        .then(|| (letter as u8 - b'A') as usize)
+}
+
+/// Fill Argb8888 `canvas` of `width`x`height` overlay with translucent background and draw
+/// opaque `label` in its center, as large as it fits into half of width.
+fn draw_overlay(label: &str, canvas: &mut [u8], width: u32, height: u32) {
+    for pixel in canvas.chunks_exact_mut(4) {
+        pixel.copy_from_slice(&BACKGROUND);
+    }
+
+    let chars = label.chars().count().max(1) as u32;
+    let fits = |scale: u32| {
+        let (w, h) = font::text_size_scaled(label, scale);
+        w <= width && h <= height
+    };
+    let Some(scale) = (1..=(width / 2 / (chars * 4 + 1)).clamp(1, 16))
+        .rev()
+        .find(|&scale| fits(scale))
+    else {
+        return;
+    };
+
+    let (label_width, label_height) = font::text_size_scaled(label, scale);
+    let (x, y) = ((width - label_width) / 2, (height - label_height) / 2);
+    font::draw_text_scaled(label, x, y, scale, canvas, width);
+
+    // Font draws Xrgb8888, make label box opaque
+    for row in y..y + label_height {
+        let start = (row * width + x) as usize * 4;
+        for pixel in canvas[start..start + label_width as usize * 4].chunks_exact_mut(4) {
+            pixel[3] = 0xff;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{draw_overlay, letter, letter_index, BACKGROUND};
+
+    #[test]
+    fn letters_match_indices() {
+        assert_eq!(letter(0), Some('A'));
+        assert_eq!(letter(2), Some('C'));
+        assert_eq!(letter(26), None);
+        assert_eq!(letter_index('C'), Some(2));
+        assert_eq!(letter_index('1'), None);
+    }
+
+    #[test]
+    fn label_is_opaque_in_center() {
+        let (width, height) = (200, 100);
+        let mut canvas = vec![0; (width * height * 4) as usize];
+        draw_overlay("A DP-1", &mut canvas, width, height);
+
+        let pixel = |x: u32, y: u32| &canvas[((y * width + x) * 4) as usize..][..4];
+        assert_eq!(pixel(0, 0), BACKGROUND);
+        assert_eq!(pixel(width / 2, height / 2)[3], 0xff);
+    }
+}
@@ -1,19 +1,119 @@
 use core::cell::Cell;
 
-use smithay_client_toolkit::shm::slot::Buffer;
-use wayland_client::{globals::GlobalList, protocol::wl_shm, Connection, EventQueue, QueueHandle};
+use smithay_client_toolkit::{
+    registry::RegistryState,
+    shm::slot::{Buffer, SlotPool},
+};
+use wayland_client::{
+    globals::{BindError, GlobalList},
+    protocol::{wl_buffer::WlBuffer, wl_output::WlOutput, wl_shm},
+    EventQueue, QueueHandle,
+};
+use wayland_protocols::ext::{
+    image_capture_source::v1::client::{
+        ext_image_capture_source_v1::ExtImageCaptureSourceV1,
+        ext_output_image_capture_source_manager_v1::ExtOutputImageCaptureSourceManagerV1,
+    },
+    image_copy_capture::v1::client::{
+        ext_image_copy_capture_frame_v1::ExtImageCopyCaptureFrameV1,
+        ext_image_copy_capture_manager_v1::{self, ExtImageCopyCaptureManagerV1},
+        ext_image_copy_capture_session_v1::ExtImageCopyCaptureSessionV1,
+    },
+};
 use wayland_protocols_wlr::screencopy::v1::client::{
-    zwlr_screencopy_frame_v1::{self, ZwlrScreencopyFrameV1},
+    zwlr_screencopy_frame_v1::ZwlrScreencopyFrameV1,
     zwlr_screencopy_manager_v1::ZwlrScreencopyManagerV1,
 };
 
+use crate::points::Rectangle;
+
 use super::{StatePhase, WaylandApp, WaylandAppState, WaylandAppStateFromPrevious};
 
 pub struct ScreenshotApp {
     pub image: Option<Box<[u8]>>,
+    /// Bytes per row of image, compositor may pad rows beyond `width * 4`
+    pub stride: usize,
+    /// Width and height of image in pixels of buffer, which are physical pixels of output
+    pub size: (u32, u32),
+    /// Compositor failed to copy frame (e.g. output disappeared), image will never be present
+    pub failed: bool,
+    /// Region of output (in logical coordinates) which image is, [`None`] if it is the whole
+    /// output
+    pub region: Option<Rectangle>,
+    /// Kept for selection, which can copy fresh frame of the same output
+    pub(super) capture: CaptureManager,
+    frame: FrameCopy,
+}
+
+/// Protocol used to copy frames of outputs.
+pub(super) enum CaptureManager {
+    /// `zwlr_screencopy_manager_v1`, supported by wlroots based compositors
+    Wlr(ZwlrScreencopyManagerV1),
+    /// `ext_image_copy_capture_manager_v1` with output capture sources, used if compositor does
+    /// not support wlr protocol
+    Ext {
+        sources: ExtOutputImageCaptureSourceManagerV1,
+        copies: ExtImageCopyCaptureManagerV1,
+    },
+}
+
+/// Single frame of output copied with [`CaptureManager`].
+pub(super) struct FrameCopy {
+    frame: Box<dyn CaptureFrame>,
     buffer: Option<Buffer>,
-    zwlr_screencopy_frame: ZwlrScreencopyFrameV1,
-    buffer_format: Option<wl_shm::Format>,
+    /// Width and height of buffer sent by compositor
+    size: Option<(u32, u32)>,
+    /// Formats offered by compositor with strides of their buffers
+    offers: Vec<(wl_shm::Format, usize)>,
+    /// Format chosen from offers once all of them are sent
+    format: Option<wl_shm::Format>,
+    stride: usize,
+    /// Compositor reported that buffer rows are bottom to top
+    y_invert: bool,
+}
+
+/// Protocol specific side of [`FrameCopy`], protocol objects are destroyed on drop.
+trait CaptureFrame {
+    /// Copy frame into `buffer` of `width`x`height` pixels, called once compositor has sent
+    /// all buffer constraints.
+    fn attach(&mut self, buffer: &WlBuffer, width: i32, height: i32);
+}
+
+struct WlrFrame(ZwlrScreencopyFrameV1);
+
+struct ExtFrame {
+    source: ExtImageCaptureSourceV1,
+    session: ExtImageCopyCaptureSessionV1,
+    frame: Option<ExtImageCopyCaptureFrameV1>,
+    qh: QueueHandle<WaylandApp>,
+}
+
+/// Event of capture protocol. Events of every protocol are translated into it in their
+/// dispatch, so [`FrameCopy`] does not depend on protocol.
+#[derive(Debug)]
+pub enum CaptureEvent {
+    /// Size of buffer for frame, with stride if protocol dictates it
+    BufferSize {
+        width: u32,
+        height: u32,
+        stride: Option<u32>,
+    },
+    /// Format of shared memory buffer supported by compositor, can be sent several times
+    Format(wl_shm::Format),
+    /// All buffer constraints are sent, frame can be copied
+    BufferDone,
+    /// Rows of copied frame are bottom to top
+    YInvert,
+    Ready,
+    Failed,
+}
+
+/// What happened with [`FrameCopy`] after capture event.
+pub(super) enum FrameStatus {
+    Pending,
+    /// Frame is copied, pixels are in Xrgb8888 format with rows of stride bytes
+    Ready(Box<[u8]>, usize),
+    Failed,
 }
 
 impl WaylandAppStateFromPrevious for ScreenshotApp {
@@ -27,97 +127,226 @@ impl WaylandAppStateFromPrevious for ScreenshotApp {
     ) -> Result<Self, super::Error> {
         let qh = event_queue.handle();
 
-        let Some(output) = ctx.base().output_state.outputs().next() else {
+        let Some(output) = ctx.base().output.clone() else {
             return Err(super::Error::NoOutput);
         };
 
-        let zwlr_screencopy_manager: ZwlrScreencopyManagerV1 = ctx
-            .base()
-            .registry_state
-            .bind_one(&qh, 1..=3, ())
-            .map_err(super::Error::Zwlr)?;
-
-        let zwlr_screencopy_frame = zwlr_screencopy_manager.capture_output(0, &output, &qh, ());
+        let capture =
+            CaptureManager::bind(&ctx.base().registry_state, &qh).map_err(super::Error::Capture)?;
+        let region = ctx.partial().and_then(|partial| partial.region.clone());
+        let (frame, region) = match region
+            .as_ref()
+            .and_then(|rect| capture.capture_region(&output, rect, &qh))
+        {
+            Some(frame) => (frame, region),
+            None => (capture.capture(&output, &qh), None),
+        };
 
         Ok(Self {
             image: None,
-            buffer: None,
-            buffer_format: None,
-            zwlr_screencopy_frame,
+            stride: 0,
+            size: (0, 0),
+            failed: false,
+            region,
+            capture,
+            frame,
         })
     }
 }
 
 impl WaylandAppState for ScreenshotApp {
     fn current_phase(&self) -> StatePhase {
-        if self.image.is_some() {
+        if self.image.is_some() || self.failed {
             StatePhase::Done
         } else {
             StatePhase::Active
         }
     }
 
-    fn zwlr_screencopy_frame_event<U>(
+    fn capture_event(
         &mut self,
         ctx: &mut super::WaylandContext,
-        _proxy: &ZwlrScreencopyFrameV1,
-        event: <ZwlrScreencopyFrameV1 as wayland_client::Proxy>::Event,
-        _data: &U,
-        _conn: &Connection,
+        event: CaptureEvent,
         _qh: &QueueHandle<WaylandApp>,
     ) {
+        let pool = &mut ctx
+            .partial_mut()
+            .expect("screenshot app requires at least partial state")
+            .pool;
+
+        match self.frame.handle_event(pool, event) {
+            FrameStatus::Pending => (),
+            FrameStatus::Ready(image, stride) => {
+                self.image = Some(image);
+                self.stride = stride;
+                self.size = self.frame.size().unwrap_or_default();
+            }
+            FrameStatus::Failed => self.failed = true,
+        }
+    }
+}
+
+impl CaptureManager {
+    /// Bind `zwlr_screencopy_manager_v1`, or ext image copy capture if compositor does not
+    /// support it. If neither is supported, error of wlr protocol is returned.
+    pub fn bind(
+        registry_state: &RegistryState,
+        qh: &QueueHandle<WaylandApp>,
+    ) -> Result<Self, BindError> {
+        let wlr_error = match registry_state.bind_one(qh, 1..=3, ()) {
+            Ok(manager) => return Ok(Self::Wlr(manager)),
+            Err(e) => e,
+        };
+        log::debug!("zwlr_screencopy_manager_v1 is not available ({wlr_error}), trying ext_image_copy_capture_manager_v1");
+
+        match (
+            registry_state.bind_one(qh, 1..=1, ()),
+            registry_state.bind_one(qh, 1..=1, ()),
+        ) {
+            (Ok(sources), Ok(copies)) => Ok(Self::Ext { sources, copies }),
+            _ => Err(wlr_error),
+        }
+    }
+
+    /// Request copy of `rect` region (in logical coordinates) of the next frame of `output`.
+    /// Returns [`None`] if protocol can copy only whole outputs.
+    pub fn capture_region(
+        &self,
+        output: &WlOutput,
+        rect: &Rectangle,
+        qh: &QueueHandle<WaylandApp>,
+    ) -> Option<FrameCopy> {
+        match self {
+            Self::Wlr(manager) => Some(FrameCopy::new(Box::new(WlrFrame(
+                manager.capture_output_region(
+                    0,
+                    output,
+                    rect.start.x as i32,
+                    rect.start.y as i32,
+                    rect.width as i32,
+                    rect.height as i32,
+                    qh,
+                    (),
+                ),
+            )))),
+            Self::Ext { .. } => {
+                log::debug!(
+                    "ext_image_copy_capture_manager_v1 can not copy region, whole output is copied"
+                );
+                None
+            }
+        }
+    }
+
+    /// Request copy of the next frame of `output`.
+    pub fn capture(&self, output: &WlOutput, qh: &QueueHandle<WaylandApp>) -> FrameCopy {
+        let frame: Box<dyn CaptureFrame> = match self {
+            Self::Wlr(manager) => Box::new(WlrFrame(manager.capture_output(0, output, qh, ()))),
+            Self::Ext { sources, copies } => {
+                let source = sources.create_source(output, qh, ());
+                let session = copies.create_session(
+                    &source,
+                    ext_image_copy_capture_manager_v1::Options::empty(),
+                    qh,
+                    (),
+                );
+                Box::new(ExtFrame {
+                    source,
+                    session,
+                    frame: None,
+                    qh: qh.clone(),
+                })
+            }
+        };
+
+        FrameCopy::new(frame)
+    }
+}
+
+impl FrameCopy {
+    fn new(frame: Box<dyn CaptureFrame>) -> Self {
+        Self {
+            frame,
+            buffer: None,
+            size: None,
+            offers: Vec::new(),
+            format: None,
+            stride: 0,
+            y_invert: false,
+        }
+    }
+
+    /// Width and height of buffer, once compositor has sent them.
+    pub fn size(&self) -> Option<(u32, u32)> {
+        self.size
+    }
+
+    /// Handle capture `event`, buffer for frame is allocated from `pool`.
+    pub fn handle_event(&mut self, pool: &mut SlotPool, event: CaptureEvent) -> FrameStatus {
         match event {
-            zwlr_screencopy_frame_v1::Event::Buffer {
+            CaptureEvent::BufferSize {
                 width,
                 height,
                 stride,
-                format,
             } => {
-                let format = match format {
-                    wayland_client::WEnum::Value(format) => format,
-                    wayland_client::WEnum::Unknown(id) => {
-                        panic!("`zwlr_screencopy_manager_v1` returned unsupported format: {id}")
+                self.size = Some((width, height));
+                self.stride = stride.unwrap_or(width * 4) as usize;
+                // Without stride in event the size applies to every offer, ext sessions may send
+                // formats before it
+                if stride.is_none() {
+                    for (_, offer_stride) in &mut self.offers {
+                        *offer_stride = self.stride;
                     }
+                }
+            }
+            CaptureEvent::Format(format) => self.offers.push((format, self.stride)),
+            CaptureEvent::BufferDone => {
+                // wlr protocol may send it again after buffer is created
+                if self.buffer.is_some() {
+                    return FrameStatus::Pending;
+                }
+                let (Some((width, height)), Some((format, stride))) =
+                    (self.size, preferred_format(&self.offers))
+                else {
+                    log::debug!(
+                        "compositor did not send size or supported format of buffer, offered: {:?}",
+                        self.offers
+                    );
+                    return FrameStatus::Failed;
                 };
-                //state.width = width;
-                //state.height = height;
-                self.buffer_format = Some(format);
-                self.buffer = Some({
-                    let (buffer, _canvas) = ctx
-                        .partial_mut()
-                        .expect("screenshot app requires at least partial state")
-                        .pool
-                        .create_buffer(width as i32, height as i32, stride as i32, format)
-                        .expect("failed to create buffer");
-
-                    self.zwlr_screencopy_frame.copy(buffer.wl_buffer());
-
-                    buffer
-                });
+                self.format = Some(format);
+                self.stride = stride;
+
+                log::trace!(
+                    "capture buffer: {format:?} {width}x{height}, stride {}",
+                    self.stride
+                );
+                let (buffer, _canvas) = pool
+                    .create_buffer(width as i32, height as i32, self.stride as i32, format)
+                    .expect("failed to create buffer");
+                self.frame
+                    .attach(buffer.wl_buffer(), width as i32, height as i32);
+                self.buffer = Some(buffer);
             }
-            zwlr_screencopy_frame_v1::Event::Ready { .. } => {
+            CaptureEvent::Ready => {
                 let buff = match &self.buffer {
                     Some(buffer) => buffer,
                     // another message: this piece of overengineering implemented by disabled
                     // people. please purge your windows manager and install some modern wayland
                     // compositors like sway or hyprland
                     None => {
-                        panic!("`zwlr_screencopy_manager_v1` send ready event without any buffers")
+                        panic!("compositor sent ready event of capture without any buffers")
                     }
                 };
 
                 let slot = buff.slot();
-                let data = ctx
-                    .partial_mut()
-                    .expect("screenshot app requires at least partial state")
-                    .pool
-                    .raw_data_mut(&slot);
+                let data = pool.raw_data_mut(&slot);
                 let mut data: Vec<u8> = Vec::from(data);
 
                 // Check for Xrgb8888 format
                 // FIXME: some formats can be supported (like rgbx or rgb) but not YET implemented.
                 // it is a good idea to convert here rgbx/rgb to xrgb.
-                match self.buffer_format {
+                match self.format {
                     Some(wl_shm::Format::Xrgb8888) | Some(wl_shm::Format::Argb8888) => (),
 
                     Some(wl_shm::Format::Xbgr8888) | Some(wl_shm::Format::Abgr8888) => {
@@ -127,12 +356,147 @@ impl WaylandAppState for ScreenshotApp {
                         }
                     },
 
-                    _ => unimplemented!("Got yet unimplemented buffer format {:?}. It is a bug, please report it to github issues", self.buffer_format),
+                    _ => unimplemented!("Got yet unimplemented buffer format {:?}. It is a bug, please report it to github issues", self.format),
                 };
 
-                self.image = Some(data.into_boxed_slice());
+                log::trace!("capture frame ready");
+                if self.y_invert {
+                    flip_rows(&mut data, self.stride);
+                }
+
+                return FrameStatus::Ready(data.into_boxed_slice(), self.stride);
             }
-            _ => {}
+            CaptureEvent::YInvert => {
+                self.y_invert = true;
+                log::trace!("capture flags: y invert");
+            }
+            CaptureEvent::Failed => {
+                log::debug!("capture failed");
+                return FrameStatus::Failed;
+            }
+        }
+
+        FrameStatus::Pending
+    }
+}
+
+/// Offer of Xrgb8888 or Argb8888, which need no conversion, or the first supported one.
+fn preferred_format(offers: &[(wl_shm::Format, usize)]) -> Option<(wl_shm::Format, usize)> {
+    offers
+        .iter()
+        .find(|(format, _)| matches!(format, wl_shm::Format::Xrgb8888 | wl_shm::Format::Argb8888))
+        .or_else(|| offers.iter().find(|(format, _)| is_supported(*format)))
+        .copied()
+}
+
+/// Formats handled by [`FrameCopy::handle_event`] when frame is ready.
+fn is_supported(format: wl_shm::Format) -> bool {
+    matches!(
+        format,
+        wl_shm::Format::Xrgb8888
+            | wl_shm::Format::Argb8888
+            | wl_shm::Format::Xbgr8888
+            | wl_shm::Format::Abgr8888
+    )
+}
+
+impl CaptureFrame for WlrFrame {
+    fn attach(&mut self, buffer: &WlBuffer, _width: i32, _height: i32) {
+        self.0.copy(buffer);
+    }
+}
+
+impl Drop for WlrFrame {
+    fn drop(&mut self) {
+        self.0.destroy();
+    }
+}
+
+impl CaptureFrame for ExtFrame {
+    fn attach(&mut self, buffer: &WlBuffer, width: i32, height: i32) {
+        let frame = self.session.create_frame(&self.qh, ());
+        frame.attach_buffer(buffer);
+        frame.damage_buffer(0, 0, width, height);
+        frame.capture();
+        self.frame = Some(frame);
+    }
+}
+
+impl Drop for ExtFrame {
+    fn drop(&mut self) {
+        if let Some(frame) = &self.frame {
+            frame.destroy();
         }
+        self.session.destroy();
+        self.source.destroy();
+    }
+}
+
+/// Drop padding at the end of rows of `stride` bytes in `image`, so every row is `row_len` bytes.
+/// Image is returned as is if rows are not padded.
+pub(super) fn pack_rows(image: Box<[u8]>, stride: usize, row_len: usize) -> Box<[u8]> {
+    if stride <= row_len {
+        return image;
+    }
+
+    image
+        .chunks_exact(stride)
+        .flat_map(|row| &row[..row_len])
+        .copied()
+        .collect()
+}
+
+/// Reverse order of rows of `stride` bytes in `data`.
+fn flip_rows(data: &mut [u8], stride: usize) {
+    let rows = data.len() / stride;
+    for row in 0..rows / 2 {
+        let (top, bottom) = data.split_at_mut((rows - row - 1) * stride);
+        top[row * stride..(row + 1) * stride].swap_with_slice(&mut bottom[..stride]);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use wayland_client::protocol::wl_shm::Format;
+
+    use super::{flip_rows, pack_rows, preferred_format};
+
+    #[test]
+    fn rows_are_flipped() {
+        let mut odd: Vec<u8> = (0..6).collect();
+        flip_rows(&mut odd, 2);
+        assert_eq!(odd, [4, 5, 2, 3, 0, 1]);
+
+        let mut even: Vec<u8> = (0..8).collect();
+        flip_rows(&mut even, 2);
+        assert_eq!(even, [6, 7, 4, 5, 2, 3, 0, 1]);
+    }
+
+    #[test]
+    fn padded_rows_are_packed() {
+        // 2x2 image with 4 bytes of padding after every row
+        let image: Box<[u8]> = (0..24).collect();
+
+        let packed = pack_rows(image, 12, 8);
+        assert_eq!(
+            &packed[..],
+            [0, 1, 2, 3, 4, 5, 6, 7, 12, 13, 14, 15, 16, 17, 18, 19]
+        );
+        assert_eq!(pack_rows(packed.clone(), 8, 8), packed);
+    }
+
+    #[test]
+    fn xrgb_is_preferred() {
+        let offers = [
+            (Format::Rgb565, 200),
+            (Format::Xbgr8888, 400),
+            (Format::Xrgb8888, 404),
+        ];
+        assert_eq!(preferred_format(&offers), Some((Format::Xrgb8888, 404)));
+        assert_eq!(
+            preferred_format(&offers[..2]),
+            Some((Format::Xbgr8888, 400))
+        );
+        assert_eq!(preferred_format(&offers[..1]), None);
     }
 }
@@ -1,24 +1,133 @@
+use std::time::{Duration, Instant};
+
+use smithay_client_toolkit::shell::WaylandSurface;
 use smithay_client_toolkit::{
     seat::keyboard::{KeyEvent, Keysym},
-    shm::slot::Buffer,
+    shm::slot::{Buffer, SlotPool},
 };
 use wayland_client::{
     globals::GlobalList,
     protocol::{wl_pointer, wl_shm},
     EventQueue, QueueHandle,
 };
-use wayland_protocols::wp::cursor_shape::v1::client::wp_cursor_shape_device_v1::Shape;
+use wayland_protocols::wp::{
+    cursor_shape::v1::client::wp_cursor_shape_device_v1::Shape,
+    tablet::zv2::client::zwp_tablet_tool_v2::ZwpTabletToolV2,
+};
 
 use crate::points::{Point, Rectangle};
 
-use super::{StatePhase, WaylandApp, WaylandAppState, WaylandAppStateFromPrevious, WaylandContext};
+use super::{
+    screenshot::{self, CaptureEvent, CaptureManager, FrameCopy, FrameStatus},
+    StatePhase, WaylandApp, WaylandAppState, WaylandAppStateFromPrevious, WaylandContext,
+};
+
+/// Duration of overlay fade in with [`SelectionOptions::animate`]
+const FADE_IN_DURATION: Duration = Duration::from_millis(120);
+/// Corners closer than this many pixels to edge of window snap to it
+const WINDOW_SNAP_DISTANCE: u32 = 12;
+/// Frames shown before marching ants move by one pixel
+const ANTS_FRAMES_PER_PIXEL: u32 = 3;
+/// Longest interval between presses of double-click, it confirms selection in edit mode
+const DOUBLE_CLICK: Duration = Duration::from_millis(400);
 
 struct SelectionData {
     pub initial: Point,
     pub current: Point,
     pub pending: Option<Point>,
 
+    /// Space is held, whole selection follows pointer
     pub is_moving: bool,
+    /// Middle mouse button is held, whole selection follows pointer
+    pub is_panning: bool,
+}
+
+impl SelectionData {
+    fn is_translating(&self) -> bool {
+        self.is_moving || self.is_panning
+    }
+}
+
+/// Selection is made but not confirmed yet, see [`SelectionOptions::confirm`] and
+/// [`SelectionOptions::edit`].
+struct AdjustingData {
+    rect: Rectangle,
+    /// Last pointer position and dragged part while mouse button is held
+    drag: Option<(Point, DragKind)>,
+    /// Overlay is fully redrawn for adjusting, later only moved area is updated (unless it is
+    /// edit mode)
+    drawn: bool,
+    /// Time of the last left button press, for double-click in edit mode
+    last_press: Option<Instant>,
+    /// Frames shown since marching ants were started, see [`SelectionOptions::march`]
+    ants: u32,
+}
+
+impl AdjustingData {
+    fn new(rect: Rectangle) -> Self {
+        Self {
+            rect,
+            drag: None,
+            drawn: false,
+            last_press: None,
+            ants: 0,
+        }
+    }
+}
+
+/// Part of adjusted selection which follows pointer.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum DragKind {
+    Move,
+    Resize(Handle),
+}
+
+/// Position of handle along one side of selection.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Edge {
+    Start,
+    /// Handle in the middle of side does not move this axis
+    Middle,
+    End,
+}
+
+/// One of eight handles drawn on border of selection in edit mode.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+struct Handle {
+    x: Edge,
+    y: Edge,
+}
+
+impl Handle {
+    /// Corners first, they win over side handles of small selections.
+    const ALL: [Self; 8] = [
+        Self::new(Edge::Start, Edge::Start),
+        Self::new(Edge::End, Edge::Start),
+        Self::new(Edge::Start, Edge::End),
+        Self::new(Edge::End, Edge::End),
+        Self::new(Edge::Middle, Edge::Start),
+        Self::new(Edge::Middle, Edge::End),
+        Self::new(Edge::Start, Edge::Middle),
+        Self::new(Edge::End, Edge::Middle),
+    ];
+
+    const fn new(x: Edge, y: Edge) -> Self {
+        Self { x, y }
+    }
+
+    /// Center of handle on border of `rect`.
+    fn position(self, rect: &Rectangle) -> Point {
+        let place = |edge, start: u32, len: u32| match edge {
+            Edge::Start => start,
+            Edge::Middle => start + len / 2,
+            Edge::End => start + len - 1,
+        };
+
+        Point::new(
+            place(self.x, rect.start.x, rect.width),
+            place(self.y, rect.start.y, rect.height),
+        )
+    }
 }
 
 #[derive(Default)]
@@ -26,15 +135,172 @@ enum SelectionState {
     #[default]
     Waiting,
     BeginSelection(SelectionData),
+    Adjusting(AdjustingData),
     SelectionCompleted(Rectangle),
+    /// Session of [`SelectionOptions::multi`] is finished, regions are in
+    /// [`SelectionApp::completed`]
+    MultiCompleted,
     Abort,
 }
 
+/// Fresh frame requested with <kbd>F</kbd>. Overlay is hidden while it is copied, otherwise it
+/// would be captured too.
+enum Recapture {
+    /// Overlay is unmapped, waiting for configure to be sure compositor has seen it
+    Hiding,
+    Copying(FrameCopy),
+}
+
+/// Pointer shape over region selector.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, clap::ValueEnum)]
+pub enum CursorShape {
+    #[default]
+    Crosshair,
+    /// Usual arrow
+    Default,
+    /// Thick plus sign
+    Cross,
+    /// Open hand
+    Grab,
+}
+
+impl CursorShape {
+    fn shape(self) -> Shape {
+        match self {
+            Self::Crosshair => Shape::Crosshair,
+            Self::Default => Shape::Default,
+            Self::Cross => Shape::Cell,
+            Self::Grab => Shape::Grab,
+        }
+    }
+}
+
+/// Settings of region selector.
+#[derive(Clone, Debug, Default)]
+pub struct SelectionOptions {
+    pub cursor_shape: CursorShape,
+    /// Show pointer coordinates next to it while waiting for selection
+    pub show_coords: bool,
+    /// Fade dim overlay in instead of showing it instantly
+    pub animate: bool,
+    /// Draw moving dashed line on edges of selection waiting for confirmation, it stops while
+    /// selection is dragged
+    pub march: bool,
+    /// Complete selection with 1x1 region under pointer on first click, screen is not dimmed
+    pub pick: bool,
+    /// Click without dragging selects 1x1 region instead of being ignored
+    pub allow_click: bool,
+    /// Drags shorter than this many pixels (between corners) are treated as clicks
+    pub min_size: u32,
+    /// Pixels added to every side of selection per mouse wheel notch
+    pub scroll_step: u32,
+    /// Draw selection at full brightness with soft falloff into dimmed screen, without
+    /// crosshair
+    pub spotlight: bool,
+    /// Round selection corners to multiples of this many pixels, unless Ctrl is held
+    pub snap: Option<u32>,
+    /// Keep selection after button is released with handles on its border: dragging handle
+    /// resizes it, dragging inside moves it, Enter or double-click confirms it
+    pub edit: bool,
+    /// Show size of selection in progress next to its moving corner
+    pub size_label: bool,
+    /// Show magnified pixels around pointer, toggled with <kbd>Z</kbd>
+    pub loupe: bool,
+    /// Ratio of width to height of selection made by dragging
+    pub aspect: Option<f64>,
+    /// Keep selection after button is released, so it can be moved with middle mouse button,
+    /// until it is confirmed with click outside of it or Enter
+    pub confirm: bool,
+    /// How much of brightness is removed from screen outside of selection, from 0.0 (not dimmed
+    /// at all) to 1.0 (black)
+    pub dim: f64,
+    /// Border drawn inside selection
+    pub border: Option<Border>,
+    /// Windows on output, bottom to top. Window under pointer is highlighted and click selects
+    /// it
+    pub windows: Vec<Rectangle>,
+    /// Snap corners of selection to edges of [`SelectionOptions::windows`] (unless Ctrl is held)
+    pub snap_to_windows: bool,
+    /// Completed selection stays highlighted and another one can be made, Enter finishes
+    /// selection of all of them
+    pub multi: bool,
+    /// Abort selection when overlay loses keyboard focus
+    pub cancel_on_unfocus: bool,
+}
+
+/// Border of selection.
+#[derive(Clone, Copy, Debug)]
+pub struct Border {
+    /// RGBA color, it is blended with screen if it is translucent
+    pub color: [u8; 4],
+    /// Width in pixels
+    pub width: u32,
+}
+
 pub struct SelectionApp {
     pub image: Box<[u8]>,
-    pub buffer: Buffer,
+    buffers: SwapBuffers,
+    pub options: SelectionOptions,
 
     state: SelectionState,
+    /// Last known pointer position
+    pointer: Option<Point>,
+    /// Area covered by coordinates readout, if it is drawn
+    coords_label: Option<Rectangle>,
+    /// Screen is fully dimmed for [`SelectionState::Waiting`], after that only crosshair and
+    /// readout at pointer are redrawn
+    waiting_drawn: bool,
+    /// Pointer position shown by crosshair and readout while waiting
+    drawn_pointer: Option<Point>,
+    /// Window under pointer highlighted while waiting
+    highlighted: Option<Rectangle>,
+    /// Area covered by size of selection in progress, if it is drawn
+    size_label: Option<Rectangle>,
+    /// Area covered by loupe and pointer position it shows, if it is drawn
+    loupe: Option<(Rectangle, Point)>,
+    /// When overlay was created, used for fade in
+    created: Instant,
+    capture: CaptureManager,
+    recapture: Option<Recapture>,
+    /// Regions selected so far with [`SelectionOptions::multi`], oldest first
+    completed: Vec<Rectangle>,
+}
+
+/// Two overlay buffers, so drawing does not wait until compositor releases the one committed
+/// last.
+struct SwapBuffers {
+    buffers: [Buffer; 2],
+    /// Index of buffer which is drawn and committed
+    current: usize,
+    /// Copy of current buffer made on swap, pool gives out only one canvas at a time
+    scratch: Vec<u8>,
+}
+
+impl SwapBuffers {
+    /// Buffer to attach on commit.
+    fn current(&self) -> &Buffer {
+        &self.buffers[self.current]
+    }
+
+    /// Canvas of current buffer. If compositor still uses it, the other buffer becomes current,
+    /// overlay is incrementally drawn so it is copied there first. Returns [`None`] only if
+    /// both buffers are in use.
+    fn canvas<'pool>(&mut self, pool: &'pool mut SlotPool) -> Option<&'pool mut [u8]> {
+        if pool.canvas(self.current()).is_some() {
+            return pool.canvas(self.current());
+        }
+
+        let other = 1 - self.current;
+        pool.canvas(&self.buffers[other])?;
+        self.scratch.clear();
+        self.scratch
+            .extend_from_slice(pool.raw_data_mut(&self.current().slot()));
+        self.current = other;
+        let canvas = pool.canvas(self.current()).expect("checked above");
+        canvas.copy_from_slice(&self.scratch);
+
+        Some(canvas)
+    }
 }
 
 impl SelectionApp {
@@ -43,179 +309,418 @@ impl SelectionApp {
     pub fn selected_region(&self) -> Option<Rectangle> {
         match &self.state {
             SelectionState::SelectionCompleted(rect) => Some(rect.clone()),
+            SelectionState::MultiCompleted => self.completed.first().cloned(),
             _ => None,
         }
     }
-}
 
-impl WaylandAppStateFromPrevious for SelectionApp {
-    type Previous = super::screenshot::ScreenshotApp;
+    /// Same as [`SelectionApp::selected_region`], but every region selected with
+    /// [`SelectionOptions::multi`] is returned, oldest first. Empty if selection is aborted.
+    pub fn selected_regions(&self) -> Vec<Rectangle> {
+        match &self.state {
+            SelectionState::SelectionCompleted(rect) => vec![rect.clone()],
+            SelectionState::MultiCompleted => self.completed.clone(),
+            _ => Vec::new(),
+        }
+    }
 
-    fn from_previous(
-        context: &mut super::WaylandContext,
-        previous: Self::Previous,
-        _: &GlobalList,
-        _: &mut EventQueue<WaylandApp>,
-    ) -> Result<Self, super::Error> {
-        let image = previous.image.expect(
-            "attempt to switch state on non-completed phase, no image present from screenshot app",
-        );
-        let partial = context
-            .partial_mut()
-            .expect("SelectionApp requires at least partial context");
+    /// Current dim factor of overlay out of 256, it grows to [`SelectionOptions::dim`] while
+    /// waiting for selection if fade in is enabled.
+    fn dim_factor(&self) -> u8 {
+        if self.options.pick {
+            return 0;
+        }
+        let full = (self.options.dim * 256.0).clamp(0.0, 255.0) as u8;
+        if !self.options.animate || !matches!(self.state, SelectionState::Waiting) {
+            return full;
+        }
 
-        let (width, height) = {
-            let pos = partial.logical_size.clone();
+        let progress = self.created.elapsed().as_secs_f32() / FADE_IN_DURATION.as_secs_f32();
+        (full as f32 * progress.min(1.0)) as u8
+    }
 
-            (pos.x, pos.y)
-        };
+    /// Pointer shape for current state, selection moved with Space held is grabbed.
+    fn cursor_shape(&self) -> Shape {
+        match &self.state {
+            SelectionState::BeginSelection(SelectionData {
+                is_moving: true, ..
+            }) => Shape::Grabbing,
+            _ => self.options.cursor_shape.shape(),
+        }
+    }
 
-        let (buffer, _canvas) = partial
-            .pool
-            .create_buffer(
-                width as i32,
-                height as i32,
-                width as i32 * 4,
-                wl_shm::Format::Xrgb8888,
-            )
-            .expect("failed to create buffer format xrgb8888");
+    /// Show pointer shape of current state.
+    fn update_cursor(&self, ctx: &WaylandContext) {
+        if let Some(full) = ctx.full() {
+            full.set_cursor_shape(self.cursor_shape());
+        }
+    }
 
-        Ok(Self {
-            image,
-            buffer,
-            state: Default::default(),
-        })
+    /// Switch state after selection of `rect` is made with pointer.
+    fn finish(&mut self, rect: Rectangle) {
+        if self.options.confirm || self.options.edit {
+            self.state = SelectionState::Adjusting(AdjustingData::new(rect));
+        } else {
+            self.complete(rect);
+        }
     }
-}
 
-impl WaylandAppState for SelectionApp {
-    fn current_phase(&self) -> StatePhase {
-        match &self.state {
-            SelectionState::Abort | SelectionState::SelectionCompleted(_) => StatePhase::Done,
-            _ => StatePhase::Active,
+    /// Complete selection of `rect`. With [`SelectionOptions::multi`] it is kept and selector
+    /// waits for the next one, it has to be redrawn then.
+    fn complete(&mut self, rect: Rectangle) {
+        if self.options.multi {
+            log::debug!("region {rect} is selected, waiting for the next one");
+            self.completed.push(rect);
+            self.state = SelectionState::Waiting;
+            // Completed region is drawn over fully dimmed screen
+            self.waiting_drawn = false;
+        } else {
+            self.state = SelectionState::SelectionCompleted(rect);
         }
     }
 
-    fn on_key_press(
+    /// Hide overlay to capture fresh frame, it is shown again when frame is copied.
+    fn hide_for_recapture(&mut self, ctx: &WaylandContext) {
+        log::debug!("hiding overlay to capture fresh frame");
+        let layer = &ctx
+            .full()
+            .expect("SelectionApp requires full context")
+            .layer;
+        // Unmap surface, then make initial commit again to get configure before buffer can be
+        // attached
+        layer.wl_surface().attach(None, 0, 0);
+        layer.commit();
+        layer.commit();
+        self.recapture = Some(Recapture::Hiding);
+    }
+
+    /// Wait for another selection after completed one is taken, frame is captured again first
+    /// if `fresh` is set.
+    pub fn restart(&mut self, ctx: &mut WaylandContext, fresh: bool, qh: &QueueHandle<WaylandApp>) {
+        self.state = SelectionState::Waiting;
+        self.waiting_drawn = false;
+        if fresh {
+            self.hide_for_recapture(ctx);
+        } else {
+            self.on_redraw(ctx, qh);
+        }
+    }
+
+    /// Dim vacated `previous` area of adjusted selection and draw it at `rect`.
+    fn move_adjusted(
         &mut self,
         ctx: &mut WaylandContext,
-        event: KeyEvent,
+        previous: Rectangle,
+        rect: Rectangle,
         qh: &QueueHandle<WaylandApp>,
     ) {
-        match event.keysym {
-            Keysym::Escape => {
-                if let SelectionState::Waiting = self.state {
-                    self.state = SelectionState::Abort;
-                } else {
-                    self.state = SelectionState::Waiting;
-                    self.on_redraw(ctx, qh);
-                }
+        let shown = self.hide_loupe(ctx);
+        {
+            let ctx = ctx
+                .full_mut()
+                .expect("SelectionApp requires full context to draw");
+            let width = ctx.partial.logical_size.x as usize;
+            let Some(canvas) = self.buffers.canvas(&mut ctx.partial.pool) else {
+                return;
+            };
+            let backdrop = utils::Backdrop::new(&self.image, self.dim_factor());
+
+            // Rectangles are not empty and include right and bottom edges in `utils`
+            let inclusive = |r: Rectangle| Rectangle::new(r.start, r.width - 1, r.height - 1);
+            utils::dim_rect(
+                inclusive(previous),
+                canvas,
+                backdrop,
+                width,
+                Some(&ctx.layer),
+            );
+            let end = Point::new(
+                rect.start.x + rect.width - 1,
+                rect.start.y + rect.height - 1,
+            );
+            utils::copy_rect(
+                inclusive(rect.clone()),
+                canvas,
+                &self.image,
+                width,
+                Some(&ctx.layer),
+            );
+            if let Some(border) = self.options.border {
+                utils::draw_border(
+                    (&rect.start, &end),
+                    border,
+                    canvas,
+                    &self.image,
+                    width,
+                    Some(&ctx.layer),
+                );
             }
+        }
+        self.show_loupe(ctx, shown);
 
-            Keysym::space => {
-                if let SelectionState::BeginSelection(SelectionData { is_moving, .. }) =
-                    &mut self.state
+        let layer = &ctx.full().expect("drawn above").layer;
+        utils::commit_drawing(layer, self.buffers.current(), qh);
+    }
+
+    /// Restore overlay under loupe, as it is drawn for current state. Returns pointer position
+    /// shown by hidden loupe.
+    fn hide_loupe(&mut self, ctx: &mut WaylandContext) -> Option<Point> {
+        let (rect, shown) = self.loupe.take()?;
+        let backdrop = utils::Backdrop::new(&self.image, self.dim_factor());
+        let ctx = ctx
+            .full_mut()
+            .expect("SelectionApp requires full context to draw");
+        let size = ctx.partial.logical_size.clone();
+        let Some(canvas) = self.buffers.canvas(&mut ctx.partial.pool) else {
+            return Some(shown);
+        };
+        let layer = Some(&ctx.layer);
+        let width = size.x as usize;
+
+        match &self.state {
+            SelectionState::BeginSelection(data) if data.initial != data.current => {
+                let corners = (&data.initial, &data.current);
+                utils::restore_label(
+                    rect.clone(),
+                    corners,
+                    self.options.spotlight,
+                    canvas,
+                    backdrop,
+                    width,
+                    layer,
+                );
+                if let Some(border) = self.options.border {
+                    utils::draw_border(corners, border, canvas, &self.image, width, layer);
+                }
+                if self.options.spotlight {
+                    return Some(shown);
+                }
+                // Whole lines are redrawn, they are cheap
+                for pos in [corners.0, corners.1] {
+                    utils::fill_crosshair(pos.clone(), canvas, size.x, size.y, layer);
+                }
+                if self
+                    .size_label
+                    .as_ref()
+                    .is_some_and(|l| l.intersection(&rect).is_some())
                 {
-                    *is_moving = true;
+                    self.size_label =
+                        utils::draw_size_label(corners.0, corners.1, canvas, size.x, size.y, layer);
+                }
+            }
+            SelectionState::Adjusting(data) => {
+                let end = Point::new(
+                    data.rect.start.x + data.rect.width - 1,
+                    data.rect.start.y + data.rect.height - 1,
+                );
+                utils::restore_label(
+                    rect,
+                    (&data.rect.start, &end),
+                    false,
+                    canvas,
+                    backdrop,
+                    width,
+                    layer,
+                );
+                if let Some(border) = self.options.border {
+                    let corners = (&data.rect.start, &end);
+                    utils::draw_border(corners, border, canvas, &self.image, width, layer);
+                }
+                if self.options.edit {
+                    for handle in Handle::ALL {
+                        utils::fill_handle(handle.position(&data.rect), canvas, size.x, layer);
+                    }
+                }
+            }
+            _ => {
+                utils::dim_rect(
+                    Rectangle::new(rect.start.clone(), rect.width - 1, rect.height - 1),
+                    canvas,
+                    backdrop,
+                    width,
+                    layer,
+                );
+                if let Some(window) = &self.highlighted {
+                    utils::draw_ants(window, 0, canvas, size.x, layer);
+                }
+                if let Some(pos) = self
+                    .drawn_pointer
+                    .clone()
+                    .filter(|_| !self.options.spotlight)
+                {
+                    // Loupe is offset from pointer it was drawn at, so pointer may have moved
+                    // under it since then. Whole lines are redrawn, they are cheap
+                    utils::fill_crosshair(pos, canvas, size.x, size.y, layer);
+                }
+                if let Some(label) = &self.coords_label {
+                    if label.intersection(&rect).is_some() {
+                        self.coords_label = utils::update_coords_label(
+                            None,
+                            self.pointer.clone(),
+                            canvas,
+                            backdrop,
+                            size.x,
+                            size.y,
+                            layer,
+                        );
+                    }
                 }
             }
-
-            _ => (),
         }
+
+        Some(shown)
     }
 
-    fn on_key_release(
-        &mut self,
-        _ctx: &mut WaylandContext,
-        event: KeyEvent,
-        _qh: &QueueHandle<WaylandApp>,
-    ) {
-        if event.keysym == Keysym::space {
-            if let SelectionState::BeginSelection(SelectionData { is_moving, .. }) = &mut self.state
-            {
-                *is_moving = false;
+    /// Draw loupe at pointer if it is enabled. Returns `true` if it is changed compared to
+    /// `shown` position of hidden one, i.e. it is moved, shown or hidden.
+    fn show_loupe(&mut self, ctx: &mut WaylandContext, shown: Option<Point>) -> bool {
+        let pointer = self.pointer.clone().filter(|_| self.options.loupe);
+        if let Some(pos) = &pointer {
+            let ctx = ctx
+                .full_mut()
+                .expect("SelectionApp requires full context to draw");
+            let size = ctx.partial.logical_size.clone();
+            if let Some(canvas) = self.buffers.canvas(&mut ctx.partial.pool) {
+                self.loupe =
+                    utils::draw_loupe(pos, canvas, &self.image, size.x, size.y, Some(&ctx.layer))
+                        .map(|rect| (rect, pos.clone()));
             }
         }
+
+        shown != pointer
     }
 
-    fn on_mouse_enter(
-        &mut self,
-        ctx: &mut WaylandContext,
-        _pos: Point,
-        pointer: &wl_pointer::WlPointer,
-        serial: u32,
-        qh: &QueueHandle<WaylandApp>,
-    ) {
-        if let Some(shape_manager) = ctx.full().and_then(|v| v.shape_manager.as_ref()) {
-            let dev = shape_manager.get_shape_device(pointer, qh);
-            dev.set_shape(serial, Shape::Crosshair);
+    /// Snap `pos` to grid of [`SelectionOptions::snap`], unless Ctrl is held.
+    fn snapped(&self, ctx: &WaylandContext, pos: Point) -> Point {
+        let Some(full) = ctx.full() else {
+            return pos;
+        };
+        if full.modifiers.ctrl {
+            return pos;
         }
+
+        let size = &full.partial.logical_size;
+        let pos = match self.options.snap {
+            Some(grid) => pos.snapped(grid, &Point::new(size.x - 1, size.y - 1)),
+            None => pos,
+        };
+        // Window edges win over grid, they are what user wants to capture
+        let pos = if self.options.snap_to_windows {
+            utils::snap_to_windows(pos, &self.options.windows, WINDOW_SNAP_DISTANCE)
+        } else {
+            pos
+        };
+        Point::new(pos.x.min(size.x - 1), pos.y.min(size.y - 1))
     }
 
-    fn on_mouse_move(
-        &mut self,
-        ctx: &mut WaylandContext,
-        pos: Point,
-        qh: &QueueHandle<WaylandApp>,
-    ) {
-        if let SelectionState::BeginSelection(SelectionData { pending, .. }) = &mut self.state {
-            *pending = Some(pos);
+    /// Topmost window under `pos`, see [`SelectionOptions::windows`].
+    fn window_at(&self, pos: &Point) -> Option<&Rectangle> {
+        self.options.windows.iter().rev().find(|w| w.contains(pos))
+    }
+
+    /// Drop selection in progress, or exit if there is none.
+    fn cancel(&mut self, ctx: &mut WaylandContext, qh: &QueueHandle<WaylandApp>) {
+        if let SelectionState::Waiting = self.state {
+            self.state = SelectionState::Abort;
+        } else {
+            self.state = SelectionState::Waiting;
+            self.update_cursor(ctx);
             self.on_redraw(ctx, qh);
         }
     }
-    fn on_mouse_press(
-        &mut self,
-        _ctx: &mut WaylandContext,
-        pos: Point,
-        _qh: &QueueHandle<WaylandApp>,
-    ) {
-        let SelectionState::Waiting = self.state else {
+
+    /// Move corner under pointer of selection in progress by arrow `key`: by 1 pixel, by 10
+    /// with Shift. With Ctrl whole selection is moved.
+    fn nudge(&mut self, ctx: &mut WaylandContext, key: Keysym, qh: &QueueHandle<WaylandApp>) {
+        let Some(full) = ctx.full() else {
+            return;
+        };
+        let modifiers = full.modifiers;
+        let size = full.partial.logical_size.clone();
+        let SelectionState::BeginSelection(data) = &mut self.state else {
             return;
         };
 
-        self.state = SelectionState::BeginSelection(SelectionData {
-            initial: pos.clone(),
-            current: pos,
-            pending: None,
+        let step = if modifiers.shift { 10 } else { 1 };
+        let (dx, dy) = match key {
+            Keysym::Left => (-step, 0),
+            Keysym::Right => (step, 0),
+            Keysym::Up => (0, -step),
+            Keysym::Down => (0, step),
+            _ => return,
+        };
+        let from = data.pending.clone().unwrap_or_else(|| data.current.clone());
+        let clamp = |delta: i32, a: u32, b: u32, size: u32| {
+            if modifiers.ctrl {
+                // Keep both corners on screen, so selection does not change its size
+                delta.clamp(
+                    -(a.min(b) as i32),
+                    size.saturating_sub(1) as i32 - a.max(b) as i32,
+                )
+            } else {
+                delta.clamp(-(b as i32), size.saturating_sub(1) as i32 - b as i32)
+            }
+        };
+        let dx = clamp(dx, data.initial.x, from.x, size.x);
+        let dy = clamp(dy, data.initial.y, from.y, size.y);
 
-            is_moving: false,
-        });
+        data.pending = Some(Point::new(
+            from.x.saturating_add_signed(dx),
+            from.y.saturating_add_signed(dy),
+        ));
+        // Moving whole selection is the same as moving pointer with Space held
+        let is_moving = data.is_moving;
+        data.is_moving |= modifiers.ctrl;
+        self.on_redraw(ctx, qh);
+        if let SelectionState::BeginSelection(data) = &mut self.state {
+            data.is_moving = is_moving;
+        }
     }
-    fn on_mouse_release(
-        &mut self,
-        _ctx: &mut WaylandContext,
-        _pos: Point,
-        _qh: &QueueHandle<WaylandApp>,
-    ) {
-        let SelectionState::BeginSelection(SelectionData {
-            initial,
-            current,
-            pending: _,
-            is_moving: _,
-        }) = &self.state
-        else {
+
+    /// Erase coordinates readout and crosshair drawn at pointer while waiting. Changes are not
+    /// committed.
+    fn erase_pointer_marks(&mut self, ctx: &mut WaylandContext) {
+        let backdrop = utils::Backdrop::new(&self.image, self.dim_factor());
+        let ctx = ctx
+            .full_mut()
+            .expect("SelectionApp requires full context to draw");
+        let Some(canvas) = self.buffers.canvas(&mut ctx.partial.pool) else {
             return;
         };
+        let size = ctx.partial.logical_size.clone();
+        let layer = Some(&ctx.layer);
 
-        if let Some(rect) = Rectangle::from_two_points(initial.clone(), current.clone()) {
-            self.state = SelectionState::SelectionCompleted(rect);
-        } else {
-            // assume rectangle without area isn't a valid selection
-            self.state = SelectionState::Waiting;
+        if let Some(label) = self.coords_label.take() {
+            utils::update_coords_label(Some(label), None, canvas, backdrop, size.x, size.y, layer);
+        }
+        if let Some(window) = self.highlighted.take() {
+            utils::dim_outline(&window, canvas, backdrop, size.x as usize, layer);
         }
+        if let Some(pos) = self.drawn_pointer.take() {
+            if !self.options.spotlight {
+                utils::dim_crosshair(pos, canvas, backdrop, size.x, size.y, layer);
+            }
+        }
+        utils::copy_regions(&self.completed, canvas, &self.image, size.x as usize, layer);
     }
 
-    /// Called on random redraws and on mouse movement
-    fn on_redraw(&mut self, ctx: &mut WaylandContext, qh: &QueueHandle<WaylandApp>) {
-        let buffer = &mut self.buffer;
+    /// Draw overlay for current state. Returns `false` if nothing has changed.
+    fn draw(&mut self, ctx: &mut WaylandContext) -> bool {
+        if !matches!(self.state, SelectionState::Waiting) {
+            // Overlay of other states covers everything drawn while waiting
+            self.waiting_drawn = false;
+            self.drawn_pointer = None;
+            self.highlighted = None;
+        }
+        let backdrop = utils::Backdrop::new(&self.image, self.dim_factor());
         let (canvas, layer, width, height) = {
             let ctx = ctx
                 .full_mut()
                 .expect("SelectionApp requires full context to draw");
 
-            let canvas = match ctx.partial.pool.canvas(buffer) {
+            let canvas = match self.buffers.canvas(&mut ctx.partial.pool) {
                 Some(canvas) => canvas,
-                None => return,
+                None => return false,
             };
 
             let layer = &ctx.layer;
@@ -225,16 +730,21 @@ impl WaylandAppState for SelectionApp {
         };
 
         let (init, previous, pending, pending_init) = match &mut self.state {
-            SelectionState::BeginSelection(SelectionData {
-                initial,
-                current,
-                pending: pending @ Some(_),
-                is_moving,
-            }) if Some(current.clone()) != *pending => {
+            SelectionState::BeginSelection(data)
+                if data.pending.is_some() && data.pending.as_ref() != Some(&data.current) =>
+            {
+                let is_translating = data.is_translating();
+                let SelectionData {
+                    initial,
+                    current,
+                    pending,
+                    ..
+                } = data;
+
                 let pending = pending.take().expect("matched");
                 let prev = current.clone();
                 *current = pending.clone();
-                let (init, pending_init) = if *is_moving {
+                let (init, pending_init) = if is_translating {
                     let dx = pending.x as i32 - prev.x as i32;
                     let dy = pending.y as i32 - prev.y as i32;
                     let prev_init = initial.clone();
@@ -256,35 +766,242 @@ impl WaylandAppState for SelectionApp {
             }) if current != initial => (initial.clone(), initial.clone(), current.clone(), None),
 
             SelectionState::Waiting => {
-                utils::dim_rect(
-                    Rectangle::new(Point::new(0, 0), width - 1, height - 1),
+                self.size_label = None;
+                // Dim factor changes on every frame of fade in
+                let fading = self.options.animate && self.created.elapsed() < FADE_IN_DURATION;
+                if !self.waiting_drawn || fading {
+                    utils::dim_rect(
+                        utils::screen_rect(width, height),
+                        canvas,
+                        backdrop,
+                        width as usize,
+                        Some(layer),
+                    );
+                    self.waiting_drawn = !fading;
+                    // Old readout, crosshair and highlight are dimmed with the rest of screen
+                    self.coords_label = None;
+                    self.drawn_pointer = None;
+                    self.highlighted = None;
+                } else if self.drawn_pointer == self.pointer {
+                    return false;
+                }
+
+                if let Some(label) = self.coords_label.take() {
+                    utils::update_coords_label(
+                        Some(label),
+                        None,
+                        canvas,
+                        backdrop,
+                        width,
+                        height,
+                        Some(layer),
+                    );
+                }
+                if let Some(window) = self.highlighted.take() {
+                    utils::dim_outline(&window, canvas, backdrop, width as usize, Some(layer));
+                }
+                if !self.options.spotlight {
+                    if let Some(pos) = self.drawn_pointer.take() {
+                        utils::dim_crosshair(pos, canvas, backdrop, width, height, Some(layer));
+                    }
+                }
+                // Dimming above may cross them
+                utils::copy_regions(
+                    &self.completed,
                     canvas,
                     &self.image,
                     width as usize,
                     Some(layer),
                 );
-                utils::commit_drawing(layer, buffer, qh);
-                return;
+                self.highlighted = self
+                    .pointer
+                    .as_ref()
+                    .and_then(|pos| self.window_at(pos))
+                    .cloned();
+                if let Some(window) = &self.highlighted {
+                    utils::draw_ants(window, 0, canvas, width, Some(layer));
+                }
+                if !self.options.spotlight {
+                    if let Some(pos) = self.pointer.clone() {
+                        utils::fill_crosshair(pos, canvas, width, height, Some(layer));
+                    }
+                }
+                if self.options.show_coords {
+                    self.coords_label = utils::update_coords_label(
+                        None,
+                        self.pointer.clone(),
+                        canvas,
+                        backdrop,
+                        width,
+                        height,
+                        Some(layer),
+                    );
+                }
+                self.drawn_pointer = self.pointer.clone();
+                return true;
             }
 
-            _ => return,
-        };
-
-        if pending_init.is_some() {
-            utils::dim_crosshair(
-                init.clone(),
+            SelectionState::Adjusting(AdjustingData {
+                rect,
+                drawn,
+                drag,
+                ants,
+                ..
+            }) if !*drawn => {
+                *drawn = true;
+                self.size_label = None;
+                utils::dim_rect(
+                    utils::screen_rect(width, height),
+                    canvas,
+                    backdrop,
+                    width as usize,
+                    Some(layer),
+                );
+                utils::copy_regions(
+                    &self.completed,
+                    canvas,
+                    &self.image,
+                    width as usize,
+                    Some(layer),
+                );
+                utils::copy_rect(
+                    Rectangle::new(rect.start.clone(), rect.width - 1, rect.height - 1),
+                    canvas,
+                    &self.image,
+                    width as usize,
+                    Some(layer),
+                );
+                if let Some(border) = self.options.border {
+                    let end = Point::new(
+                        rect.start.x + rect.width - 1,
+                        rect.start.y + rect.height - 1,
+                    );
+                    utils::draw_border(
+                        (&rect.start, &end),
+                        border,
+                        canvas,
+                        &self.image,
+                        width as usize,
+                        Some(layer),
+                    );
+                }
+                if self.options.march && drag.is_none() {
+                    utils::draw_ants(
+                        rect,
+                        *ants / ANTS_FRAMES_PER_PIXEL,
+                        canvas,
+                        width,
+                        Some(layer),
+                    );
+                }
+                if self.options.edit {
+                    for handle in Handle::ALL {
+                        utils::fill_handle(handle.position(rect), canvas, width, Some(layer));
+                    }
+                }
+                return true;
+            }
+
+            // Ants march on every frame, but not while selection is dragged
+            SelectionState::Adjusting(AdjustingData {
+                rect,
+                drag: None,
+                ants,
+                ..
+            }) if self.options.march => {
+                *ants = ants.wrapping_add(1);
+                utils::draw_ants(
+                    rect,
+                    *ants / ANTS_FRAMES_PER_PIXEL,
+                    canvas,
+                    width,
+                    Some(layer),
+                );
+                if self.options.edit {
+                    // Ants are drawn over handles on edges
+                    for handle in Handle::ALL {
+                        utils::fill_handle(handle.position(rect), canvas, width, Some(layer));
+                    }
+                }
+                return true;
+            }
+
+            _ => return false,
+        };
+
+        if self.options.spotlight {
+            // Repaint both selections with their falloff bands, everything else is fully dimmed
+            let new_init = pending_init.unwrap_or(init.clone());
+            let points = [&init, &previous, &new_init, &pending];
+            let lo = Point::new(
+                points.iter().map(|p| p.x).min().expect("not empty"),
+                points.iter().map(|p| p.y).min().expect("not empty"),
+            );
+            let hi = Point::new(
+                points.iter().map(|p| p.x).max().expect("not empty"),
+                points.iter().map(|p| p.y).max().expect("not empty"),
+            );
+            let area = Rectangle::new(
+                Point::new(
+                    lo.x.saturating_sub(utils::SPOTLIGHT_RAMP),
+                    lo.y.saturating_sub(utils::SPOTLIGHT_RAMP),
+                ),
+                hi.x - lo.x + utils::SPOTLIGHT_RAMP * 2,
+                hi.y - lo.y + utils::SPOTLIGHT_RAMP * 2,
+            );
+
+            utils::spotlight(
+                &new_init,
+                &pending,
+                area,
+                canvas,
+                backdrop,
+                width as usize,
+                Some(layer),
+            );
+            utils::copy_regions(
+                &self.completed,
                 canvas,
                 &self.image,
-                width,
-                height,
+                width as usize,
                 Some(layer),
             );
+            if let Some(border) = self.options.border {
+                utils::draw_border(
+                    (&new_init, &pending),
+                    border,
+                    canvas,
+                    &self.image,
+                    width as usize,
+                    Some(layer),
+                );
+            }
+            if self.options.size_label {
+                if let Some(label) = self.size_label.take() {
+                    utils::restore_label(
+                        label,
+                        (&new_init, &pending),
+                        true,
+                        canvas,
+                        backdrop,
+                        width as usize,
+                        Some(layer),
+                    );
+                }
+                self.size_label =
+                    utils::draw_size_label(&new_init, &pending, canvas, width, height, Some(layer));
+            }
+            return true;
+        }
+
+        if pending_init.is_some() {
+            utils::dim_crosshair(init.clone(), canvas, backdrop, width, height, Some(layer));
         };
 
         utils::dim_crosshair(
             previous.clone(),
             canvas,
-            &self.image,
+            backdrop,
             width,
             height,
             Some(layer),
@@ -295,7 +1012,7 @@ impl WaylandAppState for SelectionApp {
             previous.clone(),
             pending.clone(),
             canvas,
-            &self.image,
+            backdrop,
             width as usize,
             Some(layer),
         );
@@ -306,37 +1023,765 @@ impl WaylandAppState for SelectionApp {
                 init.clone(),
                 pending_init,
                 canvas,
-                &self.image,
+                backdrop,
                 width as usize,
                 Some(layer),
             );
         }
 
-        utils::fill_crosshair(pending_init.unwrap_or(init), canvas, width, height, Some(layer));
+        let new_init = pending_init.unwrap_or(init.clone());
+        if let Some(border) = self.options.border {
+            // Parts of old border not covered by changes above
+            utils::restore_border(
+                (&init, &previous),
+                (&new_init, &pending),
+                border.width,
+                canvas,
+                backdrop,
+                width as usize,
+                Some(layer),
+            );
+        }
+        // Old label may cover both selection and dimmed area, it is restored before crosshair is
+        // drawn over it
+        if let Some(label) = self.size_label.take() {
+            utils::restore_label(
+                label,
+                (&new_init, &pending),
+                false,
+                canvas,
+                backdrop,
+                width as usize,
+                Some(layer),
+            );
+        }
+
+        // Vacated area and old crosshair are dimmed, they may cross completed regions
+        utils::copy_regions(
+            &self.completed,
+            canvas,
+            &self.image,
+            width as usize,
+            Some(layer),
+        );
+        utils::fill_crosshair(new_init.clone(), canvas, width, height, Some(layer));
         utils::fill_crosshair(pending.clone(), canvas, width, height, Some(layer));
+        if let Some(border) = self.options.border {
+            utils::draw_border(
+                (&new_init, &pending),
+                border,
+                canvas,
+                &self.image,
+                width as usize,
+                Some(layer),
+            );
+        }
+
+        if self.options.size_label {
+            self.size_label =
+                utils::draw_size_label(&new_init, &pending, canvas, width, height, Some(layer));
+        }
+
+        true
+    }
+}
+
+impl WaylandAppStateFromPrevious for SelectionApp {
+    type Previous = super::screenshot::ScreenshotApp;
+
+    fn from_previous(
+        context: &mut super::WaylandContext,
+        previous: Self::Previous,
+        _: &GlobalList,
+        _: &mut EventQueue<WaylandApp>,
+    ) -> Result<Self, super::Error> {
+        let image = previous.image.expect(
+            "attempt to switch state on non-completed phase, no image present from screenshot app",
+        );
+        let partial = context
+            .partial_mut()
+            .expect("SelectionApp requires at least partial context");
 
-        utils::commit_drawing(layer, buffer, qh);
+        let (width, height) = {
+            let pos = partial.logical_size.clone();
+
+            (pos.x, pos.y)
+        };
+        // Overlay buffer has no padding, so image is addressed the same way as canvas
+        let image = screenshot::pack_rows(image, previous.stride, width as usize * 4);
+
+        let mut create_buffer = || {
+            let (buffer, _canvas) = partial
+                .pool
+                .create_buffer(
+                    width as i32,
+                    height as i32,
+                    width as i32 * 4,
+                    wl_shm::Format::Xrgb8888,
+                )
+                .expect("failed to create buffer format xrgb8888");
+            buffer
+        };
+        let buffers = SwapBuffers {
+            buffers: [create_buffer(), create_buffer()],
+            current: 0,
+            scratch: Vec::new(),
+        };
+
+        Ok(Self {
+            image,
+            buffers,
+            options: Default::default(),
+            state: Default::default(),
+            pointer: None,
+            coords_label: None,
+            waiting_drawn: false,
+            drawn_pointer: None,
+            highlighted: None,
+            size_label: None,
+            loupe: None,
+            created: Instant::now(),
+            capture: previous.capture,
+            recapture: None,
+            completed: Vec::new(),
+        })
+    }
+}
+
+impl WaylandAppState for SelectionApp {
+    fn current_phase(&self) -> StatePhase {
+        match &self.state {
+            SelectionState::Abort
+            | SelectionState::SelectionCompleted(_)
+            | SelectionState::MultiCompleted => StatePhase::Done,
+            _ => StatePhase::Active,
+        }
+    }
+
+    fn on_key_press(
+        &mut self,
+        ctx: &mut WaylandContext,
+        event: KeyEvent,
+        qh: &QueueHandle<WaylandApp>,
+    ) {
+        match event.keysym {
+            Keysym::Escape => self.cancel(ctx, qh),
+
+            Keysym::space => {
+                if let SelectionState::BeginSelection(SelectionData { is_moving, .. }) =
+                    &mut self.state
+                {
+                    *is_moving = true;
+                    self.update_cursor(ctx);
+                }
+            }
+
+            Keysym::Return | Keysym::KP_Enter => {
+                if let SelectionState::Adjusting(AdjustingData { rect, .. }) = &self.state {
+                    self.complete(rect.clone());
+                    self.on_redraw(ctx, qh);
+                } else if let (SelectionState::Waiting, false) =
+                    (&self.state, self.completed.is_empty())
+                {
+                    self.state = SelectionState::MultiCompleted;
+                } else if let SelectionState::Waiting = self.state {
+                    // Whole output, exactly the size of captured image
+                    let size = &ctx
+                        .partial()
+                        .expect("SelectionApp requires at least partial context")
+                        .logical_size;
+                    self.state = SelectionState::SelectionCompleted(Rectangle::new(
+                        Point::new(0, 0),
+                        size.x,
+                        size.y,
+                    ));
+                }
+            }
+
+            Keysym::_0
+            | Keysym::_1
+            | Keysym::_2
+            | Keysym::_3
+            | Keysym::_4
+            | Keysym::_5
+            | Keysym::_6 => {
+                if let SelectionState::Waiting | SelectionState::Adjusting(_) = self.state {
+                    let size = &ctx
+                        .partial()
+                        .expect("SelectionApp requires at least partial context")
+                        .logical_size;
+                    let Some(rect) = utils::preset_rect(event.keysym, size.x, size.y) else {
+                        return;
+                    };
+                    // Preset is always confirmed with Enter, even without `confirm` option
+                    self.state = SelectionState::Adjusting(AdjustingData::new(rect));
+                    self.erase_pointer_marks(ctx);
+                    self.on_redraw(ctx, qh);
+                }
+            }
+
+            Keysym::Left | Keysym::Right | Keysym::Up | Keysym::Down => {
+                self.nudge(ctx, event.keysym, qh);
+            }
+
+            Keysym::z | Keysym::Z => {
+                self.options.loupe = !self.options.loupe;
+                self.on_redraw(ctx, qh);
+            }
+
+            Keysym::f | Keysym::F => {
+                if let (SelectionState::Waiting, None) = (&self.state, &self.recapture) {
+                    self.hide_for_recapture(ctx);
+                }
+            }
+
+            _ => (),
+        }
+    }
+
+    fn on_configure(&mut self, ctx: &mut WaylandContext, qh: &QueueHandle<WaylandApp>) {
+        if let Some(Recapture::Hiding) = self.recapture {
+            let output = ctx
+                .base()
+                .output
+                .as_ref()
+                .expect("output is checked before screenshot");
+            self.recapture = Some(Recapture::Copying(self.capture.capture(output, qh)));
+            return;
+        }
+
+        self.on_redraw(ctx, qh);
+    }
+
+    fn capture_event(
+        &mut self,
+        ctx: &mut WaylandContext,
+        event: CaptureEvent,
+        qh: &QueueHandle<WaylandApp>,
+    ) {
+        let Some(Recapture::Copying(frame)) = &mut self.recapture else {
+            return;
+        };
+        let partial = ctx
+            .partial_mut()
+            .expect("SelectionApp requires at least partial context");
+        let row_len = partial.logical_size.x as usize * 4;
+
+        match frame.handle_event(&mut partial.pool, event) {
+            FrameStatus::Pending => return,
+            FrameStatus::Ready(image, stride) => {
+                let image = screenshot::pack_rows(image, stride, row_len);
+                if image.len() == self.image.len() {
+                    self.image = image;
+                } else {
+                    log::warn!("fresh frame has different size, keeping old one");
+                }
+            }
+            FrameStatus::Failed => log::warn!("failed to capture fresh frame, keeping old one"),
+        }
+
+        self.recapture = None;
+        // Old readout is not drawn on hidden overlay, and screen under it may change
+        self.coords_label = None;
+        self.waiting_drawn = false;
+        self.on_redraw(ctx, qh);
+    }
+
+    fn on_key_release(
+        &mut self,
+        ctx: &mut WaylandContext,
+        event: KeyEvent,
+        _qh: &QueueHandle<WaylandApp>,
+    ) {
+        if event.keysym == Keysym::space {
+            if let SelectionState::BeginSelection(SelectionData { is_moving, .. }) = &mut self.state
+            {
+                *is_moving = false;
+                self.update_cursor(ctx);
+            }
+        }
+    }
+
+    fn on_focus_lost(&mut self, _ctx: &mut WaylandContext, _qh: &QueueHandle<WaylandApp>) {
+        // Overlay hidden for fresh frame loses focus too, it's given back when overlay is shown
+        if self.options.cancel_on_unfocus && self.recapture.is_none() {
+            log::debug!("aborting selection, overlay lost focus");
+            self.state = SelectionState::Abort;
+        }
+    }
+
+    fn on_mouse_enter(
+        &mut self,
+        ctx: &mut WaylandContext,
+        pos: Point,
+        _pointer: &wl_pointer::WlPointer,
+        _serial: u32,
+        _qh: &QueueHandle<WaylandApp>,
+    ) {
+        self.update_cursor(ctx);
+        self.pointer = Some(pos);
+    }
+
+    fn on_tool_enter(
+        &mut self,
+        ctx: &mut WaylandContext,
+        _tool: &ZwpTabletToolV2,
+        _serial: u32,
+        _qh: &QueueHandle<WaylandApp>,
+    ) {
+        self.update_cursor(ctx);
+    }
+
+    fn on_mouse_move(
+        &mut self,
+        ctx: &mut WaylandContext,
+        pos: Point,
+        qh: &QueueHandle<WaylandApp>,
+    ) {
+        self.pointer = Some(pos.clone());
+        let shift = ctx.full().is_some_and(|ctx| ctx.modifiers.shift);
+        let snapped = self.snapped(ctx, pos.clone());
+
+        match &mut self.state {
+            SelectionState::BeginSelection(data) => {
+                // Shift keeps selection square, it is checked on every motion, so releasing it
+                // lets corner follow pointer again. Ratio passed in options wins.
+                let ratio = self.options.aspect.or(shift.then_some(1.0));
+                data.pending = Some(match ratio {
+                    Some(ratio) if !data.is_translating() => {
+                        snapped.with_aspect_to(&data.initial, ratio)
+                    }
+                    _ => snapped,
+                });
+                self.on_redraw(ctx, qh);
+            }
+            SelectionState::Adjusting(AdjustingData {
+                rect,
+                drag: Some((drag, kind)),
+                drawn,
+                ..
+            }) => {
+                let previous = rect.clone();
+                let size = &ctx
+                    .partial()
+                    .expect("SelectionApp requires at least partial context")
+                    .logical_size;
+                match *kind {
+                    DragKind::Move => {
+                        // Keep whole selection on screen
+                        let dx = (pos.x as i64 - drag.x as i64).clamp(
+                            -(rect.start.x as i64),
+                            (size.x - rect.width - rect.start.x) as i64,
+                        );
+                        let dy = (pos.y as i64 - drag.y as i64).clamp(
+                            -(rect.start.y as i64),
+                            (size.y - rect.height - rect.start.y) as i64,
+                        );
+                        rect.start = Point::new(
+                            (rect.start.x as i64 + dx) as u32,
+                            (rect.start.y as i64 + dy) as u32,
+                        );
+                    }
+                    DragKind::Resize(handle) => *rect = utils::resize_by_handle(rect, handle, &pos),
+                }
+                *drag = pos;
+
+                if self.options.edit {
+                    // Handles are drawn over overlay, redraw it completely
+                    *drawn = false;
+                    self.on_redraw(ctx, qh);
+                } else {
+                    let rect = rect.clone();
+                    self.move_adjusted(ctx, previous, rect, qh);
+                }
+            }
+            // Crosshair and readout follow pointer
+            SelectionState::Waiting => self.on_redraw(ctx, qh),
+            // Nothing else follows pointer
+            _ if self.options.loupe => self.on_redraw(ctx, qh),
+            _ => (),
+        }
+    }
+    fn on_mouse_press(
+        &mut self,
+        ctx: &mut WaylandContext,
+        pos: Point,
+        qh: &QueueHandle<WaylandApp>,
+    ) {
+        if let SelectionState::Adjusting(data) = &mut self.state {
+            let confirmed = if self.options.edit {
+                data.last_press
+                    .is_some_and(|press| press.elapsed() < DOUBLE_CLICK)
+                    && data.rect.contains(&pos)
+            } else {
+                // Click inside selection does nothing, it's moved with middle button
+                !data.rect.contains(&pos)
+            };
+            if confirmed {
+                let rect = data.rect.clone();
+                self.complete(rect);
+                self.on_redraw(ctx, qh);
+            } else if self.options.edit {
+                data.last_press = Some(Instant::now());
+                data.drag = utils::hit_test(&data.rect, &pos).map(|kind| (pos, kind));
+            }
+            return;
+        }
+        let SelectionState::Waiting = self.state else {
+            return;
+        };
+
+        if self.options.pick {
+            self.state = SelectionState::SelectionCompleted(Rectangle::new(pos, 1, 1));
+            return;
+        }
+
+        // Selection is drawn over fully dimmed screen, finish fade in immediately
+        if self.options.animate && self.created.elapsed() < FADE_IN_DURATION {
+            self.options.animate = false;
+            self.on_redraw(ctx, qh);
+        }
+
+        let pos = self.snapped(ctx, pos);
+        self.state = SelectionState::BeginSelection(SelectionData {
+            initial: pos.clone(),
+            current: pos,
+            pending: None,
+
+            is_moving: false,
+            is_panning: false,
+        });
+        // Readout and crosshair at pointer are shown only while waiting, erase them
+        self.erase_pointer_marks(ctx);
+    }
+    fn on_right_press(
+        &mut self,
+        ctx: &mut WaylandContext,
+        _pos: Point,
+        qh: &QueueHandle<WaylandApp>,
+    ) {
+        self.cancel(ctx, qh);
+    }
+    fn on_touch_cancel(&mut self, ctx: &mut WaylandContext, qh: &QueueHandle<WaylandApp>) {
+        match &mut self.state {
+            // Selection made with canceled touch is discarded
+            SelectionState::BeginSelection(_) => self.state = SelectionState::Waiting,
+            SelectionState::Adjusting(AdjustingData { drag, .. }) => *drag = None,
+            _ => return,
+        }
+        self.on_redraw(ctx, qh);
+    }
+    fn on_middle_press(
+        &mut self,
+        _ctx: &mut WaylandContext,
+        pos: Point,
+        _qh: &QueueHandle<WaylandApp>,
+    ) {
+        match &mut self.state {
+            SelectionState::BeginSelection(SelectionData { is_panning, .. }) => *is_panning = true,
+            SelectionState::Adjusting(AdjustingData { drag, .. }) => {
+                *drag = Some((pos, DragKind::Move))
+            }
+            _ => (),
+        }
+    }
+    fn on_middle_release(
+        &mut self,
+        _ctx: &mut WaylandContext,
+        _pos: Point,
+        _qh: &QueueHandle<WaylandApp>,
+    ) {
+        match &mut self.state {
+            SelectionState::BeginSelection(SelectionData { is_panning, .. }) => *is_panning = false,
+            SelectionState::Adjusting(AdjustingData { drag, .. }) => *drag = None,
+            _ => (),
+        }
+    }
+    fn on_scroll(&mut self, ctx: &mut WaylandContext, notches: i32, qh: &QueueHandle<WaylandApp>) {
+        let SelectionState::BeginSelection(SelectionData {
+            initial,
+            current,
+            pending,
+            ..
+        }) = &mut self.state
+        else {
+            return;
+        };
+        let size = ctx
+            .partial()
+            .expect("SelectionApp requires at least partial context")
+            .logical_size
+            .clone();
+
+        // Scroll up grows selection
+        let delta =
+            -notches.saturating_mul(i32::try_from(self.options.scroll_step).unwrap_or(i32::MAX));
+        if let Some(position) = pending.take() {
+            *current = position;
+        }
+        (initial.x, current.x) = utils::resize_span(initial.x, current.x, delta, size.x);
+        (initial.y, current.y) = utils::resize_span(initial.y, current.y, delta, size.y);
+
+        // Both corners are moved, erase everything and draw selection from scratch
+        {
+            let ctx = ctx
+                .full_mut()
+                .expect("SelectionApp requires full context to draw");
+            let Some(canvas) = self.buffers.canvas(&mut ctx.partial.pool) else {
+                return;
+            };
+            let backdrop = utils::Backdrop::new(&self.image, self.dim_factor());
+            utils::dim_rect(
+                utils::screen_rect(size.x, size.y),
+                canvas,
+                backdrop,
+                size.x as usize,
+                Some(&ctx.layer),
+            );
+        }
+        self.on_redraw(ctx, qh);
+    }
+
+    fn on_mouse_release(
+        &mut self,
+        ctx: &mut WaylandContext,
+        _pos: Point,
+        qh: &QueueHandle<WaylandApp>,
+    ) {
+        if let SelectionState::Adjusting(AdjustingData { drag, .. }) = &mut self.state {
+            *drag = None;
+            if self.options.march {
+                // Frames are not requested while ants are stopped
+                self.on_redraw(ctx, qh);
+            }
+            return;
+        }
+        let SelectionState::BeginSelection(SelectionData {
+            initial, current, ..
+        }) = &self.state
+        else {
+            return;
+        };
+
+        // Pointer slightly moved while clicking
+        let accidental = initial.distance(current) < self.options.min_size as f64;
+        // Dragging along one axis selects one pixel tall or wide strip
+        let window = self
+            .pointer
+            .as_ref()
+            .and_then(|pos| self.window_at(pos))
+            .cloned();
+        if let Some(rect) = Rectangle::from_two_points_or_line(initial.clone(), current.clone())
+            .filter(|_| !accidental)
+        {
+            self.finish(rect);
+        } else if let Some(window) = window {
+            // Corners are snapped, pointer is where user clicked
+            self.finish(window);
+        } else if self.options.allow_click {
+            self.finish(Rectangle::new(initial.clone(), 1, 1));
+        } else {
+            // assume click without dragging isn't a valid selection
+            self.state = SelectionState::Waiting;
+        }
+        // Selection may be released while Space is held
+        self.update_cursor(ctx);
+
+        // Discarded selection is erased as well
+        if let SelectionState::Adjusting(_) | SelectionState::Waiting = self.state {
+            self.on_redraw(ctx, qh);
+        }
+    }
+
+    /// Called on random redraws and on mouse movement
+    fn on_redraw(&mut self, ctx: &mut WaylandContext, qh: &QueueHandle<WaylandApp>) {
+        if self.recapture.is_some() {
+            // Overlay is hidden
+            return;
+        }
+
+        let shown = self.hide_loupe(ctx);
+        let drawn = self.draw(ctx);
+        let loupe_moved = self.show_loupe(ctx, shown);
+
+        if drawn || loupe_moved {
+            let layer = &ctx.full().expect("drawn above").layer;
+            utils::commit_drawing(layer, self.buffers.current(), qh);
+        }
     }
 }
 
 mod utils {
+    use std::ops::Deref;
+
     use smithay_client_toolkit::{
+        seat::keyboard::Keysym,
         shell::{wlr_layer::LayerSurface, WaylandSurface},
         shm::slot::Buffer,
     };
     use wayland_client::QueueHandle;
 
+    use super::{Border, DragKind, Edge, Handle};
     use crate::{
-        app::WaylandApp,
+        app::{font, WaylandApp},
         points::{Point, Rectangle},
     };
 
+    /// Distance between pointer and coordinates readout
+    const COORDS_LABEL_OFFSET: u32 = 16;
+
+    /// Restore dimmed screen under `previous` readout and draw coordinates of `pos` next to it.
+    /// Returns area covered by new readout.
+    pub fn update_coords_label(
+        previous: Option<Rectangle>,
+        pos: Option<Point>,
+        canvas: &mut [u8],
+        image: Backdrop,
+        width: u32,
+        height: u32,
+        layer: Option<&LayerSurface>,
+    ) -> Option<Rectangle> {
+        if let Some(rect) = previous {
+            dim_rect(
+                Rectangle::new(rect.start.clone(), rect.width - 1, rect.height - 1),
+                canvas,
+                image,
+                width as usize,
+                None,
+            );
+            damage_rect(&rect, layer);
+        }
+
+        let pos = pos?;
+        draw_label(
+            &format!("{},{}", pos.x, pos.y),
+            &pos,
+            canvas,
+            width,
+            height,
+            layer,
+        )
+    }
+
+    /// Draw `text` below right of `pos`, flipped near screen edges so it stays on screen.
+    /// Returns area covered by it, [`None`] if it does not fit into screen.
+    fn draw_label(
+        text: &str,
+        pos: &Point,
+        canvas: &mut [u8],
+        width: u32,
+        height: u32,
+        layer: Option<&LayerSurface>,
+    ) -> Option<Rectangle> {
+        let (label_width, label_height) = font::text_size(text);
+        if label_width > width || label_height > height {
+            return None;
+        }
+
+        let place = |pos: u32, size: u32, max: u32| {
+            if pos + COORDS_LABEL_OFFSET + size <= max {
+                pos + COORDS_LABEL_OFFSET
+            } else {
+                // Still clamped to screen if pointer is near both edges of small screen
+                pos.saturating_sub(COORDS_LABEL_OFFSET + size)
+                    .min(max - size)
+            }
+        };
+        let rect = Rectangle::new(
+            Point::new(
+                place(pos.x, label_width, width),
+                place(pos.y, label_height, height),
+            ),
+            label_width,
+            label_height,
+        );
+
+        font::draw_text(text, rect.start.x, rect.start.y, canvas, width);
+        damage_rect(&rect, layer);
+
+        Some(rect)
+    }
+
+    /// Draw `WxH` size of selection between corners `a` and `b` next to `b`. Returns area
+    /// covered by it.
+    pub fn draw_size_label(
+        a: &Point,
+        b: &Point,
+        canvas: &mut [u8],
+        width: u32,
+        height: u32,
+        layer: Option<&LayerSurface>,
+    ) -> Option<Rectangle> {
+        let rect = Rectangle::from_two_points_or_line(a.clone(), b.clone())?;
+        let text = format!("{}x{}", rect.width, rect.height);
+
+        draw_label(&text, b, canvas, width, height, layer)
+    }
+
+    /// Restore overlay under `label`: pixels of selection between corners `a` and `b` are
+    /// copied, others are dimmed (with falloff in `spotlight` mode).
+    pub fn restore_label(
+        label: Rectangle,
+        corners: (&Point, &Point),
+        spotlight: bool,
+        canvas: &mut [u8],
+        image: Backdrop,
+        width: usize,
+        layer: Option<&LayerSurface>,
+    ) {
+        let label = Rectangle::new(label.start, label.width - 1, label.height - 1);
+        restore_area(label, corners, spotlight, canvas, image, width, layer);
+    }
+
+    /// Same as [`restore_label`], but `label` includes its right and bottom edges.
+    fn restore_area(
+        label: Rectangle,
+        (a, b): (&Point, &Point),
+        spotlight: bool,
+        canvas: &mut [u8],
+        image: Backdrop,
+        width: usize,
+        layer: Option<&LayerSurface>,
+    ) {
+        if spotlight {
+            self::spotlight(a, b, label, canvas, image, width, layer);
+            return;
+        }
+
+        dim_rect(label.clone(), canvas, image, width, layer);
+
+        // Both rectangles include right and bottom edges
+        let (x0, x1) = (
+            a.x.min(b.x).max(label.start.x),
+            a.x.max(b.x).min(label.start.x + label.width),
+        );
+        let (y0, y1) = (
+            a.y.min(b.y).max(label.start.y),
+            a.y.max(b.y).min(label.start.y + label.height),
+        );
+        if x0 <= x1 && y0 <= y1 {
+            let common = Rectangle::new(Point::new(x0, y0), x1 - x0, y1 - y0);
+            copy_rect(common, canvas, &image, width, layer);
+        }
+    }
+
+    fn damage_rect(rect: &Rectangle, layer: Option<&LayerSurface>) {
+        if let Some(layer) = layer {
+            layer.wl_surface().damage_buffer(
+                rect.start.x as i32,
+                rect.start.y as i32,
+                rect.width as i32,
+                rect.height as i32,
+            );
+        }
+    }
+
     pub fn update_selection_partial(
         init: Point,
         previous: Point,
         pending: Point,
         canvas: &mut [u8],
-        image: &[u8],
+        image: Backdrop,
         width: usize,
         layer: Option<&LayerSurface>,
     ) {
@@ -370,14 +1815,14 @@ mod utils {
             if df_init_pending_x > df_init_previous_x {
                 let proj_previous_x = Point::new(previous.x, init.y);
                 if let Some(rect) = Rectangle::from_two_points(pending.clone(), proj_previous_x) {
-                    copy_rect(rect, canvas, image, width, layer);
+                    copy_rect(rect, canvas, &image, width, layer);
                 }
             }
 
             if df_init_pending_y > df_init_previous_y {
                 let proj_previous_y = Point::new(init.x, previous.y);
                 if let Some(rect) = Rectangle::from_two_points(pending.clone(), proj_previous_y) {
-                    copy_rect(rect, canvas, image, width, layer);
+                    copy_rect(rect, canvas, &image, width, layer);
                 }
             }
         } else {
@@ -386,8 +1831,172 @@ mod utils {
             }
 
             if let Some(rect) = Rectangle::from_two_points(init.clone(), pending.clone()) {
-                copy_rect(rect, canvas, image, width, layer);
+                copy_rect(rect, canvas, &image, width, layer);
+            }
+        }
+    }
+
+    /// Strips of `size` pixels along inner edges of rectangle with corners `a` and `b`, they
+    /// include right and bottom edges and overlap in corners.
+    fn border_strips(a: &Point, b: &Point, size: u32) -> [Rectangle; 4] {
+        let (lo, hi) = (
+            Point::new(a.x.min(b.x), a.y.min(b.y)),
+            Point::new(a.x.max(b.x), a.y.max(b.y)),
+        );
+        let (width, height) = (hi.x - lo.x, hi.y - lo.y);
+        let (size_x, size_y) = (
+            size.saturating_sub(1).min(width),
+            size.saturating_sub(1).min(height),
+        );
+
+        [
+            Rectangle::new(lo.clone(), width, size_y),
+            Rectangle::new(Point::new(lo.x, hi.y - size_y), width, size_y),
+            Rectangle::new(lo.clone(), size_x, height),
+            Rectangle::new(Point::new(hi.x - size_x, lo.y), size_x, height),
+        ]
+    }
+
+    /// Draw `border` inside selection with corners `a` and `b`. Translucent color is blended
+    /// with `image`, so border may be drawn over itself.
+    pub fn draw_border(
+        (a, b): (&Point, &Point),
+        border: Border,
+        canvas: &mut [u8],
+        image: &[u8],
+        width: usize,
+        layer: Option<&LayerSurface>,
+    ) {
+        let [red, green, blue, alpha] = border.color.map(u32::from);
+        // Xrgb8888 is stored as little endian
+        let color = [blue, green, red, 255];
+        for strip in border_strips(a, b, border.width) {
+            let Some(strip) = clamp_rect(strip, canvas, width) else {
+                continue;
+            };
+            for row in strip.start.y..=strip.start.y + strip.height {
+                let start = (row as usize * width + strip.start.x as usize) * 4;
+                let end = start + (strip.width + 1) as usize * 4;
+                for (i, pixel) in canvas[start..end].iter_mut().enumerate() {
+                    let under = image[start + i] as u32;
+                    let over = color[i % 4];
+                    *pixel = ((over * alpha + under * (255 - alpha)) / 255) as u8;
+                }
             }
+            damage_inclusive_rect(&strip, layer);
+        }
+    }
+
+    /// Length of dash of marching ants
+    const ANT_LENGTH: u32 = 4;
+
+    /// Draw black and white dashes on edges of `rect` (its right and bottom edges are outside of
+    /// it), moved clockwise by `phase` pixels.
+    pub fn draw_ants(
+        rect: &Rectangle,
+        phase: u32,
+        canvas: &mut [u8],
+        width: u32,
+        layer: Option<&LayerSurface>,
+    ) {
+        let Some(rect) = clamp_rect(
+            Rectangle::new(rect.start.clone(), rect.width - 1, rect.height - 1),
+            canvas,
+            width as usize,
+        ) else {
+            return;
+        };
+        let (lo, hi) = (
+            rect.start.clone(),
+            Point::new(rect.start.x + rect.width, rect.start.y + rect.height),
+        );
+
+        // Every edge ends before the next one starts, so corners are drawn once
+        let top = (lo.x..hi.x).map(|x| (x, lo.y));
+        let right = (lo.y..hi.y).map(|y| (hi.x, y));
+        let bottom = (lo.x + 1..=hi.x).rev().map(|x| (x, hi.y));
+        let left = (lo.y + 1..=hi.y).rev().map(|y| (lo.x, y));
+        let period = ANT_LENGTH * 2;
+        let shift = period - phase % period;
+        for (i, (x, y)) in top.chain(right).chain(bottom).chain(left).enumerate() {
+            let white = ((i as u32 % period + shift) / ANT_LENGTH).is_multiple_of(2);
+            let pos = (y * width + x) as usize * 4;
+            canvas[pos..pos + 4].fill(if white { 255 } else { 0 });
+        }
+
+        for edge in [
+            Rectangle::new(lo.clone(), rect.width, 0),
+            Rectangle::new(Point::new(lo.x, hi.y), rect.width, 0),
+            Rectangle::new(lo.clone(), 0, rect.height),
+            Rectangle::new(Point::new(hi.x, lo.y), 0, rect.height),
+        ] {
+            damage_inclusive_rect(&edge, layer);
+        }
+    }
+
+    /// Dim edges of `rect` (its right and bottom edges are outside of it), erasing
+    /// [`draw_ants`].
+    pub fn dim_outline(
+        rect: &Rectangle,
+        canvas: &mut [u8],
+        image: Backdrop,
+        width: usize,
+        layer: Option<&LayerSurface>,
+    ) {
+        let end = Point::new(
+            rect.start.x + rect.width - 1,
+            rect.start.y + rect.height - 1,
+        );
+        for edge in border_strips(&rect.start, &end, 1) {
+            dim_rect(edge, canvas, image, width, layer);
+        }
+    }
+
+    /// Move `pos` to the nearest edge of `windows` along each axis, if it is closer than
+    /// `distance`. Only edges of windows next to `pos` along other axis are considered.
+    pub fn snap_to_windows(pos: Point, windows: &[Rectangle], distance: u32) -> Point {
+        let nearest = |v: u32, edges: &mut dyn Iterator<Item = u32>| {
+            edges
+                .filter(|edge| edge.abs_diff(v) < distance)
+                .min_by_key(|edge| edge.abs_diff(v))
+                .unwrap_or(v)
+        };
+        let near =
+            |v: u32, start: u32, size: u32| v + distance > start && v < start + size + distance;
+
+        // Selection between corners does not include the far one, so window is captured
+        // exactly with its right and bottom edges just outside of it
+        let x = nearest(
+            pos.x,
+            &mut windows
+                .iter()
+                .filter(|w| near(pos.y, w.start.y, w.height))
+                .flat_map(|w| [w.start.x, w.start.x + w.width]),
+        );
+        let y = nearest(
+            pos.y,
+            &mut windows
+                .iter()
+                .filter(|w| near(pos.x, w.start.x, w.width))
+                .flat_map(|w| [w.start.y, w.start.y + w.height]),
+        );
+
+        Point::new(x, y)
+    }
+
+    /// Erase border of `width` pixels drawn inside `previous` selection, restoring its pixels
+    /// as they are for `current` selection, see [`restore_label`].
+    pub fn restore_border(
+        previous: (&Point, &Point),
+        current: (&Point, &Point),
+        border_width: u32,
+        canvas: &mut [u8],
+        image: Backdrop,
+        width: usize,
+        layer: Option<&LayerSurface>,
+    ) {
+        for strip in border_strips(previous.0, previous.1, border_width) {
+            restore_area(strip, current, false, canvas, image, width, layer);
         }
     }
 
@@ -402,6 +2011,275 @@ mod utils {
         layer.commit();
     }
 
+    // NOTE: `copy_rect` and `dim_rect` include right and bottom edges of rectangle, so that
+    // rectangle made with `Rectangle::from_two_points` covers both of its points (they are
+    // corners of selection). Rectangle of whole `width`x`height` screen is `screen_rect`.
+
+    /// Rectangle covering every pixel of `width`x`height` screen for `copy_rect` and `dim_rect`.
+    pub fn screen_rect(width: u32, height: u32) -> Rectangle {
+        Rectangle::new(
+            Point::new(0, 0),
+            width.saturating_sub(1),
+            height.saturating_sub(1),
+        )
+    }
+
+    /// Damage `rect` which includes its right and bottom edges.
+    fn damage_inclusive_rect(rect: &Rectangle, layer: Option<&LayerSurface>) {
+        damage_rect(
+            &Rectangle::new(rect.start.clone(), rect.width + 1, rect.height + 1),
+            layer,
+        );
+    }
+
+    /// Region selected by number `key`: `1`-`4` are quarters of screen (left to right, top to
+    /// bottom), `5` and `6` are left and right halves, `0` is whole screen.
+    pub fn preset_rect(key: Keysym, width: u32, height: u32) -> Option<Rectangle> {
+        let (half_w, half_h) = (width / 2, height / 2);
+        let (x, y, w, h) = match key {
+            Keysym::_0 => (0, 0, width, height),
+            Keysym::_1 => (0, 0, half_w, half_h),
+            Keysym::_2 => (half_w, 0, width - half_w, half_h),
+            Keysym::_3 => (0, half_h, half_w, height - half_h),
+            Keysym::_4 => (half_w, half_h, width - half_w, height - half_h),
+            Keysym::_5 => (0, 0, half_w, height),
+            Keysym::_6 => (half_w, 0, width - half_w, height),
+            _ => return None,
+        };
+        if w == 0 || h == 0 {
+            return None;
+        }
+
+        Some(Rectangle::new(Point::new(x, y), w, h))
+    }
+
+    /// Pixels of image around pointer shown by loupe along each side, odd so pointer is in the
+    /// middle
+    const LOUPE_PIXELS: u32 = 15;
+    /// Size of image pixel in loupe
+    const LOUPE_ZOOM: u32 = 8;
+    /// Distance between pointer and loupe
+    const LOUPE_OFFSET: u32 = 24;
+
+    /// Draw magnified `image` around `pos` above left of it, flipped near screen edges. Pixel
+    /// under pointer is outlined. Returns area covered by loupe, [`None`] if it does not fit
+    /// into screen.
+    pub fn draw_loupe(
+        pos: &Point,
+        canvas: &mut [u8],
+        image: &[u8],
+        width: u32,
+        height: u32,
+        layer: Option<&LayerSurface>,
+    ) -> Option<Rectangle> {
+        // One pixel border around magnified pixels
+        let side = LOUPE_PIXELS * LOUPE_ZOOM + 2;
+        if side > width || side > height {
+            return None;
+        }
+
+        let place = |pos: u32, max: u32| {
+            if pos >= LOUPE_OFFSET + side {
+                pos - LOUPE_OFFSET - side
+            } else {
+                (pos + LOUPE_OFFSET).min(max - side)
+            }
+        };
+        let rect = Rectangle::new(
+            Point::new(place(pos.x, width), place(pos.y, height)),
+            side,
+            side,
+        );
+
+        let half = LOUPE_PIXELS / 2;
+        let center = half * LOUPE_ZOOM + 1;
+        for y in 0..side {
+            for x in 0..side {
+                let border = x == 0 || y == 0 || x == side - 1 || y == side - 1;
+                // Outline of the middle image pixel
+                let marker = (center - 1..=center + LOUPE_ZOOM).contains(&x)
+                    && (center - 1..=center + LOUPE_ZOOM).contains(&y)
+                    && (x == center - 1
+                        || y == center - 1
+                        || x == center + LOUPE_ZOOM
+                        || y == center + LOUPE_ZOOM);
+
+                let pixel = if border || marker {
+                    [255; 4]
+                } else {
+                    let src_x = (pos.x + (x - 1) / LOUPE_ZOOM).checked_sub(half);
+                    let src_y = (pos.y + (y - 1) / LOUPE_ZOOM).checked_sub(half);
+                    match (src_x, src_y) {
+                        (Some(sx), Some(sy)) if sx < width && sy < height => {
+                            let src = (sy * width + sx) as usize * 4;
+                            image[src..src + 4].try_into().expect("4 bytes")
+                        }
+                        // Outside of screen
+                        _ => [0; 4],
+                    }
+                };
+
+                let dst = ((rect.start.y + y) * width + rect.start.x + x) as usize * 4;
+                canvas[dst..dst + 4].copy_from_slice(&pixel);
+            }
+        }
+
+        damage_rect(&rect, layer);
+        Some(rect)
+    }
+
+    /// Half of side of handle square in edit mode, pointer within it grabs handle
+    pub const HANDLE_RADIUS: u32 = 4;
+
+    /// What is dragged by pressing button at `pos` over adjusted `rect` in edit mode: handle
+    /// under pointer, whole selection if pointer is inside, [`None`] otherwise.
+    pub fn hit_test(rect: &Rectangle, pos: &Point) -> Option<DragKind> {
+        let handle = Handle::ALL.into_iter().find(|handle| {
+            let center = handle.position(rect);
+            center.x.abs_diff(pos.x) <= HANDLE_RADIUS && center.y.abs_diff(pos.y) <= HANDLE_RADIUS
+        });
+
+        match handle {
+            Some(handle) => Some(DragKind::Resize(handle)),
+            None if rect.contains(pos) => Some(DragKind::Move),
+            None => None,
+        }
+    }
+
+    /// Move sides of `rect` grabbed with `handle` to `pos`. Sides never cross each other, so
+    /// selection is at least one pixel wide and tall.
+    pub fn resize_by_handle(rect: &Rectangle, handle: Handle, pos: &Point) -> Rectangle {
+        // Both ends are inclusive
+        let resize = |edge, start: u32, len: u32, pos: u32| {
+            let end = start + len - 1;
+            match edge {
+                Edge::Start => (pos.min(end), end),
+                Edge::Middle => (start, end),
+                Edge::End => (start, pos.max(start)),
+            }
+        };
+        let (left, right) = resize(handle.x, rect.start.x, rect.width, pos.x);
+        let (top, bottom) = resize(handle.y, rect.start.y, rect.height, pos.y);
+
+        Rectangle::new(Point::new(left, top), right - left + 1, bottom - top + 1)
+    }
+
+    /// Draw white square of handle centered at `pos`.
+    pub fn fill_handle(pos: Point, canvas: &mut [u8], width: u32, layer: Option<&LayerSurface>) {
+        let rect = Rectangle::new(
+            Point::new(
+                pos.x.saturating_sub(HANDLE_RADIUS),
+                pos.y.saturating_sub(HANDLE_RADIUS),
+            ),
+            HANDLE_RADIUS * 2,
+            HANDLE_RADIUS * 2,
+        );
+        let Some(rect) = clamp_rect(rect, canvas, width as usize) else {
+            return;
+        };
+        for row in rect.start.y..=rect.start.y + rect.height {
+            let start = (row * width + rect.start.x) as usize * 4;
+            canvas[start..start + (rect.width + 1) as usize * 4].fill(255);
+        }
+
+        damage_inclusive_rect(&rect, layer);
+    }
+
+    /// Width of falloff around selection in spotlight mode
+    pub const SPOTLIGHT_RAMP: u32 = 24;
+
+    /// Dim `area` (clamped to canvas, includes its right and bottom edges) so that rectangle
+    /// with corners `a` and `b` is not dimmed and dimming grows to [`Backdrop::dim`] at
+    /// [`SPOTLIGHT_RAMP`] distance from it.
+    pub fn spotlight(
+        a: &Point,
+        b: &Point,
+        area: Rectangle,
+        canvas: &mut [u8],
+        image: Backdrop,
+        width: usize,
+        layer: Option<&LayerSurface>,
+    ) {
+        if image.dim == 0 {
+            return copy_rect(area, canvas, &image, width, layer);
+        }
+        let Some(area) = clamp_rect(area, canvas, width) else {
+            return;
+        };
+        let (lo_x, hi_x) = (a.x.min(b.x), a.x.max(b.x));
+        let (lo_y, hi_y) = (a.y.min(b.y), a.y.max(b.y));
+
+        for row in area.start.y..=area.start.y + area.height {
+            let dy = lo_y.saturating_sub(row).max(row.saturating_sub(hi_y)) as f32;
+            for col in area.start.x..=area.start.x + area.width {
+                let dx = lo_x.saturating_sub(col).max(col.saturating_sub(hi_x)) as f32;
+                let ramp = (dx.hypot(dy) / SPOTLIGHT_RAMP as f32).min(1.0);
+                let factor = (image.dim as f32 * ramp) as u8;
+
+                let pos = (row as usize * width + col as usize) * 4;
+                for i in pos..pos + 4 {
+                    canvas[i] = dim_u8_by(factor, image[i]);
+                }
+            }
+        }
+
+        damage_inclusive_rect(&area, layer);
+    }
+
+    /// Move `a` and `b` coordinates apart by `delta` each (or closer if it is negative) within
+    /// `0..size`. Coordinates never cross, if they would, they are kept as is.
+    pub fn resize_span(a: u32, b: u32, delta: i32, size: u32) -> (u32, u32) {
+        let (lo, hi) = (a.min(b) as i64, a.max(b) as i64);
+        let max = size.saturating_sub(1) as i64;
+        let (new_lo, new_hi) = (
+            (lo - delta as i64).clamp(0, max),
+            (hi + delta as i64).clamp(0, max),
+        );
+        if new_lo >= new_hi {
+            return (a, b);
+        }
+
+        if a <= b {
+            (new_lo as u32, new_hi as u32)
+        } else {
+            (new_hi as u32, new_lo as u32)
+        }
+    }
+
+    /// Clamp `rect` (which includes its right and bottom edges) to Xrgb8888 `canvas` with
+    /// `width` pixels per row. Returns [`None`] if `rect` is completely outside of it.
+    fn clamp_rect(rect: Rectangle, canvas: &[u8], width: usize) -> Option<Rectangle> {
+        let height = canvas.len() / 4 / width.max(1);
+        let (max_x, max_y) = (
+            (width as u32).checked_sub(1)?,
+            (height as u32).checked_sub(1)?,
+        );
+        if rect.start.x > max_x || rect.start.y > max_y {
+            return None;
+        }
+
+        Some(Rectangle::new(
+            rect.start.clone(),
+            rect.width.min(max_x - rect.start.x),
+            rect.height.min(max_y - rect.start.y),
+        ))
+    }
+
+    /// Copy `regions` from `image`, so they are not dimmed.
+    pub fn copy_regions(
+        regions: &[Rectangle],
+        canvas: &mut [u8],
+        image: &[u8],
+        width: usize,
+        layer: Option<&LayerSurface>,
+    ) {
+        for rect in regions {
+            // Copied rectangle is inclusive
+            let rect = Rectangle::new(rect.start.clone(), rect.width - 1, rect.height - 1);
+            copy_rect(rect, canvas, image, width, layer);
+        }
+    }
+
     pub fn copy_rect(
         rect: Rectangle,
         canvas: &mut [u8],
@@ -409,59 +2287,75 @@ mod utils {
         width: usize,
         layer: Option<&LayerSurface>,
     ) {
+        let Some(rect) = clamp_rect(rect, canvas, width) else {
+            return;
+        };
         for row in rect.start.y..=rect.start.y + rect.height {
             let row = width * row as usize * 4;
             let start = row + rect.start.x as usize * 4;
             let end = start + (1 + rect.width) as usize * 4;
             canvas[start..end].copy_from_slice(&image[start..end]);
         }
-        if let Some(layer) = layer {
-            layer.wl_surface().damage_buffer(
-                rect.start.x as i32,
-                rect.start.y as i32,
-                rect.width as i32,
-                rect.height as i32,
-            );
+        damage_inclusive_rect(&rect, layer);
+    }
+
+    /// Frozen screen under overlay and how much of brightness is removed from its dimmed
+    /// pixels, out of 256. Functions that only copy pixels take it as slice.
+    #[derive(Clone, Copy)]
+    pub struct Backdrop<'a> {
+        pub image: &'a [u8],
+        pub dim: u8,
+    }
+
+    impl<'a> Backdrop<'a> {
+        pub fn new(image: &'a [u8], dim: u8) -> Self {
+            Self { image, dim }
         }
     }
 
-    pub fn dim_u8(src: u8) -> u8 {
-        const DIM_FACTOR: u8 = 128;
+    impl Deref for Backdrop<'_> {
+        type Target = [u8];
+
+        fn deref(&self) -> &[u8] {
+            self.image
+        }
+    }
 
-        (src as usize * DIM_FACTOR as usize / 256) as u8
+    pub fn dim_u8_by(factor: u8, src: u8) -> u8 {
+        (src as usize * (256 - factor as usize) / 256) as u8
     }
 
     pub fn dim_rect(
         rect: Rectangle,
         canvas: &mut [u8],
-        image: &[u8],
+        image: Backdrop,
         width: usize,
         layer: Option<&LayerSurface>,
     ) {
+        if image.dim == 0 {
+            return copy_rect(rect, canvas, &image, width, layer);
+        }
+        let Some(rect) = clamp_rect(rect, canvas, width) else {
+            return;
+        };
+        let factor = image.dim;
         for col in rect.start.x..=(rect.start.x + rect.width) {
             for row in rect.start.y..=(rect.start.y + rect.height) {
                 let pos = row as usize * width + col as usize;
-                canvas[pos * 4] = dim_u8(image[pos * 4]);
-                canvas[pos * 4 + 1] = dim_u8(image[pos * 4 + 1]);
-                canvas[pos * 4 + 2] = dim_u8(image[pos * 4 + 2]);
-                canvas[pos * 4 + 3] = dim_u8(image[pos * 4 + 3]);
+                canvas[pos * 4] = dim_u8_by(factor, image[pos * 4]);
+                canvas[pos * 4 + 1] = dim_u8_by(factor, image[pos * 4 + 1]);
+                canvas[pos * 4 + 2] = dim_u8_by(factor, image[pos * 4 + 2]);
+                canvas[pos * 4 + 3] = dim_u8_by(factor, image[pos * 4 + 3]);
             }
         }
 
-        if let Some(layer) = layer {
-            layer.wl_surface().damage_buffer(
-                rect.start.x as i32,
-                rect.start.y as i32,
-                rect.width as i32,
-                rect.height as i32,
-            );
-        }
+        damage_inclusive_rect(&rect, layer);
     }
 
     pub fn dim_crosshair(
         pos: Point,
         canvas: &mut [u8],
-        image: &[u8],
+        image: Backdrop,
         width: u32,
         height: u32,
         layer: Option<&LayerSurface>,
@@ -469,18 +2363,18 @@ mod utils {
         // Vertical line
         for ptr in 0..height {
             let ptr = (pos.x + ptr * width) as usize * 4;
-            canvas[ptr] = dim_u8(image[ptr]);
-            canvas[ptr + 1] = dim_u8(image[ptr + 1]);
-            canvas[ptr + 2] = dim_u8(image[ptr + 2]);
-            canvas[ptr + 3] = dim_u8(image[ptr + 3]);
+            canvas[ptr] = dim_u8_by(image.dim, image[ptr]);
+            canvas[ptr + 1] = dim_u8_by(image.dim, image[ptr + 1]);
+            canvas[ptr + 2] = dim_u8_by(image.dim, image[ptr + 2]);
+            canvas[ptr + 3] = dim_u8_by(image.dim, image[ptr + 3]);
         }
         // Horizontal line
         for ptr in width * pos.y..width * (pos.y + 1) {
             let ptr = ptr as usize * 4;
-            canvas[ptr] = dim_u8(image[ptr]);
-            canvas[ptr + 1] = dim_u8(image[ptr + 1]);
-            canvas[ptr + 2] = dim_u8(image[ptr + 2]);
-            canvas[ptr + 3] = dim_u8(image[ptr + 3]);
+            canvas[ptr] = dim_u8_by(image.dim, image[ptr]);
+            canvas[ptr + 1] = dim_u8_by(image.dim, image[ptr + 1]);
+            canvas[ptr + 2] = dim_u8_by(image.dim, image[ptr + 2]);
+            canvas[ptr + 3] = dim_u8_by(image.dim, image[ptr + 3]);
         }
 
         if let Some(layer) = layer {
@@ -521,3 +2415,356 @@ mod utils {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use smithay_client_toolkit::seat::keyboard::Keysym;
+
+    use super::utils::{
+        copy_rect, dim_rect, draw_ants, draw_border, draw_loupe, fill_crosshair, hit_test,
+        preset_rect, resize_by_handle, resize_span, restore_border, restore_label, screen_rect,
+        snap_to_windows, spotlight, Backdrop, SPOTLIGHT_RAMP,
+    };
+    use super::{Border, DragKind, Edge, Handle};
+    use crate::points::{Point, Rectangle};
+
+    #[test]
+    fn rects_are_clamped_to_canvas() {
+        // 4x3 canvas
+        let image = vec![200; 4 * 3 * 4];
+        let mut canvas = vec![0; 4 * 3 * 4];
+
+        // Reaches past the right and bottom edges
+        copy_rect(
+            Rectangle::new(Point::new(2, 1), 4, 4),
+            &mut canvas,
+            &image,
+            4,
+            None,
+        );
+        assert_eq!(canvas[(4 * 3 - 1) * 4], 200);
+        assert_eq!(canvas[(4 + 1) * 4], 0);
+
+        // Touches the edges exactly
+        dim_rect(
+            Rectangle::new(Point::new(0, 0), 3, 2),
+            &mut canvas,
+            Backdrop::new(&image, 128),
+            4,
+            None,
+        );
+        assert!(canvas.iter().all(|&c| c == 100));
+
+        // Completely outside
+        dim_rect(
+            Rectangle::new(Point::new(4, 0), 1, 1),
+            &mut canvas,
+            Backdrop::new(&image, 128),
+            4,
+            None,
+        );
+    }
+
+    #[test]
+    fn screen_rect_dims_every_pixel() {
+        let image = vec![200; 5 * 3 * 4];
+        let mut canvas = vec![0; 5 * 3 * 4];
+
+        dim_rect(
+            screen_rect(5, 3),
+            &mut canvas,
+            Backdrop::new(&image, 128),
+            5,
+            None,
+        );
+        assert!(canvas.iter().all(|&c| c == 100));
+
+        // Not dimmed at all, copied as is
+        dim_rect(
+            screen_rect(5, 3),
+            &mut canvas,
+            Backdrop::new(&image, 0),
+            5,
+            None,
+        );
+        assert!(canvas.iter().all(|&c| c == 200));
+    }
+
+    #[test]
+    fn spans_are_resized_within_screen() {
+        assert_eq!(resize_span(10, 20, 5, 100), (5, 25));
+        assert_eq!(resize_span(20, 10, 5, 100), (25, 5));
+        assert_eq!(resize_span(2, 97, 5, 100), (0, 99));
+        assert_eq!(resize_span(10, 20, -3, 100), (13, 17));
+        // Would cross
+        assert_eq!(resize_span(10, 20, -5, 100), (10, 20));
+    }
+
+    #[test]
+    fn spotlight_falls_off() {
+        let width = 100;
+        let image = vec![200; width * 10 * 4];
+        let mut canvas = vec![0; width * 10 * 4];
+
+        spotlight(
+            &Point::new(10, 0),
+            &Point::new(20, 9),
+            screen_rect(width as u32, 10),
+            &mut canvas,
+            Backdrop::new(&image, 128),
+            width,
+            None,
+        );
+        let pixel = |x: u32| canvas[x as usize * 4];
+
+        // Selection itself, including its edges, is not dimmed
+        assert_eq!(pixel(10), 200);
+        assert_eq!(pixel(20), 200);
+        // Brightness decreases with distance
+        assert!(pixel(21) < 200 && pixel(21) > pixel(30));
+        assert_eq!(pixel(20 + SPOTLIGHT_RAMP), 100);
+        assert_eq!(pixel(99), 100);
+    }
+
+    #[test]
+    fn presets_cover_screen() {
+        let quarters = [Keysym::_1, Keysym::_2, Keysym::_3, Keysym::_4]
+            .map(|key| preset_rect(key, 1921, 1081).unwrap());
+        assert_eq!(quarters[0], Rectangle::new(Point::new(0, 0), 960, 540));
+        assert_eq!(quarters[3], Rectangle::new(Point::new(960, 540), 961, 541));
+        let area: u32 = quarters.iter().map(|r| r.width * r.height).sum();
+        assert_eq!(area, 1921 * 1081);
+
+        assert_eq!(
+            preset_rect(Keysym::_6, 1921, 1081),
+            Some(Rectangle::new(Point::new(960, 0), 961, 1081))
+        );
+        assert_eq!(
+            preset_rect(Keysym::_0, 1921, 1081),
+            Some(Rectangle::new(Point::new(0, 0), 1921, 1081))
+        );
+        assert_eq!(preset_rect(Keysym::_5, 1, 1), None);
+        assert_eq!(preset_rect(Keysym::_9, 100, 100), None);
+    }
+
+    #[test]
+    fn handles_are_hit_before_inside() {
+        let rect = Rectangle::new(Point::new(10, 10), 100, 50);
+
+        assert_eq!(
+            hit_test(&rect, &Point::new(12, 8)),
+            Some(DragKind::Resize(Handle::new(Edge::Start, Edge::Start)))
+        );
+        assert_eq!(
+            hit_test(&rect, &Point::new(60, 59)),
+            Some(DragKind::Resize(Handle::new(Edge::Middle, Edge::End)))
+        );
+        assert_eq!(hit_test(&rect, &Point::new(40, 30)), Some(DragKind::Move));
+        assert_eq!(hit_test(&rect, &Point::new(40, 70)), None);
+    }
+
+    #[test]
+    fn handles_resize_without_crossing() {
+        let rect = Rectangle::new(Point::new(10, 10), 100, 50);
+
+        assert_eq!(
+            resize_by_handle(
+                &rect,
+                Handle::new(Edge::End, Edge::Middle),
+                &Point::new(149, 0)
+            ),
+            Rectangle::new(Point::new(10, 10), 140, 50)
+        );
+        assert_eq!(
+            resize_by_handle(
+                &rect,
+                Handle::new(Edge::Start, Edge::Start),
+                &Point::new(0, 5)
+            ),
+            Rectangle::new(Point::new(0, 5), 110, 55)
+        );
+        // Dragged past the opposite side
+        assert_eq!(
+            resize_by_handle(
+                &rect,
+                Handle::new(Edge::Start, Edge::End),
+                &Point::new(200, 0)
+            ),
+            Rectangle::new(Point::new(109, 10), 1, 1)
+        );
+    }
+
+    #[test]
+    fn label_is_restored_by_selection() {
+        // 6x1 canvas, selection covers pixels 1..=2, label covers 2..=4
+        let image = vec![200; 6 * 4];
+        let mut canvas = vec![0; 6 * 4];
+
+        restore_label(
+            Rectangle::new(Point::new(2, 0), 3, 1),
+            (&Point::new(2, 0), &Point::new(1, 0)),
+            false,
+            &mut canvas,
+            Backdrop::new(&image, 128),
+            6,
+            None,
+        );
+        let pixels: Vec<u8> = canvas.chunks(4).map(|p| p[0]).collect();
+
+        assert_eq!(pixels, [0, 0, 200, 100, 100, 0]);
+    }
+
+    #[test]
+    fn border_is_drawn_inside_and_erased() {
+        // 6x6 canvas, selection covers 0..=4 in both directions
+        let image = vec![200; 6 * 6 * 4];
+        let mut canvas = vec![0; 6 * 6 * 4];
+        let border = Border {
+            color: [10, 20, 30, 255],
+            width: 2,
+        };
+        let (a, b) = (Point::new(0, 0), Point::new(4, 4));
+        copy_rect(
+            Rectangle::new(a.clone(), 4, 4),
+            &mut canvas,
+            &image,
+            6,
+            None,
+        );
+        draw_border((&a, &b), border, &mut canvas, &image, 6, None);
+
+        let pixel = |canvas: &[u8], x: usize, y: usize| canvas[(y * 6 + x) * 4..][..4].to_vec();
+        assert_eq!(pixel(&canvas, 0, 0), [30, 20, 10, 255]);
+        assert_eq!(pixel(&canvas, 1, 3), [30, 20, 10, 255]);
+        assert_eq!(pixel(&canvas, 2, 2), [200; 4]);
+        assert_eq!(pixel(&canvas, 5, 5), [0; 4]);
+
+        // Selection shrinks to 0..=2, old border is restored as selection or dimmed screen
+        let c = Point::new(2, 2);
+        restore_border(
+            (&a, &b),
+            (&a, &c),
+            border.width,
+            &mut canvas,
+            Backdrop::new(&image, 128),
+            6,
+            None,
+        );
+        assert_eq!(pixel(&canvas, 1, 1), [200; 4]);
+        assert_eq!(pixel(&canvas, 4, 4), [100; 4]);
+        assert_eq!(pixel(&canvas, 3, 0), [100; 4]);
+        assert_eq!(pixel(&canvas, 2, 2), [200; 4]);
+    }
+
+    #[test]
+    fn ants_march_clockwise() {
+        // 10x10 selection on 12x12 canvas, only its edges are drawn
+        let mut canvas = vec![100; 12 * 12 * 4];
+        let rect = Rectangle::new(Point::new(1, 1), 10, 10);
+        let top = |canvas: &[u8]| -> Vec<u8> { (1..=10).map(|x| canvas[(12 + x) * 4]).collect() };
+
+        draw_ants(&rect, 0, &mut canvas, 12, None);
+        assert_eq!(top(&canvas), [255, 255, 255, 255, 0, 0, 0, 0, 255, 255]);
+        // Inside, outside and bottom right corner of selection
+        assert_eq!(canvas[(2 * 12 + 2) * 4], 100);
+        assert_eq!(canvas[(11 * 12 + 11) * 4], 100);
+        assert_ne!(canvas[(10 * 12 + 10) * 4], 100);
+
+        draw_ants(&rect, 1, &mut canvas, 12, None);
+        assert_eq!(top(&canvas), [0, 255, 255, 255, 255, 0, 0, 0, 0, 255]);
+        draw_ants(&rect, 8, &mut canvas, 12, None);
+        assert_eq!(top(&canvas), [255, 255, 255, 255, 0, 0, 0, 0, 255, 255]);
+    }
+
+    #[test]
+    fn corners_snap_to_nearby_window_edges() {
+        let windows = [
+            Rectangle::new(Point::new(100, 100), 200, 100),
+            Rectangle::new(Point::new(500, 500), 10, 10),
+        ];
+
+        // Close to top left and bottom right corners
+        assert_eq!(
+            snap_to_windows(Point::new(95, 108), &windows, 12),
+            Point::new(100, 100)
+        );
+        assert_eq!(
+            snap_to_windows(Point::new(305, 195), &windows, 12),
+            Point::new(300, 200)
+        );
+        // Only one axis is close to edge
+        assert_eq!(
+            snap_to_windows(Point::new(150, 95), &windows, 12),
+            Point::new(150, 100)
+        );
+        // Edge is on line with point, but window is far away along it
+        assert_eq!(
+            snap_to_windows(Point::new(102, 400), &windows, 12),
+            Point::new(102, 400)
+        );
+        assert_eq!(
+            snap_to_windows(Point::new(50, 50), &[], 12),
+            Point::new(50, 50)
+        );
+    }
+
+    #[test]
+    fn loupe_magnifies_pixel_under_pointer() {
+        let (width, height) = (200, 200);
+        let mut image = vec![0; width * height * 4];
+        let pointer = (100 * width + 100) * 4;
+        image[pointer..pointer + 4].copy_from_slice(&[10, 20, 30, 0]);
+        let mut canvas = vec![0; width * height * 4];
+
+        let rect = draw_loupe(
+            &Point::new(100, 100),
+            &mut canvas,
+            &image,
+            width as u32,
+            height as u32,
+            None,
+        )
+        .unwrap();
+        // No room above left of pointer
+        assert_eq!(rect, Rectangle::new(Point::new(78, 78), 122, 122));
+
+        let pixel = |x: u32, y: u32| {
+            let i = ((rect.start.y + y) as usize * width + (rect.start.x + x) as usize) * 4;
+            canvas[i..i + 4].to_vec()
+        };
+        assert_eq!(pixel(0, 0), [255; 4]);
+        // Magnified pointer pixel and its outline
+        assert_eq!(pixel(60, 60), [10, 20, 30, 0]);
+        assert_eq!(pixel(56, 60), [255; 4]);
+        assert_eq!(pixel(10, 10), [0; 4]);
+    }
+
+    #[test]
+    fn crosshair_under_hidden_loupe_is_restored() {
+        let (width, height) = (300, 300);
+        let image = vec![0; width * height * 4];
+        let mut canvas = vec![0; width * height * 4];
+
+        // Pointer moved from (200, 200) to (100, 100), under loupe drawn above left of it
+        fill_crosshair(Point::new(100, 100), &mut canvas, 300, 300, None);
+        let rect = draw_loupe(&Point::new(200, 200), &mut canvas, &image, 300, 300, None).unwrap();
+        assert!(rect.contains(&Point::new(100, 100)));
+
+        // As SelectionApp::hide_loupe does while waiting
+        dim_rect(
+            Rectangle::new(rect.start.clone(), rect.width - 1, rect.height - 1),
+            &mut canvas,
+            Backdrop::new(&image, 128),
+            width,
+            None,
+        );
+        fill_crosshair(Point::new(100, 100), &mut canvas, 300, 300, None);
+
+        let pixel = |x: usize, y: usize| canvas[(y * width + x) * 4..][..4].to_vec();
+        for i in rect.start.x as usize..(rect.start.x + rect.width) as usize {
+            assert_eq!(pixel(i, 100), [255; 4]);
+            assert_eq!(pixel(100, i), [255; 4]);
+        }
+        assert_eq!(pixel(90, 90), [0; 4]);
+    }
+}
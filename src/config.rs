@@ -0,0 +1,411 @@
+use std::{
+    collections::BTreeMap,
+    ffi::OsString,
+    io,
+    path::{Path, PathBuf},
+};
+
+use clap::{Arg, ArgAction, Command};
+use prtsc_wayland::points::Rectangle;
+use serde::Deserialize;
+
+/// Defaults loaded from `$XDG_CONFIG_HOME/prtsc-wayland/config.toml`. Only the options below can
+/// be set, each sets default of [`crate::Args`] argument with the same name, values passed in
+/// command line override values from file.
+#[derive(Debug, Default, Deserialize)]
+pub struct Config {
+    pub output: Option<String>,
+    pub format: Option<String>,
+    pub quality: Option<u8>,
+    pub png_compression: Option<String>,
+    pub no_clobber: Option<bool>,
+    pub fullscreen: Option<bool>,
+    pub timeout: Option<u64>,
+    pub selection_only: Option<bool>,
+    pub selection_format: Option<String>,
+    pub cursor_shape: Option<String>,
+    pub show_coords: Option<bool>,
+    pub animate: Option<bool>,
+    pub no_animation: Option<bool>,
+    pub allow_click: Option<bool>,
+    pub min_size: Option<u32>,
+    pub scroll_step: Option<u32>,
+    pub history_size: Option<usize>,
+    pub confirm: Option<bool>,
+    pub edit: Option<bool>,
+    pub spotlight: Option<bool>,
+    pub dim: Option<f64>,
+    pub border_color: Option<String>,
+    pub border_width: Option<u32>,
+    pub no_label: Option<bool>,
+    pub zoom: Option<bool>,
+    pub snap: Option<u32>,
+    pub snap_windows: Option<bool>,
+    pub window_under_cursor: Option<bool>,
+    pub aspect: Option<String>,
+    pub keyboard_interactivity: Option<String>,
+    pub cancel_on_unfocus: Option<bool>,
+    pub pick_output: Option<bool>,
+    /// `[regions]` table of named "X,Y WxH" regions for `--region`, it has no argument to mirror
+    #[serde(default)]
+    pub regions: BTreeMap<String, String>,
+    /// Keys not known to this version, they are reported but do not fail loading
+    #[serde(flatten)]
+    unknown: toml::Table,
+}
+
+/// Where config is loaded from. It is chosen before arguments are parsed, so `--config` and
+/// `--no-config` are looked up in raw arguments, see [`Source::from_args`].
+#[derive(Debug, Default, PartialEq, Eq)]
+pub enum Source {
+    /// [`Config::path`]
+    #[default]
+    Default,
+    /// File passed with `--config`, it must exist
+    Path(PathBuf),
+    /// `--no-config`, built-in defaults only
+    None,
+}
+
+/// Environment variable with directory for output file, it overrides directory of output from
+/// config file but not output passed in command line. Other variables are handled by clap.
+pub const OUTPUT_DIR_VAR: &str = "PRTSC_OUTPUT_DIR";
+
+#[derive(Debug)]
+pub enum Error {
+    Read(io::Error),
+    Parse(toml::de::Error),
+}
+
+/// Failure to resolve `--region`, see [`resolve_region`].
+#[derive(Debug, PartialEq, Eq)]
+pub enum RegionError {
+    /// There is no region with this name in `[regions]`
+    Unknown,
+    /// Geometry of region is not "X,Y WxH", it is kept here
+    Invalid(String),
+}
+
+impl Config {
+    /// Path to config file: `$XDG_CONFIG_HOME/prtsc-wayland/config.toml` with fallback to
+    /// `$HOME/.config`. Returns [`None`] if neither variable is set.
+    pub fn path() -> Option<PathBuf> {
+        Some(crate::dirs::config_dir()?.join("config.toml"))
+    }
+
+    /// Load config from `source`. Missing file at [`Config::path`] is not an error and results
+    /// in empty config, unlike missing file passed explicitly.
+    pub fn load(source: Source) -> Result<Self, Error> {
+        let path = match source {
+            Source::Default => match Self::path() {
+                Some(path) => path,
+                None => return Ok(Self::default()),
+            },
+            Source::Path(path) => {
+                return Self::parse(&std::fs::read_to_string(path).map_err(Error::Read)?)
+            }
+            Source::None => return Ok(Self::default()),
+        };
+
+        match std::fs::read_to_string(path) {
+            Ok(s) => Self::parse(&s),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(Self::default()),
+            Err(e) => Err(Error::Read(e)),
+        }
+    }
+
+    /// Keys of config file that are not known to this version (e.g. typos or options of newer
+    /// version).
+    pub fn unknown_keys(&self) -> impl Iterator<Item = &str> {
+        self.unknown.keys().map(String::as_str)
+    }
+
+    pub fn parse(s: &str) -> Result<Self, Error> {
+        toml::from_str(s).map_err(Error::Parse)
+    }
+
+    /// Replace default values of `cmd` arguments with values from config, so arguments from
+    /// command line still take precedence.
+    pub fn apply(self, mut cmd: Command) -> Command {
+        /// Flag set in config also takes explicit value, so it can be turned off with
+        /// `--flag=false`.
+        fn flag(arg: Arg, v: bool) -> Arg {
+            arg.action(ArgAction::Set)
+                .num_args(0..=1)
+                .require_equals(true)
+                .default_missing_value("true")
+                .default_value(if v { "true" } else { "false" })
+        }
+
+        if let Some(v) = self.output {
+            cmd = cmd.mut_arg("output", |a| a.default_value(v));
+        }
+        if let Some(v) = self.format {
+            cmd = cmd.mut_arg("format", |a| a.default_value(v));
+        }
+        if let Some(v) = self.quality {
+            cmd = cmd.mut_arg("quality", |a| a.default_value(v.to_string()));
+        }
+        if let Some(v) = self.png_compression {
+            cmd = cmd.mut_arg("png_compression", |a| a.default_value(v));
+        }
+        if let Some(v) = self.no_clobber {
+            cmd = cmd.mut_arg("no_clobber", |a| flag(a, v));
+        }
+        if let Some(v) = self.fullscreen {
+            cmd = cmd.mut_arg("fullscreen", |a| flag(a, v));
+        }
+        if let Some(v) = self.timeout {
+            cmd = cmd.mut_arg("timeout", |a| a.default_value(v.to_string()));
+        }
+        if let Some(v) = self.selection_only {
+            cmd = cmd.mut_arg("selection_only", |a| flag(a, v));
+        }
+        if let Some(v) = self.selection_format {
+            cmd = cmd.mut_arg("selection_format", |a| a.default_value(v));
+        }
+        if let Some(v) = self.cursor_shape {
+            cmd = cmd.mut_arg("cursor_shape", |a| a.default_value(v));
+        }
+        if let Some(v) = self.show_coords {
+            cmd = cmd.mut_arg("show_coords", |a| flag(a, v));
+        }
+        if let Some(v) = self.animate {
+            cmd = cmd.mut_arg("animate", |a| flag(a, v));
+        }
+        if let Some(v) = self.no_animation {
+            cmd = cmd.mut_arg("no_animation", |a| flag(a, v));
+        }
+        if let Some(v) = self.allow_click {
+            cmd = cmd.mut_arg("allow_click", |a| flag(a, v));
+        }
+        if let Some(v) = self.min_size {
+            cmd = cmd.mut_arg("min_size", |a| a.default_value(v.to_string()));
+        }
+        if let Some(v) = self.scroll_step {
+            cmd = cmd.mut_arg("scroll_step", |a| a.default_value(v.to_string()));
+        }
+        if let Some(v) = self.history_size {
+            cmd = cmd.mut_arg("history_size", |a| a.default_value(v.to_string()));
+        }
+        if let Some(v) = self.confirm {
+            cmd = cmd.mut_arg("confirm", |a| flag(a, v));
+        }
+        if let Some(v) = self.edit {
+            cmd = cmd.mut_arg("edit", |a| flag(a, v));
+        }
+        if let Some(v) = self.zoom {
+            cmd = cmd.mut_arg("zoom", |a| flag(a, v));
+        }
+        if let Some(v) = self.no_label {
+            cmd = cmd.mut_arg("no_label", |a| flag(a, v));
+        }
+        if let Some(v) = self.spotlight {
+            cmd = cmd.mut_arg("spotlight", |a| flag(a, v));
+        }
+        if let Some(v) = self.dim {
+            cmd = cmd.mut_arg("dim", |a| a.default_value(v.to_string()));
+        }
+        if let Some(v) = self.border_color {
+            cmd = cmd.mut_arg("border_color", |a| a.default_value(v));
+        }
+        if let Some(v) = self.border_width {
+            cmd = cmd.mut_arg("border_width", |a| a.default_value(v.to_string()));
+        }
+        if let Some(v) = self.snap {
+            cmd = cmd.mut_arg("snap", |a| a.default_value(v.to_string()));
+        }
+        if let Some(v) = self.snap_windows {
+            cmd = cmd.mut_arg("snap_windows", |a| flag(a, v));
+        }
+        if let Some(v) = self.window_under_cursor {
+            cmd = cmd.mut_arg("window_under_cursor", |a| flag(a, v));
+        }
+        if let Some(v) = self.aspect {
+            cmd = cmd.mut_arg("aspect", |a| a.default_value(v));
+        }
+        if let Some(v) = self.keyboard_interactivity {
+            cmd = cmd.mut_arg("keyboard_interactivity", |a| a.default_value(v));
+        }
+        if let Some(v) = self.cancel_on_unfocus {
+            cmd = cmd.mut_arg("cancel_on_unfocus", |a| flag(a, v));
+        }
+        if let Some(v) = self.pick_output {
+            cmd = cmd.mut_arg("pick_output", |a| flag(a, v));
+        }
+
+        cmd
+    }
+}
+
+/// Put file name of `output` into `dir`. Stdout (`-`) is kept as is.
+pub fn output_in_dir(output: &str, dir: &Path) -> String {
+    if output == "-" {
+        return output.to_owned();
+    }
+
+    let name = Path::new(output).file_name().unwrap_or(output.as_ref());
+    dir.join(name).to_string_lossy().into_owned()
+}
+
+/// Geometry of region `name` from `[regions]` table of config, parsed the same way as
+/// `--geometry`.
+pub fn resolve_region(
+    regions: &BTreeMap<String, String>,
+    name: &str,
+) -> Result<Rectangle, RegionError> {
+    let geometry = regions.get(name).ok_or(RegionError::Unknown)?;
+
+    Rectangle::parse_geometry(geometry).ok_or_else(|| RegionError::Invalid(geometry.clone()))
+}
+
+impl Source {
+    /// Find `--config PATH` (or `--config=PATH`) and `--no-config` in command line `args`
+    /// without program name. `--no-config` wins over `--config`, scanning stops at `--`.
+    pub fn from_args(args: impl IntoIterator<Item = OsString>) -> Self {
+        let mut source = Self::Default;
+        let mut no_config = false;
+        let mut args = args.into_iter();
+
+        while let Some(arg) = args.next() {
+            if arg == "--" {
+                break;
+            } else if arg == "--no-config" {
+                no_config = true;
+            } else if arg == "--config" {
+                if let Some(path) = args.next() {
+                    source = Self::Path(path.into());
+                }
+            } else if let Some(path) = arg.to_str().and_then(|a| a.strip_prefix("--config=")) {
+                source = Self::Path(path.into());
+            }
+        }
+
+        if no_config {
+            Self::None
+        } else {
+            source
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use clap::{CommandFactory, FromArgMatches};
+
+    use std::{ffi::OsString, path::Path};
+
+    use prtsc_wayland::points::{Point, Rectangle};
+
+    use super::{output_in_dir, resolve_region, Config, RegionError, Source};
+    use crate::Args;
+
+    fn parse_with_config(config: &str, argv: &[&str]) -> Args {
+        let config = Config::parse(config).expect("valid config");
+        let matches = config
+            .apply(Args::command())
+            .try_get_matches_from(std::iter::once("prtsc-wayland").chain(argv.iter().copied()))
+            .expect("valid arguments");
+
+        Args::from_arg_matches(&matches).expect("valid matches")
+    }
+
+    #[test]
+    fn config_overrides_defaults() {
+        let args = parse_with_config(
+            "output = \"shot.png\"\nfullscreen = true\nselection_format = \"%w\"",
+            &[],
+        );
+
+        assert_eq!(args.output, "shot.png");
+        assert!(args.fullscreen);
+        assert_eq!(args.selection_format, "%w");
+    }
+
+    #[test]
+    fn cli_overrides_config() {
+        let args = parse_with_config("output = \"shot.png\"", &["-o", "cli.png"]);
+
+        assert_eq!(args.output, "cli.png");
+    }
+
+    #[test]
+    fn cli_overrides_config_flag() {
+        let args = parse_with_config("fullscreen = true", &["--fullscreen=false"]);
+        assert!(!args.fullscreen);
+
+        let args = parse_with_config("fullscreen = true", &["-f"]);
+        assert!(args.fullscreen);
+
+        let args = parse_with_config("fullscreen = false\nconfirm = false", &["-f", "--confirm"]);
+        assert!(args.fullscreen);
+        assert!(args.confirm);
+    }
+
+    #[test]
+    fn empty_config_keeps_defaults() {
+        let args = parse_with_config("", &[]);
+
+        assert_eq!(args.output, "image.png");
+        assert!(!args.fullscreen);
+    }
+
+    #[test]
+    fn unknown_keys_are_collected() {
+        let config = Config::parse("output = \"shot.png\"\nbrightness = 64\n[extra]\nx = 1")
+            .expect("unknown keys are not an error");
+
+        assert_eq!(config.output.as_deref(), Some("shot.png"));
+        let mut keys: Vec<_> = config.unknown_keys().collect();
+        keys.sort();
+        assert_eq!(keys, ["brightness", "extra"]);
+    }
+
+    #[test]
+    fn regions_are_resolved() {
+        let config = Config::parse(
+            "output = \"shot.png\"\n[regions]\nminimap = \"1600,0 320x320\"\nbad = \"1600,0\"",
+        )
+        .expect("valid config");
+
+        assert_eq!(config.unknown_keys().count(), 0);
+        assert_eq!(
+            resolve_region(&config.regions, "minimap"),
+            Ok(Rectangle::new(Point::new(1600, 0), 320, 320))
+        );
+        assert_eq!(
+            resolve_region(&config.regions, "bad"),
+            Err(RegionError::Invalid("1600,0".into()))
+        );
+        assert_eq!(
+            resolve_region(&config.regions, "chat"),
+            Err(RegionError::Unknown)
+        );
+    }
+
+    #[test]
+    fn source_from_args() {
+        let source = |argv: &[&str]| Source::from_args(argv.iter().map(OsString::from));
+
+        assert_eq!(source(&["-f"]), Source::Default);
+        assert_eq!(
+            source(&["--config", "a.toml"]),
+            Source::Path("a.toml".into())
+        );
+        assert_eq!(
+            source(&["--config=b.toml", "-f"]),
+            Source::Path("b.toml".into())
+        );
+        assert_eq!(source(&["--config", "a.toml", "--no-config"]), Source::None);
+        assert_eq!(source(&["--", "--no-config"]), Source::Default);
+    }
+
+    #[test]
+    fn output_is_moved_to_dir() {
+        let dir = Path::new("/tmp/shots");
+
+        assert_eq!(output_in_dir("image.png", dir), "/tmp/shots/image.png");
+        assert_eq!(output_in_dir("/home/a/b.jpg", dir), "/tmp/shots/b.jpg");
+        assert_eq!(output_in_dir("-", dir), "-");
+    }
+}
@@ -0,0 +1,96 @@
+/// Convert RGB8 `data` of `width`x`height` image to RGBA8, making pixels outside of rectangle
+/// with rounded corners of `radius` transparent. Corner edges are anti-aliased.
+pub fn round_corners(data: &[u8], width: u32, height: u32, radius: u32) -> Vec<u8> {
+    // Corners can't overlap
+    let radius = radius.min(width / 2).min(height / 2) as f32;
+
+    let mut out = Vec::with_capacity(width as usize * height as usize * 4);
+    for (i, pixel) in data.chunks_exact(3).enumerate() {
+        let x = (i % width as usize) as f32 + 0.5;
+        let y = (i / width as usize) as f32 + 0.5;
+
+        // Distance from pixel center to the center of nearest corner circle, per axis. Zero if
+        // pixel is not in corner square.
+        let dx = (radius - x).max(x - (width as f32 - radius)).max(0.0);
+        let dy = (radius - y).max(y - (height as f32 - radius)).max(0.0);
+        let coverage = if dx > 0.0 && dy > 0.0 {
+            (radius - dx.hypot(dy) + 0.5).clamp(0.0, 1.0)
+        } else {
+            1.0
+        };
+
+        out.extend_from_slice(pixel);
+        out.push((coverage * 255.0).round() as u8);
+    }
+
+    out
+}
+
+/// Convert RGB8 `data` to opaque RGBA8.
+pub fn rgb_to_rgba(data: &[u8]) -> Vec<u8> {
+    data.chunks_exact(3)
+        .flat_map(|p| [p[0], p[1], p[2], 255])
+        .collect()
+}
+
+/// Convert RGBA8 `data` to RGB8, compositing translucent pixels over opaque RGB `background`.
+pub fn rgba_to_rgb(data: &[u8], background: [u8; 3]) -> Vec<u8> {
+    data.chunks_exact(4)
+        .flat_map(|p| {
+            let alpha = p[3] as u32;
+            let over =
+                |c: u8, bg: u8| ((c as u32 * alpha + bg as u32 * (255 - alpha) + 127) / 255) as u8;
+            [
+                over(p[0], background[0]),
+                over(p[1], background[1]),
+                over(p[2], background[2]),
+            ]
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{rgba_to_rgb, round_corners};
+
+    #[test]
+    fn corners_are_transparent() {
+        let (width, height) = (20, 10);
+        let data = vec![7; width * height * 3];
+
+        let out = round_corners(&data, width as u32, height as u32, 4);
+        let alpha = |x: usize, y: usize| out[(y * width + x) * 4 + 3];
+
+        assert_eq!(out.len(), width * height * 4);
+        assert_eq!(out[..3], [7, 7, 7]);
+        for (x, y) in [
+            (0, 0),
+            (width - 1, 0),
+            (0, height - 1),
+            (width - 1, height - 1),
+        ] {
+            assert_eq!(alpha(x, y), 0, "corner {x},{y}");
+        }
+        // Edges between corners and the center are opaque
+        assert_eq!(alpha(width / 2, 0), 255);
+        assert_eq!(alpha(0, height / 2), 255);
+        assert_eq!(alpha(width / 2, height / 2), 255);
+        // Pixel on the arc is partially covered
+        assert!((1..255).contains(&alpha(1, 1)));
+    }
+
+    #[test]
+    fn zero_radius_keeps_everything_opaque() {
+        let out = round_corners(&[1, 2, 3, 4, 5, 6], 2, 1, 0);
+        assert_eq!(out, [1, 2, 3, 255, 4, 5, 6, 255]);
+    }
+
+    #[test]
+    fn alpha_is_composited_over_background() {
+        let data = [10, 20, 30, 255, 10, 20, 30, 0, 0, 0, 0, 128];
+        assert_eq!(
+            rgba_to_rgb(&data, [200, 100, 50]),
+            [10, 20, 30, 200, 100, 50, 100, 50, 25]
+        );
+    }
+}
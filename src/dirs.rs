@@ -0,0 +1,22 @@
+use std::path::PathBuf;
+
+/// Resolve XDG base directory from `var`, falling back to `$HOME/<fallback>` if it is unset or
+/// not absolute. Returned path already contains `prtsc-wayland` subdirectory.
+fn xdg_dir(var: &str, fallback: &str) -> Option<PathBuf> {
+    let base = std::env::var_os(var)
+        .map(PathBuf::from)
+        .filter(|p| p.is_absolute())
+        .or_else(|| std::env::var_os("HOME").map(|home| PathBuf::from(home).join(fallback)))?;
+
+    Some(base.join("prtsc-wayland"))
+}
+
+/// `$XDG_CONFIG_HOME/prtsc-wayland`
+pub fn config_dir() -> Option<PathBuf> {
+    xdg_dir("XDG_CONFIG_HOME", ".config")
+}
+
+/// `$XDG_STATE_HOME/prtsc-wayland`
+pub fn state_dir() -> Option<PathBuf> {
+    xdg_dir("XDG_STATE_HOME", ".local/state")
+}
@@ -0,0 +1,613 @@
+use std::{fmt, io::Write, path::Path};
+
+use clap::ValueEnum;
+use image::{
+    codecs::{bmp::BmpEncoder, jpeg::JpegEncoder, qoi::QoiEncoder, webp::WebPEncoder},
+    error::{ImageFormatHint, UnsupportedError, UnsupportedErrorKind},
+    ExtendedColorType, ImageEncoder, ImageError, ImageFormat, ImageResult,
+};
+
+/// Image formats which can be selected with `--format`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum)]
+pub enum OutputFormat {
+    Png,
+    Jpeg,
+    Ppm,
+    Pam,
+    Qoi,
+    Webp,
+    Bmp,
+    /// Unencoded pixels: rows top to bottom, 3 bytes (R, G, B) per pixel (4 bytes, R, G, B, A
+    /// with --round-corners; 1 byte, L with --grayscale), no padding. With --raw-header preceded
+    /// by JSON line with width, height, channels and byte_order
+    Raw,
+}
+
+/// PNG compression presets, trade encoding speed for file size.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, ValueEnum)]
+pub enum PngCompression {
+    /// Fastest encoding, largest files
+    Fast,
+    /// Settings of `image` crate
+    #[default]
+    Default,
+    /// Smallest files, slow on large screenshots
+    Best,
+}
+
+impl PngCompression {
+    fn settings(self) -> (png::Compression, png::FilterType, png::AdaptiveFilterType) {
+        use png::{AdaptiveFilterType, Compression, FilterType};
+
+        // Default is what `image::codecs::png::PngEncoder::new` uses
+        match self {
+            Self::Fast => (
+                Compression::Fast,
+                FilterType::NoFilter,
+                AdaptiveFilterType::NonAdaptive,
+            ),
+            Self::Default => (
+                Compression::Fast,
+                FilterType::Sub,
+                AdaptiveFilterType::Adaptive,
+            ),
+            Self::Best => (
+                Compression::Best,
+                FilterType::Sub,
+                AdaptiveFilterType::Adaptive,
+            ),
+        }
+    }
+}
+
+/// Encoder settings, each of them is used only by related formats.
+#[derive(Clone, Debug)]
+pub struct EncodeOptions {
+    /// JPEG quality, 1..=100
+    pub quality: u8,
+    /// Write JSON header line before raw pixels
+    pub raw_header: bool,
+    pub png_compression: PngCompression,
+    /// `tEXt` chunks (keyword, text) of PNG
+    pub png_text: Vec<(String, String)>,
+    /// Pixel density written to `pHYs` chunk of PNG
+    pub pixels_per_metre: Option<u32>,
+}
+
+impl Default for EncodeOptions {
+    fn default() -> Self {
+        Self {
+            quality: 90,
+            raw_header: false,
+            png_compression: PngCompression::Default,
+            png_text: Vec::new(),
+            pixels_per_metre: None,
+        }
+    }
+}
+
+impl OutputFormat {
+    pub fn from_image_format(format: ImageFormat) -> Option<Self> {
+        match format {
+            ImageFormat::Png => Some(Self::Png),
+            ImageFormat::Jpeg => Some(Self::Jpeg),
+            ImageFormat::Pnm => Some(Self::Ppm),
+            ImageFormat::Qoi => Some(Self::Qoi),
+            ImageFormat::WebP => Some(Self::Webp),
+            ImageFormat::Bmp => Some(Self::Bmp),
+            _ => None,
+        }
+    }
+
+    /// Usual file extension of format.
+    pub fn extension(self) -> &'static str {
+        match self {
+            Self::Png => "png",
+            Self::Jpeg => "jpg",
+            Self::Ppm => "ppm",
+            Self::Pam => "pam",
+            Self::Qoi => "qoi",
+            Self::Webp => "webp",
+            Self::Bmp => "bmp",
+            Self::Raw => "raw",
+        }
+    }
+
+    /// Whether format can store transparency, images with alpha are flattened for others.
+    pub fn has_alpha(self) -> bool {
+        !matches!(self, Self::Jpeg | Self::Ppm | Self::Bmp)
+    }
+
+    /// Detect format from `path` extension. Unlike [`ImageFormat::from_path`], PAM is told apart
+    /// from other PNM formats. Returns [`None`] format for images which can't be selected with
+    /// `--format`, but still can be written with [`image`].
+    pub fn from_path(path: &Path) -> ImageResult<(Option<Self>, ImageFormat)> {
+        let image_format = ImageFormat::from_path(path)?;
+        let is_pam = path
+            .extension()
+            .is_some_and(|ext| ext.eq_ignore_ascii_case("pam"));
+
+        let format = if is_pam {
+            Some(Self::Pam)
+        } else {
+            Self::from_image_format(image_format)
+        };
+
+        Ok((format, image_format))
+    }
+
+    /// Encode `width`x`height` image from raw `data` of `color` type and write it to `w`. Unlike
+    /// [`image::ImageBuffer::write_to`], `w` does not need to be seekable.
+    pub fn encode(
+        self,
+        options: &EncodeOptions,
+        data: &[u8],
+        width: u32,
+        height: u32,
+        color: ExtendedColorType,
+        mut w: impl Write,
+    ) -> ImageResult<()> {
+        match self {
+            Self::Png => write_png(options, data, width, height, color, w),
+            Self::Jpeg => JpegEncoder::new_with_quality(&mut w, options.quality)
+                .write_image(data, width, height, color),
+            Self::Ppm => write_ppm(data, width, height, color, w),
+            Self::Pam => write_pam(data, width, height, color, w),
+            Self::Qoi => QoiEncoder::new(w).write_image(data, width, height, color),
+            Self::Webp => WebPEncoder::new_lossless(w).write_image(data, width, height, color),
+            Self::Bmp => BmpEncoder::new(&mut w).write_image(data, width, height, color),
+            Self::Raw => write_raw(options.raw_header, data, width, height, color, w),
+        }
+    }
+}
+
+/// Error for `color` which can't be written in `format`.
+fn unsupported_color(format: ImageFormatHint, color: ExtendedColorType) -> ImageError {
+    ImageError::Unsupported(UnsupportedError::from_format_and_kind(
+        format,
+        UnsupportedErrorKind::Color(color),
+    ))
+}
+
+/// Write PNG with `png` crate directly, since [`image`] can't add text chunks.
+fn write_png(
+    options: &EncodeOptions,
+    data: &[u8],
+    width: u32,
+    height: u32,
+    color: ExtendedColorType,
+    w: impl Write,
+) -> ImageResult<()> {
+    let color_type = match color {
+        ExtendedColorType::L8 => png::ColorType::Grayscale,
+        ExtendedColorType::Rgb8 => png::ColorType::Rgb,
+        ExtendedColorType::Rgba8 => png::ColorType::Rgba,
+        _ => {
+            return Err(unsupported_color(
+                ImageFormatHint::Exact(ImageFormat::Png),
+                color,
+            ))
+        }
+    };
+    let (compression, filter, adaptive_filter) = options.png_compression.settings();
+    let to_image_error = |e: png::EncodingError| ImageError::IoError(e.into());
+
+    let mut encoder = png::Encoder::new(w, width, height);
+    encoder.set_color(color_type);
+    encoder.set_depth(png::BitDepth::Eight);
+    encoder.set_compression(compression);
+    encoder.set_filter(filter);
+    encoder.set_adaptive_filter(adaptive_filter);
+    encoder.set_pixel_dims(options.pixels_per_metre.map(|ppm| png::PixelDimensions {
+        xppu: ppm,
+        yppu: ppm,
+        unit: png::Unit::Meter,
+    }));
+    for (keyword, text) in &options.png_text {
+        encoder
+            .add_text_chunk(keyword.clone(), text.clone())
+            .map_err(to_image_error)?;
+    }
+
+    encoder
+        .write_header()
+        .and_then(|mut w| w.write_image_data(data))
+        .map_err(to_image_error)
+}
+
+/// Write binary PPM (P6), or PGM (P5) for grayscale. Pixels are already in the right layout, so
+/// header is followed by `data` as is.
+fn write_ppm(
+    data: &[u8],
+    width: u32,
+    height: u32,
+    color: ExtendedColorType,
+    mut w: impl Write,
+) -> ImageResult<()> {
+    let magic = match color {
+        ExtendedColorType::L8 => "P5",
+        ExtendedColorType::Rgb8 => "P6",
+        _ => {
+            return Err(unsupported_color(
+                ImageFormatHint::Exact(ImageFormat::Pnm),
+                color,
+            ))
+        }
+    };
+
+    write!(w, "{magic}\n{width} {height}\n255\n")?;
+    w.write_all(data)?;
+    w.flush()?;
+
+    Ok(())
+}
+
+/// Write PAM (P7) with GRAYSCALE, RGB or RGB_ALPHA tuples, `data` is written as is after header.
+fn write_pam(
+    data: &[u8],
+    width: u32,
+    height: u32,
+    color: ExtendedColorType,
+    mut w: impl Write,
+) -> ImageResult<()> {
+    let (depth, tuple_type) = match color {
+        ExtendedColorType::L8 => (1, "GRAYSCALE"),
+        ExtendedColorType::Rgb8 => (3, "RGB"),
+        ExtendedColorType::Rgba8 => (4, "RGB_ALPHA"),
+        _ => {
+            return Err(unsupported_color(
+                ImageFormatHint::Exact(ImageFormat::Pnm),
+                color,
+            ))
+        }
+    };
+
+    write!(
+        w,
+        "P7\nWIDTH {width}\nHEIGHT {height}\nDEPTH {depth}\nMAXVAL 255\nTUPLTYPE {tuple_type}\nENDHDR\n"
+    )?;
+    w.write_all(data)?;
+    w.flush()?;
+
+    Ok(())
+}
+
+/// Write `data` as is, optionally preceded by JSON line describing it.
+fn write_raw(
+    header: bool,
+    data: &[u8],
+    width: u32,
+    height: u32,
+    color: ExtendedColorType,
+    mut w: impl Write,
+) -> ImageResult<()> {
+    let (channels, byte_order) = match color {
+        ExtendedColorType::L8 => (1, "L"),
+        ExtendedColorType::Rgb8 => (3, "RGB"),
+        ExtendedColorType::Rgba8 => (4, "RGBA"),
+        _ => {
+            let format = ImageFormatHint::Name("raw".to_owned());
+            return Err(unsupported_color(format, color));
+        }
+    };
+
+    if header {
+        writeln!(
+            w,
+            r#"{{"width":{width},"height":{height},"channels":{channels},"byte_order":"{byte_order}"}}"#
+        )?;
+    }
+    w.write_all(data)?;
+    w.flush()?;
+
+    Ok(())
+}
+
+impl fmt::Display for OutputFormat {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let value = self.to_possible_value().expect("no skipped variants");
+        f.write_str(value.get_name())
+    }
+}
+
+impl fmt::Display for PngCompression {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let value = self.to_possible_value().expect("no skipped variants");
+        f.write_str(value.get_name())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+
+    use clap::ValueEnum;
+    use image::{ExtendedColorType, GenericImageView};
+
+    use super::{EncodeOptions, OutputFormat, PngCompression};
+
+    #[test]
+    fn encoded_images_decode_back() {
+        let data: Vec<u8> = (0..2 * 3 * 3).map(|i| i * 10).collect();
+
+        // Raw pixels can't be decoded without knowing their size
+        let formats = OutputFormat::value_variants()
+            .iter()
+            .filter(|&&f| f != OutputFormat::Raw);
+
+        for format in formats {
+            let mut out = Vec::new();
+            format
+                .encode(
+                    &EncodeOptions::default(),
+                    &data,
+                    2,
+                    3,
+                    ExtendedColorType::Rgb8,
+                    &mut out,
+                )
+                .unwrap_or_else(|e| panic!("failed to encode {format}: {e}"));
+
+            let image = image::load_from_memory(&out)
+                .unwrap_or_else(|e| panic!("failed to decode {format}: {e}"));
+            assert_eq!(image.dimensions(), (2, 3), "Failed for {format}");
+        }
+    }
+
+    #[test]
+    fn lossless_formats_keep_channel_order() {
+        // Distinct values in every channel, so any swap is detected
+        let data: Vec<u8> = (0..2 * 2 * 3).map(|i| i * 20).collect();
+
+        for format in [
+            OutputFormat::Png,
+            OutputFormat::Ppm,
+            OutputFormat::Pam,
+            OutputFormat::Qoi,
+            OutputFormat::Webp,
+            OutputFormat::Bmp,
+        ] {
+            let mut out = Vec::new();
+            format
+                .encode(
+                    &EncodeOptions::default(),
+                    &data,
+                    2,
+                    2,
+                    ExtendedColorType::Rgb8,
+                    &mut out,
+                )
+                .unwrap();
+
+            let image = image::load_from_memory(&out).unwrap().into_rgb8();
+            assert_eq!(image.as_raw(), &data, "Failed for {format}");
+        }
+    }
+
+    /// Split netpbm image into header lines and pixel data, header ends with `last` line.
+    fn split_header<'a>(image: &'a [u8], last: &str) -> (Vec<&'a str>, &'a [u8]) {
+        let mut lines = Vec::new();
+        let mut rest = image;
+        loop {
+            let end = rest.iter().position(|&b| b == b'\n').expect("header line");
+            let line = std::str::from_utf8(&rest[..end]).expect("ascii header");
+            lines.push(line);
+            rest = &rest[end + 1..];
+            if line == last {
+                return (lines, rest);
+            }
+        }
+    }
+
+    #[test]
+    fn ppm_is_header_and_raw_pixels() {
+        let data: Vec<u8> = (0..4 * 2 * 3).collect();
+        let mut out = Vec::new();
+        OutputFormat::Ppm
+            .encode(
+                &EncodeOptions::default(),
+                &data,
+                4,
+                2,
+                ExtendedColorType::Rgb8,
+                &mut out,
+            )
+            .unwrap();
+
+        let (header, pixels) = split_header(&out, "255");
+        assert_eq!(header, ["P6", "4 2", "255"]);
+        assert_eq!(pixels, data);
+    }
+
+    #[test]
+    fn pam_is_header_and_raw_pixels() {
+        for (color, channels, tuple_type) in [
+            (ExtendedColorType::L8, 1, "GRAYSCALE"),
+            (ExtendedColorType::Rgb8, 3, "RGB"),
+            (ExtendedColorType::Rgba8, 4, "RGB_ALPHA"),
+        ] {
+            let data: Vec<u8> = (0..3 * 2 * channels).collect();
+            let mut out = Vec::new();
+            OutputFormat::Pam
+                .encode(&EncodeOptions::default(), &data, 3, 2, color, &mut out)
+                .unwrap();
+
+            let (header, pixels) = split_header(&out, "ENDHDR");
+            assert_eq!(
+                header,
+                [
+                    "P7",
+                    "WIDTH 3",
+                    "HEIGHT 2",
+                    &format!("DEPTH {channels}"),
+                    "MAXVAL 255",
+                    &format!("TUPLTYPE {tuple_type}"),
+                    "ENDHDR",
+                ]
+            );
+            assert_eq!(pixels.len(), 3 * 2 * channels as usize);
+            assert_eq!(pixels, data);
+        }
+    }
+
+    #[test]
+    fn pam_extension_is_detected() {
+        let (format, _) = OutputFormat::from_path(Path::new("shot.pam")).unwrap();
+        assert_eq!(format, Some(OutputFormat::Pam));
+        let (format, _) = OutputFormat::from_path(Path::new("shot.ppm")).unwrap();
+        assert_eq!(format, Some(OutputFormat::Ppm));
+    }
+
+    #[test]
+    fn extensions_are_detected_back() {
+        let formats = OutputFormat::value_variants()
+            .iter()
+            .filter(|&&f| f != OutputFormat::Raw);
+
+        for &format in formats {
+            let path = format!("shot.{}", format.extension());
+            let (detected, _) = OutputFormat::from_path(Path::new(&path)).unwrap();
+            assert_eq!(detected, Some(format));
+        }
+    }
+
+    #[test]
+    fn raw_is_pixels_with_optional_header() {
+        let data: Vec<u8> = (0..5 * 2 * 3).collect();
+
+        for raw_header in [false, true] {
+            let options = EncodeOptions {
+                raw_header,
+                ..Default::default()
+            };
+            let mut out = Vec::new();
+            OutputFormat::Raw
+                .encode(&options, &data, 5, 2, ExtendedColorType::Rgb8, &mut out)
+                .unwrap();
+
+            let pixels = if raw_header {
+                let (header, pixels) = split_header(
+                    &out,
+                    r#"{"width":5,"height":2,"channels":3,"byte_order":"RGB"}"#,
+                );
+                assert_eq!(header.len(), 1);
+                pixels
+            } else {
+                &out
+            };
+            assert_eq!(pixels.len(), 5 * 2 * 3);
+            assert_eq!(pixels, data);
+        }
+    }
+
+    #[test]
+    fn png_compression_presets_decode_back() {
+        let data: Vec<u8> = (0..8 * 8 * 3).map(|i| (i % 7) as u8).collect();
+
+        for &png_compression in PngCompression::value_variants() {
+            let options = EncodeOptions {
+                png_compression,
+                ..Default::default()
+            };
+            let mut out = Vec::new();
+            OutputFormat::Png
+                .encode(&options, &data, 8, 8, ExtendedColorType::Rgb8, &mut out)
+                .unwrap();
+
+            let image = image::load_from_memory(&out).unwrap().into_rgb8();
+            assert_eq!(image.as_raw(), &data, "Failed for {png_compression:?}");
+        }
+    }
+
+    #[test]
+    fn best_png_compression_is_smallest() {
+        // Screenshot-like image: flat areas with some noise
+        let data: Vec<u8> = (0..64 * 64 * 3u32)
+            .map(|i| {
+                if i % 97 < 5 {
+                    (i * 31 % 251) as u8
+                } else {
+                    200
+                }
+            })
+            .collect();
+        let size = |png_compression| {
+            let options = EncodeOptions {
+                png_compression,
+                ..Default::default()
+            };
+            let mut out = Vec::new();
+            OutputFormat::Png
+                .encode(&options, &data, 64, 64, ExtendedColorType::Rgb8, &mut out)
+                .unwrap();
+            out.len()
+        };
+
+        let best = size(PngCompression::Best);
+        assert!(best <= size(PngCompression::Default));
+        assert!(best < size(PngCompression::Fast));
+    }
+
+    #[test]
+    fn png_chunks_are_written() {
+        let options = EncodeOptions {
+            pixels_per_metre: Some(3780),
+            png_text: vec![
+                ("Software".to_owned(), "prtsc-wayland".to_owned()),
+                ("Geometry".to_owned(), "1,2 3x4".to_owned()),
+            ],
+            ..Default::default()
+        };
+        let mut out = Vec::new();
+        OutputFormat::Png
+            .encode(
+                &options,
+                &[0; 3 * 4 * 3],
+                3,
+                4,
+                ExtendedColorType::Rgb8,
+                &mut out,
+            )
+            .unwrap();
+
+        let reader = png::Decoder::new(out.as_slice()).read_info().unwrap();
+        let chunks: Vec<_> = reader
+            .info()
+            .uncompressed_latin1_text
+            .iter()
+            .map(|c| (c.keyword.as_str(), c.text.as_str()))
+            .collect();
+        assert_eq!(
+            chunks,
+            [("Software", "prtsc-wayland"), ("Geometry", "1,2 3x4")]
+        );
+
+        let dims = reader.info().pixel_dims.expect("pHYs chunk");
+        assert_eq!((dims.xppu, dims.yppu), (3780, 3780));
+        assert_eq!(dims.unit, png::Unit::Meter);
+    }
+
+    #[test]
+    fn grayscale_images_decode_back() {
+        let data: Vec<u8> = (0..3 * 2).map(|i| i * 40).collect();
+
+        // WebP has no grayscale mode, check formats which store single channel as is
+        for format in [OutputFormat::Png, OutputFormat::Ppm, OutputFormat::Pam] {
+            let mut out = Vec::new();
+            format
+                .encode(
+                    &EncodeOptions::default(),
+                    &data,
+                    3,
+                    2,
+                    ExtendedColorType::L8,
+                    &mut out,
+                )
+                .unwrap_or_else(|e| panic!("failed to encode {format}: {e}"));
+
+            let image = image::load_from_memory(&out).unwrap();
+            assert_eq!(image.color(), image::ColorType::L8, "Failed for {format}");
+            assert_eq!(image.as_bytes(), &data, "Failed for {format}");
+        }
+    }
+}
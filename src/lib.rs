@@ -0,0 +1,736 @@
+//! Wayland screenshot utility that freezes the screen before region selection.
+//!
+//! Library entry point is [`capture`], binary is a thin CLI wrapper around it.
+
+use std::{
+    os::fd::BorrowedFd,
+    time::{Duration, Instant},
+};
+
+use app::{
+    base::BaseApp, screenshot::ScreenshotApp, selection::SelectionOptions, AppState, KeyboardMode,
+    WaylandAppManager,
+};
+use points::{Point, Rectangle};
+use wayland_client::Connection;
+use windows::Window;
+
+pub mod app;
+pub mod points;
+pub mod rect_fmt;
+pub mod windows;
+
+pub use app::Error;
+
+/// What part of the output should be captured.
+#[derive(Clone, Debug, Default)]
+pub enum CaptureMode {
+    /// Freeze the output and let user select region
+    #[default]
+    Interactive,
+    /// Capture the whole output without drawing anything on it
+    Fullscreen,
+    /// Capture fixed region (in logical output coordinates) without drawing anything on output
+    Region(Rectangle),
+    /// Same as [`CaptureMode::Region`], but region is in global compositor coordinates and is
+    /// translated with logical position of output
+    GlobalRegion(Rectangle),
+    /// Capture focused window (asked from compositor, see [`windows`]) on its output without
+    /// drawing anything. Part of window outside of output is not captured
+    ActiveWindow,
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct CaptureOptions {
+    pub mode: CaptureMode,
+    /// Name of output to capture (like `DP-1`), first output is captured if not set
+    pub output_name: Option<String>,
+    /// If [`CaptureOptions::output_name`] is not set and there are several outputs, let user
+    /// pick output by clicking it
+    pub pick_output: bool,
+    /// Name of seat which input is used for interactive selection, first seat is used if not set
+    pub seat: Option<String>,
+    /// Keyboard focus of interactive selection overlay
+    pub keyboard: KeyboardMode,
+    /// Abort interactive selection with [`Error::Timeout`] if it takes longer than this
+    pub timeout: Option<Duration>,
+    /// Settings of interactive region selector
+    pub selection: SelectionOptions,
+    /// Fill [`SelectionOptions::windows`] with windows on captured output, see [`windows`]
+    pub windows: bool,
+    /// Convert captured region to 8-bit luma instead of RGB
+    pub grayscale: bool,
+    /// Grow captured region by this many pixels on every side (shrink if it is negative), it is
+    /// clamped to the output
+    pub padding: i32,
+    /// If there are no outputs yet, wait for this long for one to appear instead of failing
+    /// with [`Error::NoOutput`] immediately
+    pub wait_for_output: Option<Duration>,
+}
+
+/// Captured region of output.
+pub struct Capture {
+    /// RGB8 pixels of captured region, `width * height * 3` bytes (or L8 pixels,
+    /// `width * height` bytes, if [`CaptureOptions::grayscale`] is set)
+    pub data: Vec<u8>,
+    /// Width of `data` in pixels. It is width of `rect`, unless compositor copied just the region
+    /// in physical pixels of output with scale factor above 1
+    pub width: u32,
+    /// Height of `data` in pixels, see `width`
+    pub height: u32,
+    /// Captured region in logical output coordinates
+    pub rect: Rectangle,
+    /// Region which was selected, `rect` is it grown by [`CaptureOptions::padding`]
+    pub selected: Rectangle,
+    /// Name of captured output, if compositor provides it
+    pub output_name: Option<String>,
+    /// Pixel density of captured output in pixels per metre, if it is known
+    pub pixels_per_metre: Option<u32>,
+    /// Scale factor of captured output
+    pub scale_factor: i32,
+}
+
+/// Output and region which [`capture`] would use, see [`dry_run`].
+#[derive(Clone, Debug)]
+pub struct DryRun {
+    /// Name of output, if compositor provides it
+    pub output_name: Option<String>,
+    /// Logical width of output
+    pub width: u32,
+    /// Logical height of output
+    pub height: u32,
+    /// Scale factor of output
+    pub scale_factor: i32,
+    /// Region in logical output coordinates, [`None`] if it is selected interactively
+    pub rect: Option<Rectangle>,
+}
+
+/// Screenshot of the whole output in Xrgb8888 format with selected regions.
+struct Screenshot {
+    image: Box<[u8]>,
+    /// Several regions are selected only with [`SelectionOptions::multi`]
+    rects: Vec<Rectangle>,
+    width: u32,
+    /// Bytes per row of image, it may be more than `width * 4`
+    stride: usize,
+    /// Compositor copied only this region (the single one of `rects` grown by padding), so image
+    /// is just this region
+    region: Option<Rectangle>,
+    /// Width and height of image in pixels of buffer
+    size: (u32, u32),
+    output_name: Option<String>,
+    pixels_per_metre: Option<u32>,
+    scale_factor: i32,
+}
+
+/// Capture output as described by `options`. If user cancels interactive selection,
+/// [`Error::Canceled`] is returned. Only the first region selected with
+/// [`SelectionOptions::multi`] is captured, see [`capture_all`].
+pub fn capture(options: &CaptureOptions) -> Result<Capture, Error> {
+    let mut captures = capture_all(options)?;
+
+    Ok(captures.swap_remove(0))
+}
+
+/// Same as [`capture`], but every region selected with [`SelectionOptions::multi`] is cropped
+/// from the same frame, oldest first. There is at least one capture.
+pub fn capture_all(options: &CaptureOptions) -> Result<Vec<Capture>, Error> {
+    Session::connect(options)?.capture_all(options)
+}
+
+/// Show interactive selection (regardless of [`CaptureOptions::mode`]) and call `on_capture`
+/// with every completed selection, selector is shown again after it until user cancels it. All
+/// selections are cropped from the same frame, unless `fresh` is set and frame is captured
+/// again before every next selection.
+pub fn capture_loop(
+    options: &CaptureOptions,
+    fresh: bool,
+    mut on_capture: impl FnMut(Capture),
+) -> Result<(), Error> {
+    let options = CaptureOptions {
+        mode: CaptureMode::Interactive,
+        ..options.clone()
+    };
+    let repeat = Repeat {
+        fresh,
+        on_capture: &mut on_capture,
+    };
+
+    let mut session = Session::connect(&options)?;
+    match make_screenshot(&mut session.mgr, &options, Some(repeat)) {
+        // Canceling selection is the only way to stop
+        Ok(_) | Err(Error::Canceled) => Ok(()),
+        Err(e) => Err(e),
+    }
+}
+
+/// Connection to compositor which can be used for several captures, so connecting and
+/// enumerating outputs is done once.
+pub struct Session {
+    mgr: WaylandAppManager,
+}
+
+impl Session {
+    /// Connect to compositor, waiting for output if [`CaptureOptions::wait_for_output`] is set.
+    pub fn connect(options: &CaptureOptions) -> Result<Self, Error> {
+        if let CaptureMode::ActiveWindow = options.mode {
+            // Missing window source fails before connecting
+            windows::detect().ok_or(Error::NoWindowSource)?;
+        }
+
+        let conn = Connection::connect_to_env().map_err(Error::Connect)?;
+        // Initialize outputs
+        let mut mgr = WaylandAppManager::initialize(&conn)?;
+        if let Some(wait) = options.wait_for_output {
+            mgr.wait_for_output(Instant::now() + wait)?;
+        }
+
+        Ok(Self { mgr })
+    }
+
+    /// Same as [`capture_all`], but on this connection. Output is chosen every time, so outputs
+    /// connected after [`Session::connect`] can be captured too.
+    pub fn capture_all(&mut self, options: &CaptureOptions) -> Result<Vec<Capture>, Error> {
+        let screenshot = make_screenshot(&mut self.mgr, options, None);
+        self.mgr.reset()?;
+        let Screenshot {
+            image,
+            rects,
+            width,
+            stride,
+            region,
+            size,
+            output_name,
+            pixels_per_metre,
+            scale_factor,
+        } = screenshot?;
+
+        rects
+            .into_iter()
+            .map(|selected| {
+                let (data, rect) = match &region {
+                    Some(region) => (
+                        convert_region(options, &image, stride, size),
+                        region.clone(),
+                    ),
+                    None => crop(options, &image, stride, width, selected.clone())?,
+                };
+                let (width, height) = match region {
+                    Some(_) => size,
+                    None => (rect.width, rect.height),
+                };
+
+                Ok(Capture {
+                    data,
+                    width,
+                    height,
+                    rect,
+                    selected,
+                    output_name: output_name.clone(),
+                    pixels_per_metre,
+                    scale_factor,
+                })
+            })
+            .collect()
+    }
+
+    /// Wait until one of `fds` is readable (or hung up) and return its index. Events of
+    /// compositor, like outputs being connected, are handled meanwhile.
+    pub fn wait_readable(&mut self, fds: &[BorrowedFd<'_>]) -> Result<usize, Error> {
+        self.mgr.dispatch_until_readable(fds)
+    }
+}
+
+/// Completed selections are passed to [`Repeat::on_capture`] instead of finishing selection,
+/// see [`capture_loop`].
+struct Repeat<'a> {
+    fresh: bool,
+    on_capture: &'a mut dyn FnMut(Capture),
+}
+
+/// Crop `rect`, grown by [`CaptureOptions::padding`], from Xrgb8888 `image` of output `width`
+/// pixels wide. Returns pixels and region which is actually cropped.
+fn crop(
+    options: &CaptureOptions,
+    image: &[u8],
+    stride: usize,
+    width: u32,
+    mut rect: Rectangle,
+) -> Result<(Vec<u8>, Rectangle), Error> {
+    if options.padding != 0 {
+        let height = (image.len() / stride) as u32;
+        rect = pad_region(rect, options.padding, width, height)?;
+    }
+
+    Ok((convert(options, image, stride, &rect), rect))
+}
+
+/// Copy `rect` region from Xrgb8888 `image` to RGB8 or L8 buffer, as [`CaptureOptions`] asks.
+fn convert(options: &CaptureOptions, image: &[u8], stride: usize, rect: &Rectangle) -> Vec<u8> {
+    if options.grayscale {
+        crop_xrgb_to_luma(image, stride, rect)
+    } else {
+        crop_xrgb_to_rgb(image, stride, rect)
+    }
+}
+
+/// Copy the whole `image` of region which compositor copied, `size` is of its buffer. On outputs
+/// with scale factor above 1 it is larger than the region in logical coordinates.
+fn convert_region(
+    options: &CaptureOptions,
+    image: &[u8],
+    stride: usize,
+    (width, height): (u32, u32),
+) -> Vec<u8> {
+    convert(
+        options,
+        image,
+        stride,
+        &Rectangle::new(Point::new(0, 0), width, height),
+    )
+}
+
+/// Find output and region which [`capture`] would use with `options`, without capturing
+/// anything. Interactive selection is not shown, so its region is not known.
+pub fn dry_run(options: &CaptureOptions) -> Result<DryRun, Error> {
+    let Session { mut mgr } = Session::connect(options)?;
+    let active_window = choose_output(&mut mgr, options)?;
+
+    let ctx = mgr.app.ctx.base();
+    let output = ctx.output.as_ref().ok_or(Error::NoOutput)?;
+    let info = ctx.output_state.info(output).ok_or(Error::NoOutputInfo)?;
+    let (width, height) = info.logical_size.ok_or(Error::NoOutputLogicalSize)?;
+    let (width, height) = (width as u32, height as u32);
+
+    let rect = match &options.mode {
+        CaptureMode::Interactive => None,
+        mode => {
+            let rect = fixed_region(
+                mode,
+                active_window.map(|(window, _)| window),
+                info.logical_position.unwrap_or((0, 0)),
+                width,
+                height,
+            )?;
+            Some(match options.padding {
+                0 => rect,
+                padding => pad_region(rect, padding, width, height)?,
+            })
+        }
+    };
+
+    Ok(DryRun {
+        output_name: info.name,
+        width,
+        height,
+        scale_factor: info.scale_factor,
+        rect,
+    })
+}
+
+/// Select output of `options`. Focused window is returned too if it is captured.
+fn choose_output(
+    mgr: &mut WaylandAppManager,
+    options: &CaptureOptions,
+) -> Result<Option<(Window, String)>, Error> {
+    // Output of focused window is captured unless other one is requested
+    let active_window = match options.mode {
+        CaptureMode::ActiveWindow => Some(active_window()?),
+        _ => None,
+    };
+
+    if let Some(name) = options
+        .output_name
+        .as_ref()
+        .or(active_window.as_ref().map(|(_, output)| output))
+    {
+        mgr.select_output(name)?;
+    } else if options.pick_output {
+        mgr.pick_output(options.keyboard)?;
+    }
+
+    Ok(active_window)
+}
+
+/// Capture output of `options` with `mgr` in base state, it is left in whatever state capture
+/// ends in, see [`WaylandAppManager::reset`].
+fn make_screenshot(
+    mgr: &mut WaylandAppManager,
+    options: &CaptureOptions,
+    mut repeat: Option<Repeat<'_>>,
+) -> Result<Screenshot, Error> {
+    let active_window = choose_output(mgr, options)?;
+
+    let (output_name, logical_position, width, height, physical_width, scale_factor) = {
+        let ctx = mgr.app.ctx.base();
+        let output = ctx.output.as_ref().ok_or(Error::NoOutput)?;
+        let info = ctx.output_state.info(output).ok_or(Error::NoOutputInfo)?;
+        let (width, height) = info.logical_size.ok_or(Error::NoOutputLogicalSize)?;
+        (
+            info.name,
+            info.logical_position.unwrap_or((0, 0)),
+            width as u32,
+            height as u32,
+            info.physical_size.0,
+            info.scale_factor,
+        )
+    };
+    let pixels_per_metre = pixels_per_metre(width, physical_width, scale_factor);
+
+    // Fixed region is known before capture, so only it (grown by padding) needs to be copied
+    let rect = match &options.mode {
+        CaptureMode::Interactive => None,
+        mode => Some(fixed_region(
+            mode,
+            active_window.map(|(window, _)| window),
+            logical_position,
+            width,
+            height,
+        )?),
+    };
+    let region = match (&rect, options.padding) {
+        (Some(rect), 0) => Some(rect.clone()),
+        (Some(rect), padding) => Some(pad_region(rect.clone(), padding, width, height)?),
+        (None, _) => None,
+    };
+
+    // Make screenshot
+    mgr.initialize_partial(region)?;
+    mgr.next_app()?;
+    mgr.dispatch_until_done()?;
+    if let AppState::ScreenshotApp(ScreenshotApp { failed: true, .. }) = mgr.app.state {
+        return Err(Error::CaptureFailed);
+    }
+
+    let rect = match rect {
+        None => {
+            // Windows are fetched before overlay is mapped, it may be listed as window too
+            let mut selection = options.selection.clone();
+            if options.windows {
+                selection.windows = windows_on_output(logical_position, width, height);
+            }
+
+            // Make selection
+            mgr.initialize_full(options.seat.as_deref(), options.keyboard)?;
+            mgr.next_app()?;
+            if let AppState::SelectionApp(app) = &mut mgr.app.state {
+                app.options = selection;
+            }
+            mgr.await_overlay(options.keyboard)?;
+            loop {
+                match options.timeout {
+                    Some(timeout) => mgr.dispatch_until_done_or(Instant::now() + timeout)?,
+                    None => mgr.dispatch_until_done()?,
+                }
+
+                let Some(repeat) = &mut repeat else {
+                    break;
+                };
+                let AppState::SelectionApp(app) = &mgr.app.state else {
+                    unreachable!("next app after screenshot should be selection");
+                };
+                let Some(rect) = app.selected_region() else {
+                    break;
+                };
+                // Selection drops padding of rows
+                let (data, padded) =
+                    crop(options, &app.image, width as usize * 4, width, rect.clone())?;
+                (repeat.on_capture)(Capture {
+                    data,
+                    width: padded.width,
+                    height: padded.height,
+                    rect: padded,
+                    selected: rect,
+                    output_name: output_name.clone(),
+                    pixels_per_metre,
+                    scale_factor,
+                });
+                mgr.restart_selection(repeat.fresh);
+            }
+            // Screen returns to normal before image is cropped and encoded
+            mgr.destroy_overlay()?;
+
+            let (rects, image) =
+                match std::mem::replace(&mut mgr.app.state, AppState::BaseApp(BaseApp)) {
+                    AppState::SelectionApp(app) => (app.selected_regions(), app.image),
+                    _ => unreachable!("next app after screenshot should be selection"),
+                };
+            if rects.is_empty() {
+                return Err(Error::Canceled);
+            }
+
+            return Ok(Screenshot {
+                image,
+                rects,
+                width,
+                // Selection drops padding of rows
+                stride: width as usize * 4,
+                region: None,
+                size: (width, height),
+                output_name,
+                pixels_per_metre,
+                scale_factor,
+            });
+        }
+        Some(rect) => rect,
+    };
+
+    let AppState::ScreenshotApp(ScreenshotApp {
+        image: Some(image),
+        stride,
+        region,
+        size,
+        ..
+    }) = std::mem::replace(&mut mgr.app.state, AppState::BaseApp(BaseApp))
+    else {
+        unreachable!("next app after base should be screenshot, image should be present")
+    };
+
+    Ok(Screenshot {
+        image,
+        rects: vec![rect],
+        region,
+        size,
+        width,
+        stride,
+        output_name,
+        pixels_per_metre,
+        scale_factor,
+    })
+}
+
+/// Focused window and name of its output. Returns [`Error::NoWindowSource`] if windows of
+/// compositor are not known and [`Error::NoActiveWindow`] if source fails or nothing is focused.
+fn active_window() -> Result<(Window, String), Error> {
+    let source = windows::detect().ok_or(Error::NoWindowSource)?;
+
+    match source.active_window() {
+        Ok(Some((window, output))) => {
+            log::debug!(
+                "active window from {}: {window:?} on {output}",
+                source.name()
+            );
+            Ok((window, output))
+        }
+        Ok(None) => {
+            log::debug!("{} reports no focused window", source.name());
+            Err(Error::NoActiveWindow)
+        }
+        Err(e) => {
+            log::warn!("failed to get active window from {}: {e}", source.name());
+            Err(Error::NoActiveWindow)
+        }
+    }
+}
+
+/// Windows on `width`x`height` output at global `position`, bottom to top. Without window source
+/// or if it fails, warning is logged and there are no windows to select.
+fn windows_on_output(position: (i32, i32), width: u32, height: u32) -> Vec<Rectangle> {
+    let Some(source) = windows::detect() else {
+        log::warn!("windows of this compositor are not known, they can not be selected");
+        return Vec::new();
+    };
+
+    match source.windows() {
+        Ok(windows) => {
+            log::debug!("got {} windows from {}", windows.len(), source.name());
+            windows
+                .iter()
+                .filter_map(|w| w.on_output(position, width, height))
+                .collect()
+        }
+        Err(e) => {
+            log::warn!("failed to get windows from {}: {e}", source.name());
+            Vec::new()
+        }
+    }
+}
+
+/// Density of captured image of output `width` logical pixels wide, image has a pixel per logical
+/// pixel (see [`Capture::data`]). Physical size of output is preferred, as it is not reported by
+/// every output (and projectors), density is guessed then: scale factor is chosen so logical
+/// pixel is about 1/96 inch, but unscaled output gives no such hint.
+fn pixels_per_metre(width: u32, physical_width_mm: i32, scale_factor: i32) -> Option<u32> {
+    const INCH: f64 = 0.0254;
+
+    if physical_width_mm > 0 {
+        Some((width as f64 * 1000.0 / physical_width_mm as f64).round() as u32)
+    } else if scale_factor > 1 {
+        Some((96.0 / INCH).round() as u32)
+    } else {
+        None
+    }
+}
+
+/// Region of non-interactive `mode` on `width`x`height` output at global `logical_position`.
+/// `active_window` must be found for [`CaptureMode::ActiveWindow`].
+fn fixed_region(
+    mode: &CaptureMode,
+    active_window: Option<Window>,
+    logical_position: (i32, i32),
+    width: u32,
+    height: u32,
+) -> Result<Rectangle, Error> {
+    match mode {
+        CaptureMode::Fullscreen => Ok(Rectangle::new(Point::new(0, 0), width, height)),
+        CaptureMode::Region(rect) => validate_region(rect.clone(), width, height),
+        CaptureMode::GlobalRegion(rect) => {
+            let (x, y) = logical_position;
+            let start = match (
+                u32::try_from(rect.start.x as i64 - x as i64),
+                u32::try_from(rect.start.y as i64 - y as i64),
+            ) {
+                (Ok(x), Ok(y)) => Point::new(x, y),
+                _ => return Err(Error::InvalidRegion),
+            };
+
+            validate_region(
+                Rectangle::new(start, rect.width, rect.height),
+                width,
+                height,
+            )
+        }
+        CaptureMode::ActiveWindow => active_window
+            .expect("active window is found for its mode")
+            .on_output(logical_position, width, height)
+            .ok_or(Error::InvalidRegion),
+        CaptureMode::Interactive => unreachable!("interactive region is selected by user"),
+    }
+}
+
+/// Grow `rect` by `padding` pixels on every side and clamp it to `width`x`height` output.
+fn pad_region(rect: Rectangle, padding: i32, width: u32, height: u32) -> Result<Rectangle, Error> {
+    rect.grow(padding)
+        .and_then(|r| r.clamp_to(&Rectangle::new(Point::new(0, 0), width, height)))
+        .ok_or(Error::InvalidRegion)
+}
+
+/// Check that `rect` fits into `width`x`height` output.
+fn validate_region(rect: Rectangle, width: u32, height: u32) -> Result<Rectangle, Error> {
+    match rect.clamp_to(&Rectangle::new(Point::new(0, 0), width, height)) {
+        Some(clamped) if clamped == rect => Ok(rect),
+        _ => Err(Error::InvalidRegion),
+    }
+}
+
+/// Iterate over Xrgb8888 pixels of `rect` region of `image` with rows of `stride` bytes.
+fn crop_xrgb<'a>(
+    image: &'a [u8],
+    stride: usize,
+    rect: &Rectangle,
+) -> impl Iterator<Item = &'a [u8]> {
+    let (x, w) = (rect.start.x as usize * 4, rect.width as usize * 4);
+
+    image
+        .chunks_exact(stride)
+        .skip(rect.start.y as usize)
+        .take(rect.height as usize)
+        .flat_map(move |row| row[x..x + w].chunks_exact(4))
+}
+
+/// Copy `rect` region from Xrgb8888 `image` with rows of `stride` bytes to RGB8 buffer.
+fn crop_xrgb_to_rgb(image: &[u8], stride: usize, rect: &Rectangle) -> Vec<u8> {
+    let mut data = Vec::with_capacity(rect.width as usize * rect.height as usize * 3);
+
+    for chunk in crop_xrgb(image, stride, rect) {
+        data.push(chunk[2]);
+        data.push(chunk[1]);
+        data.push(chunk[0]);
+    }
+
+    data
+}
+
+/// Copy `rect` region from Xrgb8888 `image` with rows of `stride` bytes to L8 buffer, using
+/// Rec. 601 luma weights.
+fn crop_xrgb_to_luma(image: &[u8], stride: usize, rect: &Rectangle) -> Vec<u8> {
+    let mut data = Vec::with_capacity(rect.width as usize * rect.height as usize);
+
+    for chunk in crop_xrgb(image, stride, rect) {
+        let (r, g, b) = (chunk[2] as u32, chunk[1] as u32, chunk[0] as u32);
+        data.push(((299 * r + 587 * g + 114 * b + 500) / 1000) as u8);
+    }
+
+    data
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{convert_region, crop_xrgb_to_luma, crop_xrgb_to_rgb, pixels_per_metre};
+    use crate::points::{Point, Rectangle};
+
+    #[test]
+    fn crop_swaps_channels() {
+        // 3x2 image, pixel `i` is [b, g, r, x] = [10i, 10i + 1, 10i + 2, 255]
+        let image: Vec<u8> = (0..6u8)
+            .flat_map(|i| [i * 10, i * 10 + 1, i * 10 + 2, 255])
+            .collect();
+
+        let data = crop_xrgb_to_rgb(&image, 12, &Rectangle::new(Point::new(1, 0), 2, 2));
+
+        assert_eq!(data, [12, 11, 10, 22, 21, 20, 42, 41, 40, 52, 51, 50]);
+    }
+
+    #[test]
+    fn region_of_scaled_output() {
+        // 2x1 region on output with scale 2 is copied to 4x2 buffer, pixel `i` is [i, i, i, 255]
+        let image: Vec<u8> = (0..8u8).flat_map(|i| [i, i, i, 255]).collect();
+
+        let data = convert_region(&Default::default(), &image, 16, (4, 2));
+
+        assert_eq!(data, (0..8u8).flat_map(|i| [i, i, i]).collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn crop_single_pixel() {
+        // 2x2 image, pixel `i` is [b, g, r, x] = [i, i, i, 255]
+        let image: Vec<u8> = (0..4u8).flat_map(|i| [i, i, i, 255]).collect();
+
+        let data = crop_xrgb_to_rgb(&image, 8, &Rectangle::new(Point::new(1, 1), 1, 1));
+
+        assert_eq!(data, [3, 3, 3]);
+    }
+
+    #[test]
+    fn crop_padded_stride() {
+        // 2x2 image with 8 bytes of padding after every row, pixel `i` is [i, i, i, 255]
+        let image: Vec<u8> = [0u8, 1, 2, 3]
+            .chunks(2)
+            .flat_map(|row| {
+                row.iter()
+                    .flat_map(|&i| [i, i, i, 255])
+                    .chain([0xAA; 8])
+                    .collect::<Vec<_>>()
+            })
+            .collect();
+
+        let data = crop_xrgb_to_rgb(&image, 16, &Rectangle::new(Point::new(1, 0), 1, 2));
+
+        assert_eq!(data, [1, 1, 1, 3, 3, 3]);
+    }
+
+    #[test]
+    fn crop_to_luma() {
+        // 2x2 image of white, red, green and blue pixels
+        let image = [
+            255, 255, 255, 0, 0, 0, 255, 0, //
+            0, 255, 0, 0, 255, 0, 0, 0,
+        ];
+
+        let data = crop_xrgb_to_luma(&image, 8, &Rectangle::new(Point::new(0, 0), 2, 2));
+
+        assert_eq!(data, [255, 76, 150, 29]);
+    }
+
+    #[test]
+    fn density() {
+        // 24" 1920x1080 monitor
+        assert_eq!(pixels_per_metre(1920, 531, 1), Some(3616));
+        // 15.6" 2560x1440 laptop panel at scale 2 is 1280 logical pixels wide, so is the image
+        assert_eq!(pixels_per_metre(1280, 344, 2), Some(3721));
+        // Without physical size density is the same whatever the scale is
+        assert_eq!(pixels_per_metre(1280, 0, 2), Some(3780));
+        assert_eq!(pixels_per_metre(960, 0, 3), Some(3780));
+        assert_eq!(pixels_per_metre(1920, 0, 1), None);
+    }
+}
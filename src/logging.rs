@@ -0,0 +1,60 @@
+use std::sync::atomic::{AtomicU8, Ordering};
+
+static LEVEL: AtomicU8 = AtomicU8::new(Level::Normal as u8);
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Level {
+    /// Only errors are printed
+    Quiet,
+    /// Errors and status messages (like "saved to ...")
+    Normal,
+}
+
+pub fn set_level(level: Level) {
+    LEVEL.store(level as u8, Ordering::Relaxed);
+}
+
+pub fn enabled(level: Level) -> bool {
+    LEVEL.load(Ordering::Relaxed) >= level as u8
+}
+
+/// Set up diagnostic logs (timings, chosen parameters, wayland events) printed to stderr. They are
+/// disabled by default, every `--verbose` raises the level, `RUST_LOG` overrides it.
+pub fn init_diagnostics(verbosity: u8) {
+    let level = match verbosity {
+        0 => log::LevelFilter::Off,
+        1 => log::LevelFilter::Debug,
+        _ => log::LevelFilter::Trace,
+    };
+
+    env_logger::Builder::new()
+        .filter_level(level)
+        .parse_default_env()
+        .init();
+}
+
+/// Print error message to stderr. Errors are printed regardless of level.
+macro_rules! error {
+    ($($arg:tt)*) => {
+        eprintln!($($arg)*)
+    };
+}
+
+/// Print status message to stderr unless `--quiet` is passed. Stdout is reserved for image data
+/// and selection output.
+macro_rules! info {
+    ($($arg:tt)*) => {
+        if $crate::logging::enabled($crate::logging::Level::Normal) {
+            eprintln!($($arg)*)
+        }
+    };
+}
+
+/// Print warning to stderr unless `--quiet` is passed.
+macro_rules! warn {
+    ($($arg:tt)*) => {
+        if $crate::logging::enabled($crate::logging::Level::Normal) {
+            eprintln!("warning: {}", format_args!($($arg)*))
+        }
+    };
+}
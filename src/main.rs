@@ -1,230 +1,1411 @@
-use app::{screenshot::ScreenshotApp, AppState, WaylandAppManager};
-use clap::Parser;
-use image::{codecs::png::PngEncoder, ImageBuffer, ImageError, Rgb};
-use iter_tools::Itertools;
-use points::{Point, Rectangle};
-use rect_fmt::RectFmt;
-use wayland_client::Connection;
-
-mod app;
-mod points;
-mod rect_fmt;
+use std::{
+    fs,
+    io::{self, Cursor, IsTerminal, Read, Seek, Write},
+    os::{fd::AsFd, unix::net::UnixStream},
+    path::{Path, PathBuf},
+    time::{Duration, Instant, SystemTime},
+};
+
+use clap::{builder::styling::Style, parser::ValueSource, CommandFactory, FromArgMatches};
+use config::Config;
+use format::{EncodeOptions, OutputFormat, PngCompression};
+use image::{ExtendedColorType, ImageError};
+use prtsc_wayland::{
+    app::{
+        self,
+        selection::{Border, CursorShape, SelectionOptions},
+        KeyboardMode,
+    },
+    points::{self, Rectangle},
+    rect_fmt::{RectFmt, SPECIFIERS},
+    Capture, CaptureMode, CaptureOptions, Session,
+};
+
+#[macro_use]
+mod logging;
+
+mod config;
+mod corners;
+mod dirs;
+mod format;
+mod man;
+mod metadata;
+mod redact;
+mod save;
+mod scale;
+mod shadow;
+mod state;
+mod stats;
 
 /// Wayland screenshot utility
-#[derive(Parser)]
-#[command(about, version, after_help = include_str!("../formatting.txt"))]
+#[derive(clap::Parser)]
+#[command(about, version)]
 struct Args {
-    /// File to save screenshot (use '-' to output to stdout)
+    /// File to save screenshot (use '-' to output to stdout). If it is not passed here, file is
+    /// put into $PRTSC_OUTPUT_DIR directory when it is set
     #[arg(long, short, default_value = "image.png")]
     output: String,
 
+    /// Image format, by default detected from output file extension (png for stdout)
+    #[arg(long, env = "PRTSC_FORMAT")]
+    format: Option<OutputFormat>,
+
+    /// Cancel selection if it is not completed in SECONDS (exits with code 1)
+    #[arg(long, value_name = "SECONDS")]
+    timeout: Option<u64>,
+
+    /// JPEG quality (1-100)
+    #[arg(
+        long,
+        default_value_t = EncodeOptions::default().quality,
+        value_parser = clap::value_parser!(u8).range(1..=100),
+    )]
+    quality: u8,
+
+    /// PNG compression preset
+    #[arg(long, value_name = "LEVEL", default_value_t = PngCompression::Default)]
+    png_compression: PngCompression,
+
+    /// If --format raw, write JSON line with width, height, channels and byte order before pixels
+    #[arg(long)]
+    raw_header: bool,
+
+    /// Save image in 8-bit grayscale
+    #[arg(long, conflicts_with_all = ["round_corners", "shadow", "analyze"])]
+    grayscale: bool,
+
+    /// Save negative of image, it is inverted after --grayscale
+    #[arg(long)]
+    invert: bool,
+
+    /// Pixelate "X,Y WxH" region (relative to captured region) before saving, can be repeated
+    #[arg(long, value_name = "GEOMETRY", value_parser = parse_geometry)]
+    pixelate: Vec<Rectangle>,
+
+    /// If --pixelate, side of averaged square blocks in pixels
+    #[arg(
+        long,
+        value_name = "PIXELS",
+        default_value_t = 16,
+        value_parser = clap::value_parser!(u32).range(1..),
+    )]
+    pixelate_block: u32,
+
+    /// Make corners of the image transparent outside of RADIUS (formats without alpha, like
+    /// JPEG, get them filled with --background or white)
+    #[arg(long, value_name = "RADIUS")]
+    round_corners: Option<u32>,
+
+    /// Put image on transparent background with drop shadow behind it (formats without alpha,
+    /// like JPEG, get it filled with --background or white)
+    #[arg(long)]
+    shadow: bool,
+
+    /// If --shadow, blur radius of shadow in pixels
+    #[arg(long, value_name = "PIXELS", default_value_t = 16)]
+    shadow_blur: u32,
+
+    /// If --shadow, "X,Y" shift of shadow relative to image
+    #[arg(long, value_name = "X,Y", default_value = "0,8", value_parser = parse_offset)]
+    shadow_offset: (i32, i32),
+
+    /// If --shadow, color of shadow as #RRGGBB or #RRGGBBAA
+    #[arg(long, value_name = "COLOR", default_value = "#00000080", value_parser = parse_color)]
+    shadow_color: [u8; 4],
+
+    /// Color as #RRGGBB which transparent parts of the image (of --round-corners and --shadow) are
+    /// flattened onto for formats without alpha [default: #ffffff]. PNG keeps alpha unless this is
+    /// given. Alpha of COLOR is ignored
+    #[arg(long, value_name = "COLOR", value_parser = parse_color)]
+    background: Option<[u8; 4]>,
+
+    /// Grow captured region by PIXELS on every side, negative values shrink it
+    #[arg(
+        long,
+        value_name = "PIXELS",
+        allow_negative_numbers = true,
+        default_value_t = 0
+    )]
+    crop_padding: i32,
+
+    /// Resize saved image by FACTOR (e.g. 0.5 for half resolution)
+    #[arg(long, value_name = "FACTOR", value_parser = parse_scale, conflicts_with = "pick_color")]
+    scale: Option<f64>,
+
+    /// Resampling filter of --scale
+    #[arg(long, default_value = "lanczos3")]
+    filter: scale::Filter,
+
+    /// Pixel density written to PNG, by default computed from output size and scale
+    #[arg(long, value_name = "N")]
+    dpi: Option<u32>,
+
+    /// Do not embed capture time, output name and geometry into PNG
+    #[arg(long)]
+    no_metadata: bool,
+
+    /// Save image to new file in $TMPDIR (with extension of --format) and print its path
+    #[arg(long, conflicts_with_all = ["output", "selection_only", "pipe", "pick_color"])]
+    to_temp: bool,
+
+    /// Refuse to overwrite the output file if it already exists (exits with code 7)
+    #[arg(long)]
+    no_clobber: bool,
+
     /// Do not use region selector
     #[arg(long, short)]
     fullscreen: bool,
 
-    /// Only make region selection and print it
+    /// Capture region of the previous interactive selection on its output without showing region
+    /// selector, region selector is shown if the output is gone or region does not fit into it
+    #[arg(long, visible_alias = "last", conflicts_with = "fullscreen")]
+    last_region: bool,
+
+    /// Print regions of previous interactive selections (oldest first) in --selection-format and
+    /// exit
+    #[arg(long, conflicts_with_all = ["fullscreen", "last_region", "geometry", "pick_color"])]
+    print_history: bool,
+
+    /// Number of interactive selections remembered for --print-history, 0 disables history
+    #[arg(long, value_name = "N", default_value_t = 10)]
+    history_size: usize,
+
+    /// Capture output with this name (like DP-1) instead of the first one
+    #[arg(long, value_name = "NAME")]
+    output_name: Option<String>,
+
+    /// Show letter and name on every output and capture the one clicked (or chosen with its
+    /// letter key), only if there are several outputs
+    #[arg(long, conflicts_with = "output_name")]
+    pick_output: bool,
+
+    /// Use input of seat with this name (like seat0) for region selector instead of the first one
+    #[arg(long, value_name = "NAME")]
+    seat: Option<String>,
+
+    /// Keyboard focus of region selector. With "none" compositor shortcuts and screen readers
+    /// are not blocked, but keys do not work, cancel selection with right click
+    #[arg(long, value_name = "MODE", default_value = "exclusive")]
+    keyboard_interactivity: KeyboardMode,
+
+    /// Cancel region selection when its overlay loses keyboard focus (e.g. on switching
+    /// windows). Some compositors take focus away spuriously, so it is off by default
+    #[arg(long)]
+    cancel_on_unfocus: bool,
+
+    /// If there are no outputs yet, wait up to SECONDS for one to appear (exits with code 6)
+    #[arg(long, value_name = "SECONDS")]
+    wait_for_output: Option<u64>,
+
+    /// Capture "X,Y WxH" region in global compositor coordinates without showing region selector
+    #[arg(
+        long,
+        value_parser = parse_geometry,
+        conflicts_with_all = ["fullscreen", "last_region"],
+    )]
+    geometry: Option<Rectangle>,
+
+    /// Capture focused window (asked from sway or Hyprland over IPC) without showing region
+    /// selector. Its output is captured unless --output-name is passed
+    #[arg(
+        long,
+        conflicts_with_all = [
+            "fullscreen", "last_region", "geometry", "pick_output", "pick_color", "selection_only",
+        ],
+    )]
+    active_window: bool,
+
+    /// Capture region with this NAME from [regions] table of config file (in "X,Y WxH" format,
+    /// relative to top left corner of captured output) without showing region selector
+    #[arg(
+        long,
+        value_name = "NAME",
+        conflicts_with_all = [
+            "fullscreen", "last_region", "geometry", "active_window", "pick_color",
+        ],
+    )]
+    region: Option<String>,
+
+    /// Print names of regions from [regions] table of config file and exit
+    #[arg(long, conflicts_with_all = ["region", "print_history"])]
+    list_regions: bool,
+
+    /// Interpret --geometry relative to top left corner of captured output
+    #[arg(long, requires = "geometry")]
+    relative: bool,
+
+    /// Print name, logical size and scale of output and region which would be captured, then
+    /// exit without capturing anything
+    #[arg(
+        long,
+        conflicts_with_all = ["pick_output", "pick_color", "print_history", "to_temp"],
+    )]
+    dry_run: bool,
+
+    /// Keep selecting regions of the same frame until Enter is pressed, each region is saved to
+    /// numbered --output (shot.png becomes shot-1.png, shot-2.png...)
+    #[arg(
+        long,
+        conflicts_with_all = [
+            "fullscreen", "last_region", "geometry", "region", "active_window", "pick_color",
+            "pipe", "to_temp",
+        ],
+    )]
+    multi: bool,
+
+    /// Stay running and capture again on every line read from stdin or SIGUSR1, reusing
+    /// connection to compositor, until SIGTERM. Images are saved to numbered --output (shot.png
+    /// becomes shot-1.png, shot-2.png...)
+    #[arg(
+        long,
+        conflicts_with_all = [
+            "multi", "repeat", "pick_color", "dry_run", "print_history", "list_regions",
+        ],
+    )]
+    watch: bool,
+
+    /// Only make region selection and print it. If --output is passed too, image is saved and
+    /// selection is printed to stderr
     #[arg(long, short)]
     selection_only: bool,
 
-    /// If --selection-only, format of selection output
-    #[arg(long, short = 'F', default_value = "%x,%y %wx%h%n")]
+    /// If --selection-only or --print-history, format of selection output. "json" prints object
+    /// with x, y, width, height, output (null if unknown) and scale fields
+    #[arg(
+        long,
+        short = 'F',
+        env = "PRTSC_SELECTION_FORMAT",
+        default_value = "%x,%y %wx%h%n"
+    )]
     selection_format: String,
+
+    /// If --selection-only, also print min/max/mean RGB and brightness of selected region. With
+    /// --selection-format json they are printed in "stats" field of selection object
+    #[arg(long, requires = "selection_only")]
+    analyze: bool,
+
+    /// With --selection-only, print every selection as soon as it is made and show selector
+    /// again, until selection is canceled with Escape
+    #[arg(
+        long = "loop",
+        requires = "selection_only",
+        conflicts_with_all = [
+            "output", "fullscreen", "last_region", "geometry", "region", "active_window", "multi",
+        ],
+    )]
+    repeat: bool,
+
+    /// With --loop, capture screen again before every next selection instead of selecting on
+    /// the same frame
+    #[arg(long, requires = "repeat")]
+    fresh: bool,
+
+    /// Show pointer coordinates next to it until selection is started
+    #[arg(long)]
+    show_coords: bool,
+
+    /// Pointer shape over region selector
+    #[arg(long, value_name = "SHAPE", default_value = "crosshair")]
+    cursor_shape: CursorShape,
+
+    /// Fade in region selector overlay
+    #[arg(long)]
+    animate: bool,
+
+    /// Do not animate region selector: no fade in and no moving dashes on edges of selection
+    /// waiting for --confirm or --edit
+    #[arg(long, conflicts_with = "animate")]
+    no_animation: bool,
+
+    /// Select single pixel with click without dragging (otherwise click is ignored)
+    #[arg(long)]
+    allow_click: bool,
+
+    /// Ignore drags shorter than PIXELS between corners, like clicks without dragging
+    #[arg(long, value_name = "PIXELS", default_value_t = 0)]
+    min_size: u32,
+
+    /// Snap selection corners to multiples of PIXELS (hold Ctrl to place corner freely)
+    #[arg(long, value_name = "PIXELS", value_parser = clap::value_parser!(u32).range(1..))]
+    snap: Option<u32>,
+
+    /// Snap selection corners to edges of windows (asked from sway or Hyprland over IPC),
+    /// highlight window under pointer and select it with click
+    #[arg(long)]
+    snap_windows: bool,
+
+    /// Highlight window under pointer (asked from sway or Hyprland over IPC) and capture it with
+    /// click, dragging still selects region
+    #[arg(long)]
+    window_under_cursor: bool,
+
+    /// Lock ratio of width to height of dragged selection, as "W:H" (like 16:9) or number
+    #[arg(long, value_name = "RATIO", value_parser = parse_aspect)]
+    aspect: Option<f64>,
+
+    /// Show magnified pixels around pointer in region selector (toggled with Z)
+    #[arg(long)]
+    zoom: bool,
+
+    /// Do not show size of selection next to pointer while dragging
+    #[arg(long)]
+    no_label: bool,
+
+    /// How much darker screen outside of selection is, from 0 (not dimmed) to 1 (black)
+    #[arg(long, value_name = "FACTOR", default_value_t = 0.5, value_parser = parse_dim)]
+    dim: f64,
+
+    /// Draw border of this color as #RRGGBB or #RRGGBBAA inside selection
+    #[arg(long, value_name = "COLOR", value_parser = parse_color)]
+    border_color: Option<[u8; 4]>,
+
+    /// If --border-color, width of border in pixels
+    #[arg(
+        long,
+        value_name = "PIXELS",
+        default_value_t = 2,
+        value_parser = clap::value_parser!(u32).range(1..)
+    )]
+    border_width: u32,
+
+    /// Keep selected region at full brightness with soft falloff instead of drawing crosshair
+    #[arg(long)]
+    spotlight: bool,
+
+    /// Keep selection after mouse button is released to move it with the middle mouse button,
+    /// confirm it with click outside of it or Enter
+    #[arg(long)]
+    confirm: bool,
+
+    /// Keep selection after mouse button is released with handles to resize it, drag inside to
+    /// move it, confirm it with double-click or Enter
+    #[arg(long)]
+    edit: bool,
+
+    /// Grow (scroll up) or shrink (scroll down) selection by PIXELS on every side per mouse wheel
+    /// notch while dragging
+    #[arg(
+        long,
+        value_name = "PIXELS",
+        default_value_t = 10,
+        value_parser = clap::value_parser!(u32).range(1..=1000)
+    )]
+    scroll_step: u32,
+
+    /// Also write saved image to stdout
+    #[arg(long, conflicts_with = "selection_only")]
+    pipe: bool,
+
+    /// Print color of clicked pixel instead of saving image
+    #[arg(
+        long,
+        conflicts_with_all = [
+            "selection_only", "fullscreen", "last_region", "geometry", "pipe", "grayscale",
+            "crop_padding",
+        ],
+    )]
+    pick_color: bool,
+
+    /// If --pick-color, format of printed color
+    #[arg(long, value_name = "FORMAT", default_value = "hex")]
+    color_format: ColorFormat,
+
+    /// Write image to stdout even if it is a terminal
+    #[arg(long)]
+    force_stdout: bool,
+
+    /// Do not print status messages, only errors
+    #[arg(long, short, conflicts_with = "verbose")]
+    quiet: bool,
+
+    /// Print timings and chosen parameters to stderr, pass twice to also print wayland events
+    #[arg(long, short, action = clap::ArgAction::Count)]
+    verbose: u8,
+
+    /// Read defaults from FILE instead of $XDG_CONFIG_HOME/prtsc-wayland/config.toml
+    #[arg(long, value_name = "FILE")]
+    #[allow(dead_code)] // config is loaded before parsing, see `config::Source::from_args`
+    config: Option<PathBuf>,
+
+    /// Do not read config file
+    #[arg(long)]
+    #[allow(dead_code)]
+    no_config: bool,
+
+    #[command(subcommand)]
+    command: Option<Subcommand>,
 }
 
-enum ScreenshotResult {
-    Selection {
-        image: Box<[u8]>,
-        rect: Rectangle,
-        width: u32,
-        output_name: Option<String>,
-    },
-    Canceled,
+fn parse_geometry(s: &str) -> Result<Rectangle, String> {
+    Rectangle::parse_geometry(s).ok_or_else(|| format!("expected \"X,Y WxH\", got \"{s}\""))
 }
 
-fn make_screenshot(args: &Args) -> Result<ScreenshotResult, app::Error> {
-    let conn = Connection::connect_to_env().map_err(app::Error::Connect)?;
-    // Initialize outputs
-    let mut mgr = WaylandAppManager::initialize(&conn)?;
+fn parse_offset(s: &str) -> Result<(i32, i32), String> {
+    s.split_once(',')
+        .and_then(|(x, y)| Some((x.parse().ok()?, y.parse().ok()?)))
+        .ok_or_else(|| format!("expected \"X,Y\", got \"{s}\""))
+}
 
-    let output_name = {
-        let ctx = mgr.app.ctx.base();
-        ctx.output_state
-            .outputs()
-            .next()
-            .and_then(|o| ctx.output_state.info(&o).and_then(|i| i.name))
-    };
+fn parse_aspect(s: &str) -> Result<f64, String> {
+    points::parse_aspect(s)
+        .ok_or_else(|| format!("expected \"W:H\" or positive number, got \"{s}\""))
+}
 
-    // Make screenshot
-    mgr.initialize_partial()?;
-    mgr.next_app()?;
-    mgr.dispatch_until_done()?;
+fn parse_scale(s: &str) -> Result<f64, String> {
+    match s.parse() {
+        Ok(factor) if factor > 0.0 && f64::is_finite(factor) => Ok(factor),
+        _ => Err(format!("expected positive number, got \"{s}\"")),
+    }
+}
 
-    if args.fullscreen {
-        let AppState::ScreenshotApp(ScreenshotApp {
-            image: Some(image), ..
-        }) = mgr.app.state
-        else {
-            unreachable!("next app after base should be screenshot, image should be present")
-        };
-        let ctx = mgr
-            .app
-            .ctx
-            .partial()
-            .expect("partial context should be initialized here");
-        let (width, height) = (ctx.logical_size.x, ctx.logical_size.y);
-
-        Ok(ScreenshotResult::Selection {
-            image,
-            width,
-            rect: Rectangle::new(Point::new(0, 0), width, height),
-            output_name,
-        })
+fn parse_dim(s: &str) -> Result<f64, String> {
+    match s.parse() {
+        Ok(factor) if (0.0..=1.0).contains(&factor) => Ok(factor),
+        _ => Err(format!("expected number from 0 to 1, got \"{s}\"")),
+    }
+}
+
+fn parse_color(s: &str) -> Result<[u8; 4], String> {
+    shadow::parse_color(s).ok_or_else(|| format!("expected #RRGGBB or #RRGGBBAA, got \"{s}\""))
+}
+
+/// Selection printed with `--selection-format json --analyze`, stats are null for empty region.
+#[derive(serde::Serialize)]
+struct AnalyzedJson {
+    #[serde(flatten)]
+    selection: state::HistoryEntry,
+    stats: Option<stats::RegionStats>,
+}
+
+/// Write `rect` in `--selection-format` to `out`. Color of its top left `pixel` is printed as
+/// unknown if it is not passed.
+fn print_selection(
+    out: &mut dyn Write,
+    fmt: &str,
+    rect: &Rectangle,
+    output_name: Option<&str>,
+    scale: i32,
+    pixel: Option<[u8; 3]>,
+) -> io::Result<()> {
+    if fmt == "json" {
+        let selection = state::HistoryEntry::new(rect, output_name, scale);
+        writeln!(
+            out,
+            "{}",
+            serde_json::to_string(&selection).expect("selection is serializable")
+        )
     } else {
-        // Make selection
-        mgr.initialize_full()?;
-        mgr.next_app()?;
-        mgr.dispatch_until_done()?;
-
-        let (rect, image) = match mgr.app.state {
-            AppState::SelectionApp(app) => (app.selected_region(), app.image),
-            _ => unreachable!("next app after screenshot should be selection"),
+        let fmt = RectFmt {
+            rect: rect.clone(),
+            fmt,
+            output_name,
+            scale,
+            pixel,
         };
+        write!(out, "{fmt}")
+    }
+}
 
-        let Some(rect) = rect else {
-            return Ok(ScreenshotResult::Canceled);
+/// Write region of `capture` in `--selection-format` to `out`, followed by its statistics if
+/// `--analyze` is passed.
+fn print_capture(out: &mut dyn Write, args: &Args, capture: &Capture) -> io::Result<()> {
+    let pixel = match capture.data[..] {
+        [l, ..] if args.grayscale => Some([l, l, l]),
+        [r, g, b, ..] => Some([r, g, b]),
+        _ => None,
+    };
+    if args.analyze && args.selection_format == "json" {
+        let selection = AnalyzedJson {
+            selection: state::HistoryEntry::new(
+                &capture.rect,
+                capture.output_name.as_deref(),
+                capture.scale_factor,
+            ),
+            stats: stats::RegionStats::compute(&capture.data),
         };
+        let json = serde_json::to_string(&selection).expect("selection is serializable");
+        return writeln!(out, "{json}");
+    }
+    print_selection(
+        out,
+        &args.selection_format,
+        &capture.rect,
+        capture.output_name.as_deref(),
+        capture.scale_factor,
+        pixel,
+    )?;
+    if args.analyze {
+        if let Some(stats) = stats::RegionStats::compute(&capture.data) {
+            write!(out, "{stats}")?;
+        }
+    }
 
-        let width = mgr
-            .app
-            .ctx
-            .partial()
-            .expect("partial context should be initialized here")
-            .logical_size
-            .x;
-
-        Ok(ScreenshotResult::Selection {
-            image,
-            rect,
-            width,
-            output_name,
-        })
+    Ok(())
+}
+
+/// Color printed with `--pick-color`.
+#[derive(Clone, Copy, clap::ValueEnum)]
+enum ColorFormat {
+    /// #RRGGBB
+    Hex,
+    /// rgb(R, G, B)
+    Rgb,
+}
+
+impl ColorFormat {
+    fn format(self, [r, g, b]: [u8; 3]) -> String {
+        match self {
+            Self::Hex => format!("#{r:02X}{g:02X}{b:02X}"),
+            Self::Rgb => format!("rgb({r}, {g}, {b})"),
+        }
     }
 }
 
-fn save_image(args: &Args, rect: Rectangle, data: &[u8]) -> Result<(), ImageError> {
-    let buffer = ImageBuffer::<Rgb<u8>, _>::from_raw(rect.width, rect.height, data)
-        .expect("Failed to create ImageBuffer from raw data");
+/// Color of single RGB8 pixel picked with `--pick-color`.
+fn picked_color(data: &[u8]) -> Option<[u8; 3]> {
+    match *data {
+        [r, g, b] => Some([r, g, b]),
+        _ => None,
+    }
+}
+
+#[derive(clap::Subcommand)]
+enum Subcommand {
+    /// Write man page to DIR
+    #[command(hide = true)]
+    Mangen { dir: PathBuf },
+}
+
+/// [`Args`] command with help of selection format specifiers and exit codes.
+fn command() -> clap::Command {
+    use std::fmt::Write as _;
 
+    let heading = Style::new().bold().underline();
+    let bold = Style::new().bold();
+
+    let mut help = format!("{heading}Formatting:{heading:#}\n");
+    for spec in SPECIFIERS {
+        _ = writeln!(
+            help,
+            "  {bold}{:5}{bold:#} {}",
+            spec.usage(),
+            spec.description
+        );
+    }
+    help.push('\n');
+    help.push_str(include_str!("../formatting.txt"));
+
+    Args::command().after_help(help)
+}
+
+/// Exit codes of the process, documented in `--help`.
+#[derive(Clone, Copy)]
+enum ExitCode {
+    Canceled = 1,
+    Wayland = 2,
+    Save = 3,
+    Usage = 4,
+    Unsupported = 5,
+    NoOutput = 6,
+    FileExists = 7,
+}
+
+impl ExitCode {
+    fn exit(self) -> ! {
+        std::process::exit(self as i32)
+    }
+}
+
+/// Output of encoders which need to go back in it.
+trait WriteSeek: Write + Seek {}
+
+impl<T: Write + Seek> WriteSeek for T {}
+
+fn save_image(
+    args: &Args,
+    options: &EncodeOptions,
+    data: &[u8],
+    width: u32,
+    height: u32,
+    color: ExtendedColorType,
+) -> Result<(), ImageError> {
     match args.output.as_str() {
         "-" => {
-            let encoder = PngEncoder::new(std::io::stdout());
-            buffer.write_with_encoder(encoder)?;
+            let format = args.format.unwrap_or(OutputFormat::Png);
+            log::debug!("format: {format}");
+
+            let start = Instant::now();
+            format.encode(options, data, width, height, color, io::stdout().lock())?;
+            suggest_faster_format(format, start.elapsed());
         }
         path => {
-            buffer.save(path)?;
-            println!("saved to {}", args.output);
+            // Resolve format before creating any files, so unsupported extensions don't leave
+            // garbage behind
+            let (format, image_format) = match args.format {
+                Some(format) => (Some(format), None),
+                None => {
+                    let (format, image_format) = OutputFormat::from_path(Path::new(path))?;
+                    (format, Some(image_format))
+                }
+            };
+
+            let encode = |mut w: &mut dyn WriteSeek| match format {
+                Some(format) => {
+                    log::debug!("format: {format}");
+                    let start = Instant::now();
+                    format.encode(options, data, width, height, color, w)?;
+                    suggest_faster_format(format, start.elapsed());
+                    Ok(())
+                }
+                // Formats which can't be selected with --format, but still supported by `image`
+                None => {
+                    let image_format = image_format.expect("detected from extension");
+                    log::debug!("format: {image_format:?}");
+                    image::write_buffer_with_format(
+                        &mut w,
+                        data,
+                        width,
+                        height,
+                        color,
+                        image_format,
+                    )
+                }
+            };
+
+            // --to-temp output is already reserved by us, so it's replaced regardless of
+            // --no-clobber
+            let no_clobber = args.no_clobber && !args.to_temp;
+            if args.pipe {
+                // Encode once and write the same bytes to both file and stdout
+                let mut encoded = Cursor::new(Vec::new());
+                encode(&mut encoded)?;
+                let encoded = encoded.into_inner();
+
+                save::write_atomically(
+                    Path::new(path),
+                    no_clobber,
+                    |w| Ok(w.write_all(&encoded)?),
+                )?;
+                io::stdout().lock().write_all(&encoded)?;
+            } else {
+                save::write_atomically(Path::new(path), no_clobber, |w| encode(w))?;
+            }
+            info!("saved to {}", args.output);
         }
     }
 
     Ok(())
 }
 
-fn main() {
-    let args = Args::parse();
+/// PNG encoding of large screenshots takes noticeable time, suggest QOI in verbose output if it
+/// is the case.
+fn suggest_faster_format(format: OutputFormat, elapsed: Duration) {
+    if format == OutputFormat::Png && elapsed > Duration::from_millis(100) {
+        log::debug!(
+            "hint: png encoding took {elapsed:?}, --format qoi is lossless and much faster"
+        );
+    }
+}
 
-    let (image, rect, width, output_name) = match make_screenshot(&args) {
-        Ok(ScreenshotResult::Selection {
-            image,
-            rect,
-            width,
-            output_name,
-        }) => (image, rect, width, output_name),
-        Ok(ScreenshotResult::Canceled) => {
-            eprintln!("selection canceled");
-            std::process::exit(1);
+/// Options of capture described by `args`. Interactive selection is used if `--last-region` has
+/// no previous selection.
+fn capture_options(args: &Args) -> CaptureOptions {
+    let mut options = CaptureOptions {
+        mode: CaptureMode::Interactive,
+        output_name: args.output_name.clone(),
+        pick_output: args.pick_output,
+        seat: args.seat.clone(),
+        timeout: args.timeout.map(Duration::from_secs),
+        windows: args.snap_windows || args.window_under_cursor,
+        selection: SelectionOptions {
+            cursor_shape: args.cursor_shape,
+            show_coords: args.show_coords,
+            // Fade in may be enabled in config
+            animate: args.animate && !args.no_animation,
+            march: !args.no_animation,
+            pick: args.pick_color,
+            multi: args.multi,
+            allow_click: args.allow_click,
+            min_size: args.min_size,
+            scroll_step: args.scroll_step,
+            confirm: args.confirm,
+            edit: args.edit,
+            spotlight: args.spotlight,
+            size_label: !args.no_label,
+            loupe: args.zoom,
+            snap: args.snap,
+            aspect: args.aspect,
+            dim: args.dim,
+            windows: Vec::new(),
+            snap_to_windows: args.snap_windows,
+            cancel_on_unfocus: args.cancel_on_unfocus,
+            border: args.border_color.map(|color| Border {
+                color,
+                width: args.border_width,
+            }),
+        },
+        keyboard: args.keyboard_interactivity,
+        grayscale: args.grayscale,
+        wait_for_output: args.wait_for_output.map(Duration::from_secs),
+        padding: args.crop_padding,
+    };
+
+    if args.fullscreen {
+        options.mode = CaptureMode::Fullscreen;
+    } else if args.active_window {
+        options.mode = CaptureMode::ActiveWindow;
+    } else if let Some(rect) = &args.geometry {
+        options.mode = if args.relative {
+            CaptureMode::Region(rect.clone())
+        } else {
+            CaptureMode::GlobalRegion(rect.clone())
+        };
+    } else if args.last_region {
+        match state::load_last_region() {
+            Some(last) => {
+                options.mode = CaptureMode::Region(last.rect);
+                if options.output_name.is_none() && !options.pick_output {
+                    options.output_name = last.output;
+                }
+            }
+            None => warn!("no previous selection found, falling back to interactive selection"),
         }
+    }
+
+    options
+}
 
-        Err(app::Error::Connect(c)) => {
-            eprintln!("unable to connect to wayland server: {c}");
-            std::process::exit(1);
+/// Capture screenshot according to `args` with `session`, falling back to interactive selection
+/// if `--last-region` can't be used. There are several captures only with `--multi`.
+fn capture(args: &Args, session: &mut Session) -> Result<Vec<Capture>, app::Error> {
+    let mut options = capture_options(args);
+    let captures = match session.capture_all(&options) {
+        Err(app::Error::InvalidRegion) if args.last_region => {
+            warn!("previous selection does not fit into output");
+            warn!("falling back to interactive selection");
+            options.mode = CaptureMode::Interactive;
+            session.capture_all(&options)
         }
-        Err(app::Error::Shm(e)) => {
-            eprintln!("failed to initialize wl_shm: {e}");
-            std::process::exit(1);
+        Err(app::Error::UnknownOutput(name)) if args.last_region && args.output_name.is_none() => {
+            warn!("output {name} of previous selection is not connected");
+            warn!("falling back to interactive selection");
+            options.mode = CaptureMode::Interactive;
+            options.output_name = None;
+            session.capture_all(&options)
         }
-        Err(app::Error::Zwlr(e)) => {
-            eprintln!("failed to initialize zwlr_screencopy_frame_v1: {e}");
-            eprintln!(
-                "note: it may occur because your wayland compositor does not support this protocol"
-            );
-            eprintln!(
-                "usually it happens on KDE or GNOME. you may use another screenshot utility."
-            );
-            eprintln!("check compositor support of zwlr_screencopy_frame_v1 here:");
-            eprintln!(
-                "https://wayland.app/protocols/wlr-screencopy-unstable-v1#compositor-support"
+        result => result,
+    }?;
+
+    if let (CaptureMode::Interactive, false) = (&options.mode, args.pick_color) {
+        let last = captures.last().expect("at least one region is captured");
+        if let Err(e) = state::save_last_region(&last.selected, last.output_name.as_deref()) {
+            warn!("failed to save selection for --last-region: {e}");
+        }
+        if args.history_size > 0 {
+            for capture in &captures {
+                let entry = state::HistoryEntry::new(
+                    &capture.selected,
+                    capture.output_name.as_deref(),
+                    capture.scale_factor,
+                );
+                if let Err(e) = state::push_history(entry, args.history_size) {
+                    warn!("failed to save selection to history: {e}");
+                }
+            }
+        }
+    }
+
+    Ok(captures)
+}
+
+/// Print error of capture and exit with its code.
+fn exit_on_error(e: app::Error) -> ! {
+    match e {
+        app::Error::Canceled => {
+            error!("selection canceled");
+            ExitCode::Canceled.exit();
+        }
+
+        app::Error::Timeout => {
+            error!("selection timed out");
+            ExitCode::Canceled.exit();
+        }
+
+        app::Error::Connect(c) => {
+            error!("unable to connect to wayland server: {c}");
+            ExitCode::Wayland.exit();
+        }
+        app::Error::Shm(e) => {
+            error!("failed to initialize wl_shm: {e}");
+            ExitCode::Wayland.exit();
+        }
+        app::Error::Capture(e) => {
+            error!("failed to initialize zwlr_screencopy_manager_v1: {e}");
+            error!("ext_image_copy_capture_manager_v1 is not supported either");
+            error!(
+                "note: it may occur because your wayland compositor does not support these protocols"
             );
-            std::process::exit(1);
+            error!("usually it happens on GNOME. you may use another screenshot utility.");
+            error!("check compositor support of the protocols here:");
+            error!("https://wayland.app/protocols/wlr-screencopy-unstable-v1#compositor-support");
+            error!("https://wayland.app/protocols/ext-image-copy-capture-v1#compositor-support");
+            ExitCode::Unsupported.exit();
+        }
+        app::Error::Compositor(e) => {
+            error!("failed to initialize wl_compositor: {e}");
+            ExitCode::Wayland.exit();
+        }
+        app::Error::LayerShell(e) => {
+            error!("failed to initialize layer shell: {e}");
+            ExitCode::Unsupported.exit();
+        }
+        app::Error::Global(e) => {
+            error!("failed to initialize event queue: {e}");
+            ExitCode::Wayland.exit();
         }
-        Err(app::Error::Compositor(e)) => {
-            eprintln!("failed to initialize wl_compositor: {e}");
-            std::process::exit(1);
+        app::Error::CreatePool(e) => {
+            error!("failed to create pool: {e}");
+            ExitCode::Wayland.exit();
         }
-        Err(app::Error::LayerShell(e)) => {
-            eprintln!("failed to initialize layer shell: {e}");
-            std::process::exit(1);
+        app::Error::Dispatch(e) => {
+            error!("dispatch error: {e}");
+            ExitCode::Wayland.exit();
         }
-        Err(app::Error::Global(e)) => {
-            eprintln!("failed to initialize event queue: {e}");
-            std::process::exit(1);
+        app::Error::NoOutput | app::Error::NoOutputInfo => {
+            error!("failed to find any wayland outputs");
+            error!("you may turn on your monitor *joke*");
+            ExitCode::NoOutput.exit();
         }
-        Err(app::Error::CreatePool(e)) => {
-            eprintln!("failed to create pool: {e}");
-            std::process::exit(1);
+        app::Error::NoOutputLogicalSize => {
+            error!("output does not contains information about logical size");
+            ExitCode::NoOutput.exit();
         }
-        Err(app::Error::Dispatch(e)) => {
-            eprintln!("dispatch error: {e}");
-            std::process::exit(1);
+        app::Error::UnknownOutput(name) => {
+            error!("there is no output named {name}");
+            ExitCode::NoOutput.exit();
         }
-        Err(app::Error::NoOutput | app::Error::NoOutputInfo) => {
-            eprintln!("failed to find any wayland outputs");
-            eprintln!("you may turn on your monitor *joke*");
-            std::process::exit(1);
+        app::Error::UnknownSeat(name) => {
+            error!("there is no seat named {name}");
+            ExitCode::Usage.exit();
         }
-        Err(app::Error::NoOutputLogicalSize) => {
-            eprintln!("output does not contains information about logical size");
-            std::process::exit(1);
+        app::Error::CaptureFailed => {
+            error!("capture failed: compositor could not copy output");
+            ExitCode::Wayland.exit();
+        }
+        app::Error::InvalidRegion => {
+            error!("requested region does not fit into output");
+            ExitCode::Usage.exit();
+        }
+        app::Error::NoWindowSource => {
+            error!("windows of this compositor are not known");
+            error!("note: supported compositors are sway and Hyprland");
+            ExitCode::Unsupported.exit();
+        }
+        app::Error::NoActiveWindow => {
+            error!("failed to find focused window");
+            ExitCode::Unsupported.exit();
+        }
+    }
+}
+
+fn main() {
+    let mut config = match Config::load(config::Source::from_args(std::env::args_os().skip(1))) {
+        Ok(config) => config,
+        Err(config::Error::Read(e)) => {
+            error!("failed to read config file: {e}");
+            ExitCode::Usage.exit();
+        }
+        Err(config::Error::Parse(e)) => {
+            error!("failed to parse config file: {e}");
+            ExitCode::Usage.exit();
         }
     };
+    let config_unknown_keys: Vec<String> = config.unknown_keys().map(String::from).collect();
+    let regions = std::mem::take(&mut config.regions);
+    let (mut args, output_from_cli) = config
+        .apply(command())
+        .try_get_matches()
+        .and_then(|matches| {
+            let output_from_cli = matches.value_source("output") == Some(ValueSource::CommandLine);
+            Ok((Args::from_arg_matches(&matches)?, output_from_cli))
+        })
+        .unwrap_or_else(|e| {
+            // clap uses code 2 for usage errors, but it is reserved for wayland errors here
+            _ = e.print();
+            if e.use_stderr() {
+                ExitCode::Usage.exit()
+            } else {
+                std::process::exit(0)
+            }
+        });
 
-    if args.selection_only {
-        let fmt = RectFmt {
-            rect,
-            fmt: &args.selection_format,
-            output_name: output_name.as_deref(),
+    if args.quiet {
+        logging::set_level(logging::Level::Quiet);
+    }
+    logging::init_diagnostics(args.verbose);
+
+    for key in config_unknown_keys {
+        warn!("unknown config key `{key}` is ignored");
+    }
+    if let Some(Subcommand::Mangen { dir }) = &args.command {
+        match man::write_man_page(command(), dir) {
+            Ok(path) => info!("man page is written to {}", path.display()),
+            Err(e) => {
+                error!("failed to write man page: {e}");
+                ExitCode::Save.exit();
+            }
+        }
+        return;
+    }
+    if args.print_history {
+        let mut stdout = io::stdout().lock();
+        for entry in state::load_history() {
+            _ = print_selection(
+                &mut stdout,
+                &args.selection_format,
+                &entry.rect(),
+                entry.output.as_deref(),
+                entry.scale,
+                None,
+            );
+        }
+        return;
+    }
+    if args.list_regions {
+        for name in regions.keys() {
+            println!("{name}");
+        }
+        return;
+    }
+    if let Some(name) = &args.region {
+        match config::resolve_region(&regions, name) {
+            Ok(rect) => {
+                args.geometry = Some(rect);
+                args.relative = true;
+            }
+            Err(config::RegionError::Unknown) => {
+                error!("there is no region named {name} in config file");
+                ExitCode::Usage.exit();
+            }
+            Err(config::RegionError::Invalid(geometry)) => {
+                error!("region {name} in config file is \"{geometry}\", expected \"X,Y WxH\"");
+                ExitCode::Usage.exit();
+            }
+        }
+    }
+    if !output_from_cli {
+        if let Some(dir) = std::env::var_os(config::OUTPUT_DIR_VAR).filter(|d| !d.is_empty()) {
+            args.output = config::output_in_dir(&args.output, Path::new(&dir));
+        }
+    }
+
+    if args.dry_run {
+        let dry_run =
+            prtsc_wayland::dry_run(&capture_options(&args)).unwrap_or_else(|e| exit_on_error(e));
+        println!(
+            "output: {}",
+            dry_run.output_name.as_deref().unwrap_or("<unknown>")
+        );
+        println!("resolution: {}x{}", dry_run.width, dry_run.height);
+        println!("scale: {}", dry_run.scale_factor);
+        match dry_run.rect {
+            Some(rect) => println!("region: {rect}"),
+            None => println!("region: <interactive>"),
+        }
+        return;
+    }
+
+    // With --selection-only image is saved only if output is passed explicitly
+    let saves_image = !args.selection_only || output_from_cli;
+    if (args.multi || args.watch) && args.output == "-" && saves_image {
+        error!(
+            "--multi and --watch save every image to its own file, pass file name with --output"
+        );
+        ExitCode::Usage.exit();
+    }
+    if (args.output == "-" || args.pipe)
+        && saves_image
+        && !args.pick_color
+        && !args.force_stdout
+        && io::stdout().is_terminal()
+    {
+        error!("refusing to write binary image data to a terminal");
+        error!("redirect stdout to a file or pipe, or pass --force-stdout");
+        ExitCode::Usage.exit();
+    }
+
+    if args.repeat {
+        let result = prtsc_wayland::capture_loop(&capture_options(&args), args.fresh, |capture| {
+            let mut stdout = io::stdout().lock();
+            // Consumers read selections while selector is still shown
+            _ = print_capture(&mut stdout, &args, &capture).and_then(|()| stdout.flush());
+        });
+        result.unwrap_or_else(|e| exit_on_error(e));
+        return;
+    }
+
+    let mut session =
+        Session::connect(&capture_options(&args)).unwrap_or_else(|e| exit_on_error(e));
+    if args.watch {
+        watch(&mut args, &mut session, saves_image);
+        return;
+    }
+    capture_and_save(&mut args, &mut session, saves_image, None)
+        .unwrap_or_else(|e| exit_on_error(e));
+}
+
+/// Capture screenshot and save or print it according to `args`. Image is saved to `--output`
+/// numbered with `number` if it is set (or with number of region for `--multi`).
+fn capture_and_save(
+    args: &mut Args,
+    session: &mut Session,
+    saves_image: bool,
+    number: Option<usize>,
+) -> Result<(), app::Error> {
+    let output = args.output.clone();
+    let start = Instant::now();
+    let captures = capture(args, session);
+    log::debug!("capture: {:?} elapsed", start.elapsed());
+    let captured_at = SystemTime::now();
+    let captures = captures?;
+
+    if args.pick_color {
+        let Some(color) = picked_color(&captures[0].data) else {
+            error!("picked region is not a single pixel");
+            ExitCode::Usage.exit();
         };
-        print!("{fmt}");
+        println!("{}", args.color_format.format(color));
         std::process::exit(0);
     }
 
-    // Write Xrgb8888 buffer to rgb vector
-    let mut data = Vec::with_capacity(rect.width as usize * rect.height as usize * 4);
+    for (i, capture) in captures.into_iter().enumerate() {
+        let rect = &capture.rect;
+
+        log::debug!(
+            "output: {}, region: {},{} {}x{}",
+            capture.output_name.as_deref().unwrap_or("<unknown>"),
+            rect.start.x,
+            rect.start.y,
+            rect.width,
+            rect.height
+        );
+
+        if args.selection_only {
+            // Stdout may be taken by image data
+            let mut out: Box<dyn Write> = if saves_image {
+                Box::new(io::stderr().lock())
+            } else {
+                Box::new(io::stdout().lock())
+            };
+            _ = print_capture(&mut out, args, &capture);
+            if !saves_image {
+                continue;
+            }
+        }
+
+        let number = if args.multi { Some(i + 1) } else { number };
+        if let Some(n) = number {
+            args.output = save::numbered_output(&output, n);
+        }
+        save_capture(args, capture, captured_at);
+    }
+    args.output = output;
+
+    Ok(())
+}
+
+/// Capture screenshot with `session` every time a line is read from stdin or SIGUSR1 is
+/// received, until SIGTERM is received. Capture in progress is finished first.
+fn watch(args: &mut Args, session: &mut Session, saves_image: bool) {
+    // Signal handlers only write to these sockets, so they can be polled with stdin
+    let register = |signal| {
+        let (rx, tx) = UnixStream::pair()?;
+        signal_hook::low_level::pipe::register(signal, tx)?;
+        io::Result::Ok(rx)
+    };
+    let (term, mut usr1) = match (
+        register(signal_hook::consts::SIGTERM),
+        register(signal_hook::consts::SIGUSR1),
+    ) {
+        (Ok(term), Ok(usr1)) => (term, usr1),
+        (Err(e), _) | (_, Err(e)) => {
+            error!("failed to set up signal handlers: {e}");
+            ExitCode::Unsupported.exit();
+        }
+    };
+
+    info!(
+        "waiting for a line on stdin or SIGUSR1 (pid {})",
+        std::process::id()
+    );
+    let stdin = io::stdin();
+    let mut stdin_open = true;
+    let mut buf = [0; 256];
+    let mut count = 0;
+    loop {
+        let mut fds = vec![term.as_fd(), usr1.as_fd()];
+        if stdin_open {
+            fds.push(stdin.as_fd());
+        }
+        let triggers = match session
+            .wait_readable(&fds)
+            .unwrap_or_else(|e| exit_on_error(e))
+        {
+            0 => {
+                info!("terminated");
+                return;
+            }
+            // Several signals may be delivered at once
+            1 => usr1.read(&mut buf).unwrap_or(1).max(1),
+            _ => match rustix::io::read(&stdin, &mut buf) {
+                Ok(0) | Err(_) => {
+                    // Signals still work
+                    stdin_open = false;
+                    0
+                }
+                Ok(n) => buf[..n].iter().filter(|&&b| b == b'\n').count(),
+            },
+        };
+
+        for _ in 0..triggers {
+            count += 1;
+            match capture_and_save(args, session, saves_image, Some(count)) {
+                Ok(()) => (),
+                Err(app::Error::Canceled) => warn!("selection canceled"),
+                Err(app::Error::Timeout) => warn!("selection timed out"),
+                Err(e) => exit_on_error(e),
+            }
+        }
+    }
+}
+
+/// Color which transparency of saved image is flattened onto: `--background`, or white if
+/// format of output can't store alpha. [`None`] if alpha is kept.
+fn flatten_color(args: &Args) -> Option<[u8; 3]> {
+    if let Some([r, g, b, _]) = args.background {
+        return Some([r, g, b]);
+    }
+
+    match output_format(args) {
+        Some(format) if !format.has_alpha() => Some([255, 255, 255]),
+        _ => None,
+    }
+}
 
-    let region = image.chunks_exact(4);
-    let region = region.chunks(width as usize);
-    let region = region
-        .into_iter()
-        .skip(rect.start.y as usize)
-        .take(rect.height as usize)
-        .flat_map(|v| v.skip(rect.start.x as usize).take(rect.width as usize));
+/// Format image is saved in, [`None`] if it can't be selected with `--format` (or output has
+/// unknown extension, saving fails then).
+fn output_format(args: &Args) -> Option<OutputFormat> {
+    if let Some(format) = args.format {
+        return Some(format);
+    }
+    if args.output == "-" || args.to_temp {
+        return Some(OutputFormat::Png);
+    }
+
+    OutputFormat::from_path(Path::new(&args.output))
+        .ok()
+        .and_then(|(format, _)| format)
+}
+
+/// Post-process `capture` and save it to `args.output`, exiting on errors.
+fn save_capture(args: &mut Args, capture: Capture, captured_at: SystemTime) {
+    let options = EncodeOptions {
+        quality: args.quality,
+        raw_header: args.raw_header,
+        png_compression: args.png_compression,
+        png_text: if args.no_metadata {
+            Vec::new()
+        } else {
+            metadata::png_text(&capture, captured_at)
+        },
+        pixels_per_metre: match args.dpi {
+            Some(dpi) => Some((dpi as f64 / 0.0254).round() as u32),
+            // Scaled image keeps physical size of captured region
+            None => capture
+                .pixels_per_metre
+                .map(|ppm| (ppm as f64 * args.scale.unwrap_or(1.0)).round() as u32),
+        },
+    };
+
+    let (mut width, mut height) = (capture.width, capture.height);
+    let mut data = capture.data;
+    if args.invert {
+        // Every channel of both RGB and luma
+        data.iter_mut().for_each(|v| *v = 255 - *v);
+    }
+    for region in &args.pixelate {
+        let channels = if args.grayscale { 1 } else { 3 };
+        if !redact::pixelate(
+            &mut data,
+            width,
+            height,
+            channels,
+            region,
+            args.pixelate_block,
+        ) {
+            warn!("--pixelate region {region} is outside of captured image");
+        }
+    }
+    if let Some(factor) = args.scale {
+        (data, width, height) =
+            scale::scale(data, width, height, args.grayscale, factor, args.filter);
+    }
+    let (mut data, mut color) = match args.round_corners {
+        Some(radius) => (
+            corners::round_corners(&data, width, height, radius),
+            ExtendedColorType::Rgba8,
+        ),
+        None if args.grayscale => (data, ExtendedColorType::L8),
+        None => (data, ExtendedColorType::Rgb8),
+    };
+
+    if args.shadow {
+        if color == ExtendedColorType::Rgb8 {
+            data = corners::rgb_to_rgba(&data);
+        }
+        let shadow = shadow::Shadow {
+            blur: args.shadow_blur,
+            offset: args.shadow_offset,
+            color: args.shadow_color,
+        };
+        (data, width, height) = shadow::drop_shadow(&data, width, height, &shadow);
+        color = ExtendedColorType::Rgba8;
+    }
+    if color == ExtendedColorType::Rgba8 {
+        if let Some(background) = flatten_color(args) {
+            data = corners::rgba_to_rgb(&data, background);
+            color = ExtendedColorType::Rgb8;
+        }
+    }
+
+    if args.to_temp {
+        let extension = args.format.unwrap_or(OutputFormat::Png).extension();
+        let path = save::reserve_temp_output(extension).and_then(|path| {
+            path.into_os_string().into_string().map_err(|path| {
+                _ = fs::remove_file(&path);
+                io::Error::new(io::ErrorKind::InvalidData, "temporary path is not UTF-8")
+            })
+        });
+        match path {
+            Ok(path) => args.output = path,
+            Err(e) => {
+                error!("failed to create temporary file: {e}");
+                ExitCode::Save.exit();
+            }
+        }
+    }
+
+    let start = Instant::now();
+    let result = save_image(args, &options, &data, width, height, color);
+    log::debug!("encode: {:?} elapsed", start.elapsed());
+
+    if args.to_temp && result.is_err() {
+        // Reserved file is left empty
+        _ = fs::remove_file(&args.output);
+    }
+    match result {
+        Ok(()) if args.to_temp => println!("{}", args.output),
+        Ok(()) => (),
+        Err(ImageError::IoError(e)) if e.kind() == io::ErrorKind::AlreadyExists => {
+            error!("refusing to overwrite existing file {}", args.output);
+            ExitCode::FileExists.exit();
+        }
+        Err(e) => {
+            error!("failed to save: {e}");
+            ExitCode::Save.exit();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use clap::Parser;
+
+    use prtsc_wayland::{
+        points::{Point, Rectangle},
+        Capture,
+    };
+
+    use super::{
+        flatten_color, parse_color, parse_dim, picked_color, print_capture, Args, ColorFormat,
+    };
+
+    #[test]
+    fn picked_color_formats() {
+        assert_eq!(ColorFormat::Hex.format([255, 128, 0]), "#FF8000");
+        assert_eq!(ColorFormat::Rgb.format([255, 128, 0]), "rgb(255, 128, 0)");
+
+        assert_eq!(picked_color(&[255, 128, 0]), Some([255, 128, 0]));
+        assert_eq!(picked_color(&[0; 12]), None);
+    }
+
+    #[test]
+    fn dim_is_limited() {
+        assert_eq!(parse_dim("0"), Ok(0.0));
+        assert_eq!(parse_dim("0.25"), Ok(0.25));
+        assert_eq!(parse_dim("1"), Ok(1.0));
+        assert!(parse_dim("1.5").is_err());
+        assert!(parse_dim("-0.1").is_err());
+        assert!(parse_dim("NaN").is_err());
+    }
+
+    #[test]
+    fn alpha_is_flattened_without_alpha_format() {
+        let flatten = |argv: &[&str]| {
+            let args =
+                Args::parse_from(std::iter::once("prtsc-wayland").chain(argv.iter().copied()));
+            flatten_color(&args)
+        };
+
+        assert_eq!(flatten(&["-o", "x.png"]), None);
+        assert_eq!(flatten(&["-o", "x.jpg"]), Some([255, 255, 255]));
+        assert_eq!(flatten(&["-o", "x.bmp"]), Some([255, 255, 255]));
+        assert_eq!(
+            flatten(&["-o", "-", "--format", "ppm"]),
+            Some([255, 255, 255])
+        );
+        assert_eq!(
+            flatten(&["-o", "x.jpg", "--background", "#102030"]),
+            Some([16, 32, 48])
+        );
+        assert_eq!(
+            flatten(&["-o", "x.png", "--background", "#102030"]),
+            Some([16, 32, 48])
+        );
+    }
+
+    #[test]
+    fn analyzed_selection_json() {
+        let args = Args::parse_from(["prtsc-wayland", "-s", "--analyze", "-F", "json"]);
+        let rect = Rectangle::new(Point::new(1, 2), 2, 1);
+        let capture = Capture {
+            data: vec![0, 10, 255, 255, 20, 255],
+            width: 2,
+            height: 1,
+            rect: rect.clone(),
+            selected: rect,
+            output_name: None,
+            pixels_per_metre: None,
+            scale_factor: 1,
+        };
 
-    for chunk in region {
-        data.push(chunk[2]);
-        data.push(chunk[1]);
-        data.push(chunk[0]);
+        let mut out = Vec::new();
+        print_capture(&mut out, &args, &capture).unwrap();
+        let json: serde_json::Value = serde_json::from_slice(&out).unwrap();
+        assert_eq!(json["x"], 1);
+        assert_eq!(json["width"], 2);
+        assert_eq!(json["output"], serde_json::Value::Null);
+        assert_eq!(json["stats"]["min"], serde_json::json!([0, 10, 255]));
+        assert_eq!(
+            json["stats"]["mean"],
+            serde_json::json!([127.5, 15.0, 255.0])
+        );
+        assert!(json["stats"]["brightness"].is_f64());
     }
 
-    if let Err(e) = save_image(&args, rect, &data) {
-        eprintln!("failed to save: {e}");
+    #[test]
+    fn border_color_is_checked() {
+        assert_eq!(parse_color("#ff0000"), Ok([255, 0, 0, 255]));
+        assert!(parse_color("red").is_err());
     }
 }
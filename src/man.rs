@@ -0,0 +1,72 @@
+//! Man page for packagers, written with hidden `mangen` subcommand.
+
+use std::{
+    fs,
+    io::{self, Write},
+    path::{Path, PathBuf},
+};
+
+use clap::Command;
+use clap_mangen::{
+    roff::{bold, roman, Roff},
+    Man,
+};
+use prtsc_wayland::rect_fmt::SPECIFIERS;
+
+/// Render man page of `cmd` into `dir`. Returns path of written file.
+pub fn write_man_page(cmd: Command, dir: &Path) -> io::Result<PathBuf> {
+    let path = dir.join(format!("{}.1", cmd.get_name()));
+    let mut page = Vec::new();
+    render(cmd, &mut page)?;
+    fs::write(&path, page)?;
+
+    Ok(path)
+}
+
+fn render(cmd: Command, w: &mut dyn Write) -> io::Result<()> {
+    let man = Man::new(cmd);
+
+    man.render_title(w)?;
+    man.render_name_section(w)?;
+    man.render_synopsis_section(w)?;
+    man.render_description_section(w)?;
+    man.render_options_section(w)?;
+    // NOTE: after help is not rendered, it is written with terminal escapes for --help
+    formatting_section().to_writer(w)?;
+    man.render_version_section(w)
+}
+
+/// Specifiers of `--selection-format`.
+fn formatting_section() -> Roff {
+    let mut roff = Roff::new();
+    roff.control("SH", ["FORMATTING"]);
+    roff.text([
+        roman("Specifiers replaced in "),
+        bold("--selection-format"),
+        roman(":"),
+    ]);
+    for spec in SPECIFIERS {
+        roff.control("TP", []);
+        roff.text([bold(spec.usage())]);
+        roff.text([roman(spec.description)]);
+    }
+
+    roff
+}
+
+#[cfg(test)]
+mod tests {
+    use super::render;
+
+    #[test]
+    fn specifiers_are_documented() {
+        let mut page = Vec::new();
+        render(crate::command(), &mut page).unwrap();
+        let page = String::from_utf8(page).unwrap();
+
+        assert!(page.contains(".SH FORMATTING"));
+        assert!(page.contains("%x %X"));
+        assert!(page.contains("The name of output"));
+        assert!(page.contains("\\-\\-selection\\-format"));
+    }
+}
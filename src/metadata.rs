@@ -0,0 +1,99 @@
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use prtsc_wayland::Capture;
+
+/// PNG `tEXt` chunks describing where screenshot came from. Geometry is in the same "x,y wxh"
+/// format as `--geometry` accepts.
+pub fn png_text(capture: &Capture, time: SystemTime) -> Vec<(String, String)> {
+    let mut text = vec![
+        ("Creation Time".to_owned(), format_timestamp(time)),
+        (
+            "Software".to_owned(),
+            concat!(env!("CARGO_PKG_NAME"), " ", env!("CARGO_PKG_VERSION")).to_owned(),
+        ),
+        ("Geometry".to_owned(), capture.rect.to_string()),
+    ];
+    if let Some(name) = &capture.output_name {
+        text.push(("Source".to_owned(), name.clone()));
+    }
+
+    text
+}
+
+/// Format `time` as RFC 3339 UTC timestamp, like `2024-01-31T12:00:00Z`.
+fn format_timestamp(time: SystemTime) -> String {
+    let secs = time
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0);
+    let (days, secs) = (secs / 86400, secs % 86400);
+
+    // Days to civil date, see http://howardhinnant.github.io/date_algorithms.html#civil_from_days
+    let z = days as i64 + 719468;
+    let era = z / 146097;
+    let doe = z - era * 146097;
+    let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + (month <= 2) as i64;
+
+    format!(
+        "{year:04}-{month:02}-{day:02}T{:02}:{:02}:{:02}Z",
+        secs / 3600,
+        secs / 60 % 60,
+        secs % 60
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::{Duration, UNIX_EPOCH};
+
+    use prtsc_wayland::{
+        points::{Point, Rectangle},
+        Capture,
+    };
+
+    use super::{format_timestamp, png_text};
+
+    #[test]
+    fn text_describes_capture() {
+        let mut capture = Capture {
+            data: Vec::new(),
+            width: 300,
+            height: 200,
+            rect: Rectangle::new(Point::new(10, 20), 300, 200),
+            selected: Rectangle::new(Point::new(10, 20), 300, 200),
+            output_name: Some("DP-1".to_owned()),
+            pixels_per_metre: None,
+            scale_factor: 1,
+        };
+        let time = UNIX_EPOCH + Duration::from_secs(951_827_696);
+
+        let text = png_text(&capture, time);
+        let get = |key: &str| text.iter().find(|(k, _)| k == key).map(|(_, v)| v.as_str());
+        assert_eq!(get("Creation Time"), Some("2000-02-29T12:34:56Z"));
+        assert_eq!(get("Geometry"), Some("10,20 300x200"));
+        assert_eq!(get("Source"), Some("DP-1"));
+        assert!(get("Software").is_some_and(|s| s.starts_with("prtsc-wayland ")));
+
+        // Unknown output is left out
+        capture.output_name = None;
+        assert!(!png_text(&capture, time).iter().any(|(k, _)| k == "Source"));
+    }
+
+    #[test]
+    fn timestamps() {
+        assert_eq!(format_timestamp(UNIX_EPOCH), "1970-01-01T00:00:00Z");
+        assert_eq!(
+            format_timestamp(UNIX_EPOCH + Duration::from_secs(951_827_696)),
+            "2000-02-29T12:34:56Z"
+        );
+        assert_eq!(
+            format_timestamp(UNIX_EPOCH + Duration::from_secs(1_735_689_599)),
+            "2024-12-31T23:59:59Z"
+        );
+    }
+}
@@ -1,4 +1,4 @@
-use std::cmp::Ordering;
+use std::{cmp::Ordering, fmt};
 
 pub type PointInt = u32;
 
@@ -8,7 +8,7 @@ pub struct Point {
     pub y: PointInt,
 }
 
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, PartialEq, Eq)]
 pub struct Rectangle {
     /// Top left point of rectangle
     pub start: Point,
@@ -50,6 +50,50 @@ impl Point {
     pub fn is_same_quater(&self, a: &Self, b: &Self) -> bool {
         self.quater(a) == self.quater(b)
     }
+
+    /// Round point to the nearest multiple of `grid`, but not past `max` (inclusive).
+    pub fn snapped(&self, grid: PointInt, max: &Self) -> Self {
+        let snap = |v: PointInt, max: PointInt| {
+            let v = (v + grid / 2) / grid * grid;
+            if v > max {
+                // Last grid line on screen
+                max / grid * grid
+            } else {
+                v
+            }
+        };
+
+        Self::new(snap(self.x, max.x), snap(self.y, max.y))
+    }
+
+    /// Move point towards `anchor` along one axis, so rectangle between them has `ratio` of
+    /// width to height and fits into rectangle between original point and `anchor`.
+    pub fn with_aspect_to(&self, anchor: &Self, ratio: f64) -> Self {
+        let dx = self.x.abs_diff(anchor.x);
+        let dy = self.y.abs_diff(anchor.y);
+        let (dx, dy) = if dx as f64 > dy as f64 * ratio {
+            ((dy as f64 * ratio).round() as PointInt, dy)
+        } else {
+            (dx, (dx as f64 / ratio).round() as PointInt)
+        };
+        let towards = |v: PointInt, anchor: PointInt, d: PointInt| {
+            if v < anchor {
+                anchor - d
+            } else {
+                anchor + d
+            }
+        };
+
+        Self::new(towards(self.x, anchor.x, dx), towards(self.y, anchor.y, dy))
+    }
+
+    /// Euclidean distance between points.
+    pub fn distance(&self, other: &Self) -> f64 {
+        let dx = self.x.abs_diff(other.x) as f64;
+        let dy = self.y.abs_diff(other.y) as f64;
+
+        dx.hypot(dy)
+    }
 }
 
 impl Rectangle {
@@ -82,9 +126,137 @@ impl Rectangle {
     }
 }
 
+impl Rectangle {
+    /// Same as [`Rectangle::from_two_points`], but points located in one axis make rectangle one
+    /// pixel tall or wide. Returns [`None`] only if points are same.
+    pub fn from_two_points_or_line(a: Point, b: Point) -> Option<Self> {
+        if a == b {
+            return None;
+        }
+
+        let start = Point::new(a.x.min(b.x), a.y.min(b.y));
+        let width = a.x.abs_diff(b.x).max(1);
+        let height = a.y.abs_diff(b.y).max(1);
+
+        Some(Self::new(start, width, height))
+    }
+}
+
+impl Rectangle {
+    /// Number of pixels in rectangle, it does not fit into [`PointInt`] on huge virtual desktops.
+    pub fn area(&self) -> u64 {
+        self.width as u64 * self.height as u64
+    }
+
+    /// Coordinates right after bottom right pixel, they may not fit into [`PointInt`].
+    fn end(&self) -> (u64, u64) {
+        (
+            self.start.x as u64 + self.width as u64,
+            self.start.y as u64 + self.height as u64,
+        )
+    }
+
+    /// Whether `point` is one of pixels of rectangle.
+    pub fn contains(&self, point: &Point) -> bool {
+        let (end_x, end_y) = self.end();
+
+        (self.start.x as u64..end_x).contains(&(point.x as u64))
+            && (self.start.y as u64..end_y).contains(&(point.y as u64))
+    }
+
+    /// Common part of two rectangles, [`None`] if they do not overlap.
+    pub fn intersection(&self, other: &Self) -> Option<Self> {
+        let start = Point::new(
+            self.start.x.max(other.start.x),
+            self.start.y.max(other.start.y),
+        );
+        let (self_x, self_y) = self.end();
+        let (other_x, other_y) = other.end();
+        let (end_x, end_y) = (self_x.min(other_x), self_y.min(other_y));
+        if end_x <= start.x as u64 || end_y <= start.y as u64 {
+            return None;
+        }
+
+        // Both ends are at most `start + PointInt::MAX` of the other rectangle
+        Some(Self::new(
+            start.clone(),
+            (end_x - start.x as u64) as PointInt,
+            (end_y - start.y as u64) as PointInt,
+        ))
+    }
+
+    /// Part of rectangle inside of `bounds` (like output), [`None`] if it is outside.
+    pub fn clamp_to(&self, bounds: &Self) -> Option<Self> {
+        self.intersection(bounds)
+    }
+
+    /// Grow rectangle by `by` on every side (or shrink if it is negative). Top left corner does
+    /// not go below zero and size does not go over [`PointInt::MAX`]. Returns [`None`] if nothing
+    /// is left after shrinking.
+    pub fn grow(&self, by: i32) -> Option<Self> {
+        let (end_x, end_y) = self.end();
+        let start_x = self.start.x as i64 - by as i64;
+        let start_y = self.start.y as i64 - by as i64;
+        let end_x = end_x as i64 + by as i64;
+        let end_y = end_y as i64 + by as i64;
+
+        let start = Point::new(start_x.max(0) as u32, start_y.max(0) as u32);
+        if end_x <= start.x as i64 || end_y <= start.y as i64 {
+            return None;
+        }
+
+        let size = |end: i64, start: u32| (end - start as i64).min(PointInt::MAX as i64) as u32;
+        Some(Self::new(
+            start.clone(),
+            size(end_x, start.x),
+            size(end_y, start.y),
+        ))
+    }
+}
+
+/// Parse ratio of width to height as `W:H` (like `16:9`) or as a number (like `1.777`).
+/// Returns [`None`] if it is not a positive finite number.
+pub fn parse_aspect(s: &str) -> Option<f64> {
+    let ratio = match s.split_once(':') {
+        Some((w, h)) => w.trim().parse::<f64>().ok()? / h.trim().parse::<f64>().ok()?,
+        None => s.trim().parse().ok()?,
+    };
+
+    (ratio > 0.0 && ratio.is_finite()).then_some(ratio)
+}
+
+impl Rectangle {
+    /// Parse geometry in `x,y wxh` format (the same format as default `--selection-format`).
+    /// Returns [`None`] if the string is malformed or rectangle is degenerate.
+    pub fn parse_geometry(s: &str) -> Option<Self> {
+        let (pos, size) = s.trim().split_once(' ')?;
+        let (x, y) = pos.split_once(',')?;
+        let (width, height) = size.trim_start().split_once('x')?;
+
+        let start = Point::new(x.parse().ok()?, y.parse().ok()?);
+        let (width, height) = (width.parse().ok()?, height.parse().ok()?);
+        if width == 0 || height == 0 {
+            return None;
+        }
+
+        Some(Self::new(start, width, height))
+    }
+}
+
+/// Formats rectangle in `x,y wxh` format, see [`Rectangle::parse_geometry`].
+impl fmt::Display for Rectangle {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{},{} {}x{}",
+            self.start.x, self.start.y, self.width, self.height
+        )
+    }
+}
+
 #[cfg(test)]
 mod tests {
-    use super::{Point, Quater};
+    use super::{parse_aspect, Point, Quater, Rectangle};
 
     #[test]
     fn quater_tests() {
@@ -105,4 +277,187 @@ mod tests {
             assert_eq!(*expected, actual, "Failed for a = {a:?}, b = {b:?}");
         }
     }
+
+    #[test]
+    fn distances() {
+        let a = Point::new(10, 10);
+
+        assert_eq!(a.distance(&a), 0.0);
+        assert_eq!(a.distance(&Point::new(13, 14)), 5.0);
+        assert_eq!(Point::new(13, 14).distance(&a), 5.0);
+        assert_eq!(a.distance(&Point::new(10, 0)), 10.0);
+        assert_eq!(
+            Point::new(0, 0).distance(&Point::new(u32::MAX, 0)),
+            u32::MAX as f64
+        );
+    }
+
+    #[test]
+    fn square_aspect() {
+        let anchor = Point::new(50, 50);
+
+        assert_eq!(
+            Point::new(60, 80).with_aspect_to(&anchor, 1.0),
+            Point::new(60, 60)
+        );
+        assert_eq!(
+            Point::new(10, 45).with_aspect_to(&anchor, 1.0),
+            Point::new(45, 45)
+        );
+        assert_eq!(
+            Point::new(70, 20).with_aspect_to(&anchor, 1.0),
+            Point::new(70, 30)
+        );
+        assert_eq!(Point::new(50, 90).with_aspect_to(&anchor, 1.0), anchor);
+    }
+
+    #[test]
+    fn aspect_is_kept() {
+        let anchor = Point::new(100, 100);
+
+        assert_eq!(
+            Point::new(260, 300).with_aspect_to(&anchor, 16.0 / 9.0),
+            Point::new(260, 190)
+        );
+        assert_eq!(
+            Point::new(0, 0).with_aspect_to(&anchor, 16.0 / 9.0),
+            Point::new(0, 44)
+        );
+        assert_eq!(
+            Point::new(110, 0).with_aspect_to(&anchor, 0.5),
+            Point::new(110, 80)
+        );
+    }
+
+    #[test]
+    fn snapping_stays_on_screen() {
+        let max = Point::new(99, 49);
+
+        assert_eq!(Point::new(3, 4).snapped(8, &max), Point::new(0, 8));
+        assert_eq!(Point::new(12, 20).snapped(8, &max), Point::new(16, 24));
+        assert_eq!(Point::new(98, 49).snapped(8, &max), Point::new(96, 48));
+        assert_eq!(Point::new(42, 7).snapped(1, &max), Point::new(42, 7));
+    }
+
+    #[test]
+    fn aspect_parsing() {
+        assert_eq!(parse_aspect("16:9"), Some(16.0 / 9.0));
+        assert_eq!(parse_aspect("1.5"), Some(1.5));
+        assert_eq!(parse_aspect(" 4 : 3 "), Some(4.0 / 3.0));
+        assert_eq!(parse_aspect("16:0"), None);
+        assert_eq!(parse_aspect("-1"), None);
+        assert_eq!(parse_aspect("16:9:1"), None);
+        assert_eq!(parse_aspect("wide"), None);
+    }
+
+    #[test]
+    fn lines_are_one_pixel_thick() {
+        assert_eq!(
+            Rectangle::from_two_points_or_line(Point::new(10, 5), Point::new(3, 5)),
+            Some(Rectangle::new(Point::new(3, 5), 7, 1))
+        );
+        assert_eq!(
+            Rectangle::from_two_points_or_line(Point::new(4, 2), Point::new(4, 9)),
+            Some(Rectangle::new(Point::new(4, 2), 1, 7))
+        );
+        assert_eq!(
+            Rectangle::from_two_points_or_line(Point::new(3, 9), Point::new(8, 2)),
+            Rectangle::from_two_points(Point::new(3, 9), Point::new(8, 2))
+        );
+        assert_eq!(
+            Rectangle::from_two_points_or_line(Point::new(4, 4), Point::new(4, 4)),
+            None
+        );
+    }
+
+    #[test]
+    fn grow_and_intersect() {
+        let screen = Rectangle::new(Point::new(0, 0), 100, 50);
+        let rect = Rectangle::new(Point::new(5, 10), 20, 20);
+
+        assert_eq!(rect.grow(3), Some(Rectangle::new(Point::new(2, 7), 26, 26)));
+        assert_eq!(
+            rect.grow(-3),
+            Some(Rectangle::new(Point::new(8, 13), 14, 14))
+        );
+        assert_eq!(rect.grow(-10), None);
+        assert!(rect.contains(&Point::new(5, 29)));
+        assert!(!rect.contains(&Point::new(25, 10)));
+        assert_eq!(
+            rect.grow(100).and_then(|r| r.intersection(&screen)),
+            Some(screen.clone())
+        );
+        assert_eq!(
+            Rectangle::new(Point::new(90, 40), 20, 20).intersection(&screen),
+            Some(Rectangle::new(Point::new(90, 40), 10, 10))
+        );
+        assert_eq!(
+            Rectangle::new(Point::new(100, 0), 5, 5).intersection(&screen),
+            None
+        );
+    }
+
+    #[test]
+    fn huge_rectangles_do_not_overflow() {
+        let max = u32::MAX;
+        let huge = Rectangle::new(Point::new(max - 10, max - 10), max, max);
+
+        assert_eq!(huge.area(), max as u64 * max as u64);
+        assert!(huge.contains(&Point::new(max, max)));
+        assert!(!huge.contains(&Point::new(max - 11, max)));
+        assert_eq!(
+            huge.intersection(&Rectangle::new(Point::new(0, 0), max, max)),
+            Some(Rectangle::new(Point::new(max - 10, max - 10), 10, 10))
+        );
+        assert_eq!(
+            huge.grow(5),
+            Some(Rectangle::new(Point::new(max - 15, max - 15), max, max))
+        );
+        assert_eq!(
+            Rectangle::new(Point::new(0, 0), max, max).grow(-1),
+            Some(Rectangle::new(Point::new(1, 1), max - 2, max - 2))
+        );
+    }
+
+    #[test]
+    fn clamped_to_bounds() {
+        let output = Rectangle::new(Point::new(0, 0), 1920, 1080);
+        let rect = Rectangle::new(Point::new(10, 20), 30, 40);
+
+        assert_eq!(rect.clamp_to(&output), Some(rect.clone()));
+        assert_eq!(
+            Rectangle::new(Point::new(1900, 1000), 100, 100).clamp_to(&output),
+            Some(Rectangle::new(Point::new(1900, 1000), 20, 80))
+        );
+        assert_eq!(
+            Rectangle::new(Point::new(1920, 0), 10, 10).clamp_to(&output),
+            None
+        );
+    }
+
+    #[test]
+    fn geometry_roundtrip() {
+        let rect = Rectangle::new(Point::new(10, 20), 300, 400);
+
+        assert_eq!(rect.to_string(), "10,20 300x400");
+        assert_eq!(
+            Rectangle::parse_geometry("10,20 300x400"),
+            Some(rect.clone())
+        );
+        assert_eq!(Rectangle::parse_geometry("10,20 300x400\n"), Some(rect));
+    }
+
+    #[test]
+    fn geometry_malformed() {
+        for s in [
+            "",
+            "10,20",
+            "10 20 300x400",
+            "10,20 300",
+            "10,20 0x400",
+            "-1,20 300x400",
+        ] {
+            assert_eq!(Rectangle::parse_geometry(s), None, "Failed for {s:?}");
+        }
+    }
 }
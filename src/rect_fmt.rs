@@ -2,10 +2,97 @@ use std::fmt::{Display, Write as _};
 
 use crate::points::Rectangle;
 
+/// Value substituted for format specifier.
+#[derive(Clone, Copy)]
+enum Value {
+    X,
+    Y,
+    Width,
+    Height,
+    Area,
+    /// Prefix of [`Value::X`], [`Value::Y`], [`Value::Width`] or [`Value::Height`]
+    Physical,
+    Output,
+    Color,
+    Newline,
+}
+
+/// Format specifier of [`RectFmt`], like `%x`.
+pub struct Specifier {
+    /// Characters after `%` which are replaced with value
+    pub chars: &'static [char],
+    pub description: &'static str,
+    value: Value,
+}
+
+/// All specifiers known to [`RectFmt`], `--help` and man page are generated from it.
+pub const SPECIFIERS: &[Specifier] = &[
+    Specifier {
+        chars: &['x', 'X'],
+        description: "The x-coordinate of the selection",
+        value: Value::X,
+    },
+    Specifier {
+        chars: &['y', 'Y'],
+        description: "The y-coordinate of the selection",
+        value: Value::Y,
+    },
+    Specifier {
+        chars: &['w', 'W'],
+        description: "The width of the selection",
+        value: Value::Width,
+    },
+    Specifier {
+        chars: &['h', 'H'],
+        description: "The height of the selection",
+        value: Value::Height,
+    },
+    Specifier {
+        chars: &['a'],
+        description: "The area of the selection in pixels",
+        value: Value::Area,
+    },
+    Specifier {
+        chars: &['p'],
+        description: "Prefix of %x %y %w %h for physical pixels (multiplied by output scale)",
+        value: Value::Physical,
+    },
+    Specifier {
+        chars: &['o'],
+        description: "The name of output",
+        value: Value::Output,
+    },
+    Specifier {
+        chars: &['c'],
+        description: "The color of the top left pixel of the selection (#rrggbb)",
+        value: Value::Color,
+    },
+    Specifier {
+        chars: &['n'],
+        description: "Newline char ('\\n')",
+        value: Value::Newline,
+    },
+];
+
+impl Specifier {
+    /// Specifiers as they are written in format string, e.g. `%x %X`.
+    pub fn usage(&self) -> String {
+        self.chars
+            .iter()
+            .map(|c| format!("%{c}"))
+            .collect::<Vec<_>>()
+            .join(" ")
+    }
+}
+
 pub struct RectFmt<'a> {
     pub rect: Rectangle,
     pub fmt: &'a str,
     pub output_name: Option<&'a str>,
+    /// Scale factor of output, logical coordinates are multiplied by it for `%p`
+    pub scale: i32,
+    /// RGB color of the top left pixel of `rect`, if it is known
+    pub pixel: Option<[u8; 3]>,
 }
 
 impl Display for RectFmt<'_> {
@@ -28,14 +115,42 @@ impl Display for RectFmt<'_> {
 
             let remainder = &part[ctrl.len_utf8()..];
 
-            match ctrl {
-                'x' | 'X' => write!(f, "{}{remainder}", self.rect.start.x)?,
-                'y' | 'Y' => write!(f, "{}{remainder}", self.rect.start.y)?,
-                'w' | 'W' => write!(f, "{}{remainder}", self.rect.width)?,
-                'h' | 'H' => write!(f, "{}{remainder}", self.rect.height)?,
-                'o' => write!(f, "{}{remainder}", self.output_name.unwrap_or("<unknown>"))?,
-                'n' => write!(f, "\n{remainder}")?,
-                _ => write!(f, "%{part}")?,
+            let Some(spec) = SPECIFIERS.iter().find(|s| s.chars.contains(&ctrl)) else {
+                write!(f, "%{part}")?;
+                continue;
+            };
+
+            match spec.value {
+                Value::X => write!(f, "{}{remainder}", self.rect.start.x)?,
+                Value::Y => write!(f, "{}{remainder}", self.rect.start.y)?,
+                Value::Width => write!(f, "{}{remainder}", self.rect.width)?,
+                Value::Height => write!(f, "{}{remainder}", self.rect.height)?,
+                Value::Area => write!(f, "{}{remainder}", self.rect.area())?,
+                Value::Physical => {
+                    let mut chars = remainder.chars();
+                    let value = chars
+                        .next()
+                        .and_then(|c| SPECIFIERS.iter().find(|s| s.chars.contains(&c)))
+                        .and_then(|spec| match spec.value {
+                            Value::X => Some(self.rect.start.x as i64),
+                            Value::Y => Some(self.rect.start.y as i64),
+                            Value::Width => Some(self.rect.width as i64),
+                            Value::Height => Some(self.rect.height as i64),
+                            _ => None,
+                        });
+                    match value {
+                        Some(v) => write!(f, "{}{}", v * self.scale as i64, chars.as_str())?,
+                        None => write!(f, "%{part}")?,
+                    }
+                }
+                Value::Output => {
+                    write!(f, "{}{remainder}", self.output_name.unwrap_or("<unknown>"))?
+                }
+                Value::Color => match self.pixel {
+                    Some([r, g, b]) => write!(f, "#{r:02x}{g:02x}{b:02x}{remainder}")?,
+                    None => write!(f, "<unknown>{remainder}")?,
+                },
+                Value::Newline => write!(f, "\n{remainder}")?,
             }
         }
 
@@ -43,3 +158,60 @@ impl Display for RectFmt<'_> {
     }
 }
 
+#[cfg(test)]
+mod tests {
+    use super::RectFmt;
+    use crate::points::{Point, Rectangle};
+
+    #[test]
+    fn specifiers_are_replaced() {
+        let fmt = RectFmt {
+            rect: Rectangle::new(Point::new(1, 2), 30, 40),
+            fmt: "%x,%Y %wx%H %o%% %q %c%n",
+            output_name: Some("DP-1"),
+            scale: 1,
+            pixel: Some([255, 16, 0]),
+        };
+
+        assert_eq!(fmt.to_string(), "1,2 30x40 DP-1% %q #ff1000\n");
+    }
+
+    #[test]
+    fn unknown_values() {
+        let fmt = RectFmt {
+            rect: Rectangle::new(Point::new(1, 2), 30, 40),
+            fmt: "%o %c",
+            output_name: None,
+            scale: 1,
+            pixel: None,
+        };
+
+        assert_eq!(fmt.to_string(), "<unknown> <unknown>");
+    }
+
+    #[test]
+    fn area_does_not_overflow() {
+        let fmt = RectFmt {
+            rect: Rectangle::new(Point::new(0, 0), u32::MAX, 2),
+            fmt: "%a",
+            output_name: None,
+            scale: 1,
+            pixel: None,
+        };
+
+        assert_eq!(fmt.to_string(), "8589934590");
+    }
+
+    #[test]
+    fn physical_pixels() {
+        let fmt = RectFmt {
+            rect: Rectangle::new(Point::new(1, 2), 30, 40),
+            fmt: "%px,%pY %pWx%ph %x %p %pa %po",
+            output_name: None,
+            scale: 2,
+            pixel: None,
+        };
+
+        assert_eq!(fmt.to_string(), "2,4 60x80 1 %p %pa %po");
+    }
+}
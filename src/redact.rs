@@ -0,0 +1,76 @@
+use prtsc_wayland::points::{Point, Rectangle};
+
+/// Replace pixels of `region` in `data` (`width`x`height` image with `channels` bytes per pixel)
+/// with average color of `block`x`block` squares, counted from top left corner of `region`.
+/// Part of `region` outside of image is ignored. Returns `false` if nothing was pixelated.
+pub fn pixelate(
+    data: &mut [u8],
+    width: u32,
+    height: u32,
+    channels: usize,
+    region: &Rectangle,
+    block: u32,
+) -> bool {
+    let image = Rectangle::new(Point::new(0, 0), width, height);
+    let Some(region) = region.intersection(&image) else {
+        return false;
+    };
+    let (end_x, end_y) = (
+        region.start.x + region.width,
+        region.start.y + region.height,
+    );
+    let block = block.max(1);
+
+    for block_y in (region.start.y..end_y).step_by(block as usize) {
+        for block_x in (region.start.x..end_x).step_by(block as usize) {
+            // Byte offsets of pixels in block
+            let pixels: Vec<usize> = (block_y..(block_y + block).min(end_y))
+                .flat_map(|y| {
+                    (block_x..(block_x + block).min(end_x))
+                        .map(move |x| (y * width + x) as usize * channels)
+                })
+                .collect();
+            let count = pixels.len() as u64;
+
+            for c in 0..channels {
+                let sum: u64 = pixels.iter().map(|&i| data[i + c] as u64).sum();
+                let average = ((sum + count / 2) / count) as u8;
+                for &i in &pixels {
+                    data[i + c] = average;
+                }
+            }
+        }
+    }
+
+    true
+}
+
+#[cfg(test)]
+mod tests {
+    use prtsc_wayland::points::{Point, Rectangle};
+
+    use super::pixelate;
+
+    #[test]
+    fn blocks_are_averaged() {
+        // 4x2 grayscale image, left 2x2 block is pixelated
+        let mut data = vec![0, 100, 7, 7, 50, 250, 7, 7];
+        let region = Rectangle::new(Point::new(0, 0), 2, 2);
+
+        assert!(pixelate(&mut data, 4, 2, 1, &region, 2));
+        assert_eq!(data, [100, 100, 7, 7, 100, 100, 7, 7]);
+    }
+
+    #[test]
+    fn region_is_clipped_to_image() {
+        // 3x1 RGB image, partial block at the right edge
+        let mut data = vec![10, 20, 30, 0, 0, 0, 200, 100, 50];
+        let region = Rectangle::new(Point::new(1, 0), 10, 10);
+
+        assert!(pixelate(&mut data, 3, 1, 3, &region, 4));
+        assert_eq!(data, [10, 20, 30, 100, 50, 25, 100, 50, 25]);
+
+        let outside = Rectangle::new(Point::new(3, 0), 1, 1);
+        assert!(!pixelate(&mut data, 3, 1, 3, &outside, 4));
+    }
+}
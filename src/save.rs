@@ -0,0 +1,328 @@
+use std::{
+    ffi::{OsStr, OsString},
+    fs::{File, OpenOptions},
+    io::{self, BufWriter, Write as _},
+    path::{Path, PathBuf},
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use image::ImageError;
+
+/// Create a file that does not exist yet (`O_CREAT | O_EXCL`). Fails with
+/// [`io::ErrorKind::AlreadyExists`] if anything (including dangling symlink or directory) is
+/// located at `path`.
+pub fn create_new_file(path: impl AsRef<Path>) -> io::Result<File> {
+    OpenOptions::new().write(true).create_new(true).open(path)
+}
+
+/// Create file in `dir` with name made by `name` from random-ish suffix, retrying with other
+/// suffixes if it already exists.
+fn create_unique_file(dir: &Path, name: impl Fn(u32) -> OsString) -> io::Result<(PathBuf, File)> {
+    let seed = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |d| d.subsec_nanos())
+        ^ std::process::id().rotate_left(16);
+
+    let mut last_err = None;
+    for attempt in 0..16u32 {
+        let suffix = seed.wrapping_add(attempt.wrapping_mul(0x9e37_79b9));
+        let path = dir.join(name(suffix));
+        match create_new_file(&path) {
+            Ok(file) => return Ok((path, file)),
+            Err(e) if e.kind() == io::ErrorKind::AlreadyExists => last_err = Some(e),
+            Err(e) => return Err(e),
+        }
+    }
+
+    Err(last_err.expect("at least one attempt was made"))
+}
+
+/// Create `.<name>.tmp-XXXXXXXX` file next to `path`.
+fn create_temp_file(dir: &Path, name: &OsStr) -> io::Result<(PathBuf, File)> {
+    create_unique_file(dir, |suffix| {
+        let mut tmp_name = OsString::from(".");
+        tmp_name.push(name);
+        tmp_name.push(format!(".tmp-{suffix:08x}"));
+        tmp_name
+    })
+}
+
+/// Reserve `prtsc-wayland-XXXXXXXX.<extension>` file in temporary directory (`$TMPDIR`). It is
+/// empty until it is replaced with [`write_atomically`] and is not removed on exit.
+pub fn reserve_temp_output(extension: &str) -> io::Result<PathBuf> {
+    let (path, _) = create_unique_file(&std::env::temp_dir(), |suffix| {
+        format!("prtsc-wayland-{suffix:08x}.{extension}").into()
+    })?;
+    Ok(path)
+}
+
+/// Write file via temporary file in the same directory, which is synced and then renamed over
+/// `path`, so `path` never contains partially written data. With `no_clobber` existing `path` is
+/// never replaced and [`io::ErrorKind::AlreadyExists`] is returned instead: `path` is reserved
+/// with [`create_new_file`] before writing and the temporary file replaces this empty file.
+///
+/// Temporary file (and reserved `path`) is removed on any error.
+pub fn write_atomically(
+    path: &Path,
+    no_clobber: bool,
+    write: impl FnOnce(&mut BufWriter<&mut File>) -> Result<(), ImageError>,
+) -> Result<(), ImageError> {
+    let Some(name) = path.file_name() else {
+        return Err(
+            io::Error::new(io::ErrorKind::InvalidInput, "output path has no file name").into(),
+        );
+    };
+    let dir = match path.parent() {
+        Some(dir) if !dir.as_os_str().is_empty() => dir,
+        _ => Path::new("."),
+    };
+
+    // Fail early, before spending time on encoding. Reserved file is replaced with rename(2) like
+    // any other, so filesystems without hard links work too
+    if no_clobber {
+        create_new_file(path)?;
+    }
+
+    let result = create_temp_file(dir, name)
+        .map_err(ImageError::from)
+        .and_then(|(tmp_path, mut file)| {
+            let result = write_temp_file(&mut file, &tmp_path, path, write);
+            if result.is_err() {
+                _ = std::fs::remove_file(&tmp_path);
+            }
+            result
+        });
+
+    if no_clobber && result.is_err() {
+        _ = std::fs::remove_file(path);
+    }
+
+    result
+}
+
+/// Write `file` at `tmp_path` with `write`, sync it and rename it over `path`.
+fn write_temp_file(
+    file: &mut File,
+    tmp_path: &Path,
+    path: &Path,
+    write: impl FnOnce(&mut BufWriter<&mut File>) -> Result<(), ImageError>,
+) -> Result<(), ImageError> {
+    let mut writer = BufWriter::new(&mut *file);
+    write(&mut writer)?;
+    writer.flush()?;
+    drop(writer);
+    file.sync_all()?;
+    std::fs::rename(tmp_path, path)?;
+
+    Ok(())
+}
+
+/// Insert `-<n>` before extension of file name in `output`, e.g. `shot.png` becomes
+/// `shot-1.png`. Used to save several regions of `--multi`.
+pub fn numbered_output(output: &str, n: usize) -> String {
+    let name_start = output.rfind('/').map_or(0, |i| i + 1);
+    // Leading dot of hidden files is not an extension
+    match output[name_start..].rfind('.').filter(|&i| i > 0) {
+        Some(i) => {
+            let (stem, extension) = output.split_at(name_start + i);
+            format!("{stem}-{n}{extension}")
+        }
+        None => format!("{output}-{n}"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        io::{self, Write},
+        path::{Path, PathBuf},
+    };
+
+    use image::ImageError;
+
+    use super::{create_new_file, numbered_output, reserve_temp_output, write_atomically};
+
+    fn temp_path(name: &str) -> PathBuf {
+        let path =
+            std::env::temp_dir().join(format!("prtsc-wayland-{}-{name}", std::process::id()));
+        _ = std::fs::remove_file(&path);
+        _ = std::fs::remove_dir_all(&path);
+        path
+    }
+
+    fn dir_entries(dir: &Path) -> Vec<String> {
+        let mut entries: Vec<_> = std::fs::read_dir(dir)
+            .unwrap()
+            .map(|e| e.unwrap().file_name().to_string_lossy().into_owned())
+            .collect();
+        entries.sort();
+        entries
+    }
+
+    #[test]
+    fn create_new_file_refuses_existing() {
+        let path = temp_path("existing.png");
+
+        create_new_file(&path).expect("file should not exist yet");
+        let err = create_new_file(&path).expect_err("file already exists");
+        assert_eq!(err.kind(), io::ErrorKind::AlreadyExists);
+
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn create_new_file_refuses_symlinks() {
+        let target = temp_path("symlink-target.png");
+        let link = temp_path("symlink.png");
+        std::os::unix::fs::symlink(&target, &link).unwrap();
+
+        // Dangling symlink must not be followed
+        let err = create_new_file(&link).expect_err("symlink exists");
+        assert_eq!(err.kind(), io::ErrorKind::AlreadyExists);
+        assert!(!target.exists(), "symlink target should not be created");
+
+        std::fs::remove_file(&link).unwrap();
+    }
+
+    #[test]
+    fn create_new_file_refuses_directories() {
+        let path = temp_path("directory");
+        std::fs::create_dir(&path).unwrap();
+
+        let err = create_new_file(&path).expect_err("directory exists");
+        assert_eq!(err.kind(), io::ErrorKind::AlreadyExists);
+
+        std::fs::remove_dir(&path).unwrap();
+    }
+
+    #[test]
+    fn write_atomically_replaces_file() {
+        let dir = temp_path("atomic-replace");
+        std::fs::create_dir(&dir).unwrap();
+        let path = dir.join("image.png");
+        std::fs::write(&path, b"old").unwrap();
+
+        write_atomically(&path, false, |w| Ok(w.write_all(b"new")?)).unwrap();
+
+        assert_eq!(std::fs::read(&path).unwrap(), b"new");
+        assert_eq!(dir_entries(&dir), ["image.png"]);
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn write_atomically_removes_temp_file_on_error() {
+        let dir = temp_path("atomic-error");
+        std::fs::create_dir(&dir).unwrap();
+        let path = dir.join("image.png");
+        std::fs::write(&path, b"old").unwrap();
+
+        write_atomically(&path, false, |w| {
+            w.write_all(b"partial")?;
+            Err(ImageError::IoError(io::Error::other("disk is full")))
+        })
+        .expect_err("writer failed");
+
+        assert_eq!(std::fs::read(&path).unwrap(), b"old");
+        assert_eq!(dir_entries(&dir), ["image.png"]);
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn write_atomically_no_clobber() {
+        let dir = temp_path("atomic-no-clobber");
+        std::fs::create_dir(&dir).unwrap();
+        let path = dir.join("image.png");
+        std::fs::write(&path, b"old").unwrap();
+
+        let err = write_atomically(&path, true, |w| Ok(w.write_all(b"new")?))
+            .expect_err("file already exists");
+        assert!(matches!(err, ImageError::IoError(e) if e.kind() == io::ErrorKind::AlreadyExists));
+
+        let fresh = dir.join("fresh.png");
+        write_atomically(&fresh, true, |w| Ok(w.write_all(b"new")?)).unwrap();
+
+        assert_eq!(std::fs::read(&path).unwrap(), b"old");
+        assert_eq!(std::fs::read(&fresh).unwrap(), b"new");
+        assert_eq!(dir_entries(&dir), ["fresh.png", "image.png"]);
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn write_atomically_no_clobber_keeps_links_and_directories() {
+        let dir = temp_path("atomic-no-clobber-kinds");
+        std::fs::create_dir(&dir).unwrap();
+        let link = dir.join("link.png");
+        std::os::unix::fs::symlink(dir.join("target.png"), &link).unwrap();
+        std::fs::create_dir(dir.join("dir.png")).unwrap();
+
+        for name in ["link.png", "dir.png"] {
+            let err = write_atomically(&dir.join(name), true, |w| Ok(w.write_all(b"new")?))
+                .expect_err("path already exists");
+            assert!(
+                matches!(err, ImageError::IoError(e) if e.kind() == io::ErrorKind::AlreadyExists)
+            );
+        }
+
+        // Dangling symlink is not followed
+        assert_eq!(dir_entries(&dir), ["dir.png", "link.png"]);
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn write_atomically_no_clobber_reserves_path() {
+        let dir = temp_path("atomic-no-clobber-reserve");
+        std::fs::create_dir(&dir).unwrap();
+        let path = dir.join("image.png");
+
+        // Path is taken before image is encoded, so nothing else can create it meanwhile
+        write_atomically(&path, true, |w| {
+            assert_eq!(std::fs::read(&path)?, b"");
+            assert!(create_new_file(&path).is_err());
+            Ok(w.write_all(b"new")?)
+        })
+        .unwrap();
+        assert_eq!(std::fs::read(&path).unwrap(), b"new");
+        assert_eq!(dir_entries(&dir), ["image.png"]);
+
+        // Reservation is dropped on error
+        let failed = dir.join("failed.png");
+        write_atomically(&failed, true, |_| {
+            Err(ImageError::IoError(io::Error::other("disk is full")))
+        })
+        .expect_err("writer failed");
+        assert_eq!(dir_entries(&dir), ["image.png"]);
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn temp_outputs_are_unique() {
+        let a = reserve_temp_output("png").unwrap();
+        let b = reserve_temp_output("png").unwrap();
+
+        assert_ne!(a, b);
+        assert_eq!(a.extension().unwrap(), "png");
+        assert!(a.starts_with(std::env::temp_dir()));
+        // Reserved file is replaced, not written through
+        write_atomically(&a, false, |w| Ok(w.write_all(b"new")?)).unwrap();
+        assert_eq!(std::fs::read(&a).unwrap(), b"new");
+
+        std::fs::remove_file(&a).unwrap();
+        std::fs::remove_file(&b).unwrap();
+    }
+
+    #[test]
+    fn numbered_outputs() {
+        assert_eq!(numbered_output("shot.png", 1), "shot-1.png");
+        assert_eq!(
+            numbered_output("dir.d/shot.tar.png", 2),
+            "dir.d/shot.tar-2.png"
+        );
+        assert_eq!(numbered_output("dir.d/shot", 3), "dir.d/shot-3");
+        assert_eq!(numbered_output(".png", 4), ".png-4");
+    }
+}
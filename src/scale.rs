@@ -0,0 +1,95 @@
+use image::{imageops, ImageBuffer, Luma, Pixel, Rgb};
+
+/// Resampling filter of `--scale`.
+#[derive(Clone, Copy, Debug, Default, clap::ValueEnum)]
+pub enum Filter {
+    Nearest,
+    Triangle,
+    #[default]
+    Lanczos3,
+}
+
+impl From<Filter> for imageops::FilterType {
+    fn from(filter: Filter) -> Self {
+        match filter {
+            Filter::Nearest => Self::Nearest,
+            Filter::Triangle => Self::Triangle,
+            Filter::Lanczos3 => Self::Lanczos3,
+        }
+    }
+}
+
+/// Size of `width`x`height` image scaled by `factor`, rounded to nearest pixel but never empty.
+pub fn scaled_size(width: u32, height: u32, factor: f64) -> (u32, u32) {
+    let scale = |v: u32| ((v as f64 * factor).round() as u32).max(1);
+
+    (scale(width), scale(height))
+}
+
+/// Resize RGB8 (or L8 if `grayscale`) `data` of `width`x`height` image by `factor`. Returns new
+/// image with its size, factor of 1 returns `data` as is.
+pub fn scale(
+    data: Vec<u8>,
+    width: u32,
+    height: u32,
+    grayscale: bool,
+    factor: f64,
+    filter: Filter,
+) -> (Vec<u8>, u32, u32) {
+    let (new_width, new_height) = scaled_size(width, height, factor);
+    if (new_width, new_height) == (width, height) {
+        return (data, width, height);
+    }
+
+    let data = if grayscale {
+        resize::<Luma<u8>>(data, width, height, new_width, new_height, filter)
+    } else {
+        resize::<Rgb<u8>>(data, width, height, new_width, new_height, filter)
+    };
+
+    (data, new_width, new_height)
+}
+
+fn resize<P: Pixel<Subpixel = u8> + 'static>(
+    data: Vec<u8>,
+    width: u32,
+    height: u32,
+    new_width: u32,
+    new_height: u32,
+    filter: Filter,
+) -> Vec<u8> {
+    let image = ImageBuffer::<P, _>::from_raw(width, height, data)
+        .expect("buffer size should match image size");
+
+    imageops::resize(&image, new_width, new_height, filter.into()).into_raw()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{scale, scaled_size, Filter};
+
+    #[test]
+    fn sizes_are_rounded() {
+        assert_eq!(scaled_size(1920, 1080, 0.5), (960, 540));
+        assert_eq!(scaled_size(3, 5, 0.5), (2, 3));
+        assert_eq!(scaled_size(1, 1, 0.1), (1, 1));
+    }
+
+    #[test]
+    fn scaling_by_one_keeps_image() {
+        let data: Vec<u8> = (0..2 * 2 * 3).collect();
+
+        assert_eq!(
+            scale(data.clone(), 2, 2, false, 1.0, Filter::Lanczos3),
+            (data, 2, 2)
+        );
+    }
+
+    #[test]
+    fn grayscale_is_scaled() {
+        let (data, width, height) = scale(vec![200; 4 * 2], 4, 2, true, 0.5, Filter::Nearest);
+
+        assert_eq!((width, height), (2, 1));
+        assert_eq!(data, [200, 200]);
+    }
+}
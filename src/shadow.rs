@@ -0,0 +1,148 @@
+/// Drop shadow settings of `--shadow`.
+#[derive(Clone, Debug)]
+pub struct Shadow {
+    /// Blur radius in pixels, image is padded with it on every side
+    pub blur: u32,
+    /// Shift of shadow relative to image
+    pub offset: (i32, i32),
+    /// RGBA color of shadow
+    pub color: [u8; 4],
+}
+
+/// Put RGBA8 `data` of `width`x`height` image on larger transparent canvas with blurred shadow
+/// behind it. Returns new image with its size.
+pub fn drop_shadow(data: &[u8], width: u32, height: u32, shadow: &Shadow) -> (Vec<u8>, u32, u32) {
+    let (ox, oy) = shadow.offset;
+    let pad = shadow.blur;
+    let out_width = width + pad * 2 + ox.unsigned_abs();
+    let out_height = height + pad * 2 + oy.unsigned_abs();
+
+    // Top left corners of image and its shadow on canvas
+    let image_x = (pad as i32 - ox.min(0)) as usize;
+    let image_y = (pad as i32 - oy.min(0)) as usize;
+    let shadow_x = (image_x as i32 + ox) as usize;
+    let shadow_y = (image_y as i32 + oy) as usize;
+
+    let (w, h) = (out_width as usize, out_height as usize);
+
+    // Shadow has the shape of image alpha
+    let mut mask = vec![0f32; w * h];
+    for (i, pixel) in data.chunks_exact(4).enumerate() {
+        let (x, y) = (i % width as usize, i / width as usize);
+        mask[(shadow_y + y) * w + shadow_x + x] = pixel[3] as f32 / 255.0;
+    }
+
+    // Two box blurs are close enough to gaussian one
+    let radius = shadow.blur as usize / 2;
+    for _ in 0..2 {
+        box_blur(&mut mask, h, w, w, 1, radius);
+        box_blur(&mut mask, w, 1, h, w, radius);
+    }
+
+    let [r, g, b, a] = shadow.color;
+    let mut out = Vec::with_capacity(w * h * 4);
+    for coverage in mask {
+        out.extend_from_slice(&[r, g, b, (a as f32 * coverage).round() as u8]);
+    }
+
+    // Composite image over shadow
+    for (i, src) in data.chunks_exact(4).enumerate() {
+        let (x, y) = (i % width as usize, i / width as usize);
+        let pos = ((image_y + y) * w + image_x + x) * 4;
+        let dst = &mut out[pos..pos + 4];
+
+        let src_a = src[3] as f32 / 255.0;
+        let dst_a = dst[3] as f32 / 255.0 * (1.0 - src_a);
+        let out_a = src_a + dst_a;
+        if out_a == 0.0 {
+            continue;
+        }
+
+        for c in 0..3 {
+            dst[c] = ((src[c] as f32 * src_a + dst[c] as f32 * dst_a) / out_a).round() as u8;
+        }
+        dst[3] = (out_a * 255.0).round() as u8;
+    }
+
+    (out, out_width, out_height)
+}
+
+/// Box blur of `radius` along `lines` lines of `len` values. Line `i` starts at `i * line_step`,
+/// its values are `step` apart. Values outside of line are zeros.
+fn box_blur(
+    values: &mut [f32],
+    lines: usize,
+    line_step: usize,
+    len: usize,
+    step: usize,
+    radius: usize,
+) {
+    if radius == 0 {
+        return;
+    }
+
+    let window = (radius * 2 + 1) as f32;
+    let mut prefix = vec![0f32; len + 1];
+    for line in 0..lines {
+        let start = line * line_step;
+        for i in 0..len {
+            prefix[i + 1] = prefix[i] + values[start + i * step];
+        }
+        for i in 0..len {
+            let (lo, hi) = (i.saturating_sub(radius), (i + radius + 1).min(len));
+            values[start + i * step] = (prefix[hi] - prefix[lo]) / window;
+        }
+    }
+}
+
+/// Parse `#RRGGBB` or `#RRGGBBAA` color.
+pub fn parse_color(s: &str) -> Option<[u8; 4]> {
+    let hex = s.strip_prefix('#')?;
+    if !matches!(hex.len(), 6 | 8) || !hex.is_ascii() {
+        return None;
+    }
+
+    let channel = |i: usize| u8::from_str_radix(&hex[i * 2..i * 2 + 2], 16).ok();
+    let alpha = if hex.len() == 8 { channel(3)? } else { 255 };
+
+    Some([channel(0)?, channel(1)?, channel(2)?, alpha])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{drop_shadow, parse_color, Shadow};
+
+    #[test]
+    fn shadow_pads_image() {
+        let (width, height) = (4, 3);
+        let data = [10, 20, 30, 255].repeat(width * height);
+        let shadow = Shadow {
+            blur: 4,
+            offset: (2, -1),
+            color: [0, 0, 0, 200],
+        };
+
+        let (out, out_width, out_height) = drop_shadow(&data, width as u32, height as u32, &shadow);
+        let (out_width, out_height) = (out_width as usize, out_height as usize);
+        assert_eq!((out_width, out_height), (width + 10, height + 9));
+        assert_eq!(out.len(), out_width * out_height * 4);
+
+        let pixel = |x: usize, y: usize| &out[(y * out_width + x) * 4..(y * out_width + x) * 4 + 4];
+        // Image is at (pad, pad + 1), because shadow is moved up
+        assert_eq!(pixel(4, 5), [10, 20, 30, 255]);
+        assert_eq!(pixel(4 + width - 1, 5 + height - 1), [10, 20, 30, 255]);
+        // Shadow is visible to the right of image, corners are transparent
+        assert!(pixel(4 + width, 5)[3] > 0);
+        assert_eq!(pixel(0, 0)[3], 0);
+        assert_eq!(pixel(out_width - 1, out_height - 1)[3], 0);
+    }
+
+    #[test]
+    fn colors() {
+        assert_eq!(parse_color("#102030"), Some([16, 32, 48, 255]));
+        assert_eq!(parse_color("#10203040"), Some([16, 32, 48, 64]));
+        assert_eq!(parse_color("102030"), None);
+        assert_eq!(parse_color("#1020"), None);
+        assert_eq!(parse_color("#10203g"), None);
+    }
+}
@@ -0,0 +1,202 @@
+//! Small pieces of state persisted between runs in `$XDG_STATE_HOME/prtsc-wayland`.
+
+use std::{io, path::PathBuf};
+
+use prtsc_wayland::points::{Point, Rectangle};
+use serde::{Deserialize, Serialize};
+
+fn last_region_path() -> Option<PathBuf> {
+    Some(crate::dirs::state_dir()?.join("last-region"))
+}
+
+fn history_path() -> Option<PathBuf> {
+    Some(crate::dirs::state_dir()?.join("history"))
+}
+
+fn no_state_dir() -> io::Error {
+    io::Error::new(
+        io::ErrorKind::NotFound,
+        "neither XDG_STATE_HOME nor HOME is set",
+    )
+}
+
+/// Region of the last interactive selection with name of its output, if it is known.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct LastRegion {
+    pub rect: Rectangle,
+    pub output: Option<String>,
+}
+
+/// Parse "X,Y WxH" line with optional output name on the next line. Files of older versions
+/// have no output line.
+fn parse_last_region(s: &str) -> Option<LastRegion> {
+    let mut lines = s.lines();
+    let rect = Rectangle::parse_geometry(lines.next()?)?;
+    let output = lines
+        .next()
+        .map(str::trim)
+        .filter(|name| !name.is_empty())
+        .map(String::from);
+
+    Some(LastRegion { rect, output })
+}
+
+fn format_last_region(last: &LastRegion) -> String {
+    match &last.output {
+        Some(output) => format!("{}\n{output}\n", last.rect),
+        None => format!("{}\n", last.rect),
+    }
+}
+
+/// Load region of the last interactive selection. Missing or malformed state results in
+/// [`None`].
+pub fn load_last_region() -> Option<LastRegion> {
+    let s = std::fs::read_to_string(last_region_path()?).ok()?;
+
+    parse_last_region(&s)
+}
+
+/// Remember region of interactive selection on `output` for `--last-region`.
+pub fn save_last_region(rect: &Rectangle, output: Option<&str>) -> io::Result<()> {
+    let Some(path) = last_region_path() else {
+        return Err(no_state_dir());
+    };
+
+    if let Some(dir) = path.parent() {
+        std::fs::create_dir_all(dir)?;
+    }
+    let last = LastRegion {
+        rect: rect.clone(),
+        output: output.map(String::from),
+    };
+    std::fs::write(path, format_last_region(&last))
+}
+
+/// Selection as JSON object printed with `--selection-format json`. Interactive selections are
+/// remembered in history file as such objects, one per line, oldest first.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct HistoryEntry {
+    pub x: u32,
+    pub y: u32,
+    pub width: u32,
+    pub height: u32,
+    pub output: Option<String>,
+    pub scale: i32,
+}
+
+impl HistoryEntry {
+    pub fn new(rect: &Rectangle, output: Option<&str>, scale: i32) -> Self {
+        Self {
+            x: rect.start.x,
+            y: rect.start.y,
+            width: rect.width,
+            height: rect.height,
+            output: output.map(String::from),
+            scale,
+        }
+    }
+
+    pub fn rect(&self) -> Rectangle {
+        Rectangle::new(Point::new(self.x, self.y), self.width, self.height)
+    }
+}
+
+/// Parse history file, malformed lines are skipped.
+fn parse_history(s: &str) -> Vec<HistoryEntry> {
+    s.lines()
+        .filter_map(|line| serde_json::from_str(line).ok())
+        .collect()
+}
+
+/// Append `entry` to `history` and drop the oldest entries to keep at most `max` of them.
+fn format_history(mut history: Vec<HistoryEntry>, entry: HistoryEntry, max: usize) -> String {
+    history.push(entry);
+    let skip = history.len().saturating_sub(max);
+
+    history[skip..]
+        .iter()
+        .map(|e| serde_json::to_string(e).expect("history entry is serializable") + "\n")
+        .collect()
+}
+
+/// Load remembered selections, oldest first. Missing history is empty.
+pub fn load_history() -> Vec<HistoryEntry> {
+    history_path()
+        .and_then(|path| std::fs::read_to_string(path).ok())
+        .map_or_else(Vec::new, |s| parse_history(&s))
+}
+
+/// Add interactive selection to history for `--print-history`, keeping at most `max` entries.
+pub fn push_history(entry: HistoryEntry, max: usize) -> io::Result<()> {
+    let Some(path) = history_path() else {
+        return Err(no_state_dir());
+    };
+
+    if let Some(dir) = path.parent() {
+        std::fs::create_dir_all(dir)?;
+    }
+    std::fs::write(path, format_history(load_history(), entry, max))
+}
+
+#[cfg(test)]
+mod tests {
+    use prtsc_wayland::points::{Point, Rectangle};
+
+    use super::{
+        format_history, format_last_region, parse_history, parse_last_region, HistoryEntry,
+        LastRegion,
+    };
+
+    fn entry(x: u32) -> HistoryEntry {
+        HistoryEntry {
+            x,
+            y: 2,
+            width: 30,
+            height: 40,
+            output: Some("DP-1".into()),
+            scale: 1,
+        }
+    }
+
+    #[test]
+    fn history_is_ring_buffer() {
+        let history = format_history(vec![entry(1), entry(2)], entry(3), 2);
+
+        assert_eq!(parse_history(&history), [entry(2), entry(3)]);
+        assert_eq!(
+            parse_history(&format_history(Vec::new(), entry(1), 5)),
+            [entry(1)]
+        );
+    }
+
+    #[test]
+    fn corrupt_history_lines_are_skipped() {
+        let history = format_history(Vec::new(), entry(1), 5);
+        let corrupt = format!("garbage\n{{\"x\": 1}}\n{history}10,20 30x40\n");
+
+        assert_eq!(parse_history(&corrupt), [entry(1)]);
+    }
+
+    #[test]
+    fn last_region_keeps_output() {
+        let last = LastRegion {
+            rect: Rectangle::new(Point::new(10, 20), 30, 40),
+            output: Some("DP-1".into()),
+        };
+
+        assert_eq!(format_last_region(&last), "10,20 30x40\nDP-1\n");
+        assert_eq!(parse_last_region(&format_last_region(&last)), Some(last));
+    }
+
+    #[test]
+    fn last_region_without_output() {
+        let rect = Rectangle::new(Point::new(10, 20), 30, 40);
+
+        // Written by older versions and when compositor does not name outputs
+        assert_eq!(
+            parse_last_region("10,20 30x40\n"),
+            Some(LastRegion { rect, output: None })
+        );
+        assert_eq!(parse_last_region("garbage\nDP-1\n"), None);
+    }
+}
@@ -0,0 +1,78 @@
+use std::fmt;
+
+/// Color statistics of RGB8 region, printed with `--analyze`.
+#[derive(Debug, PartialEq, serde::Serialize)]
+pub struct RegionStats {
+    pub min: [u8; 3],
+    pub max: [u8; 3],
+    pub mean: [f64; 3],
+    /// Mean relative luminance (Rec. 709 coefficients), 0..=1
+    pub brightness: f64,
+}
+
+impl RegionStats {
+    /// Compute statistics of RGB8 `data`. Returns [`None`] if `data` has no pixels.
+    pub fn compute(data: &[u8]) -> Option<Self> {
+        let pixels = data.len() / 3;
+        if pixels == 0 {
+            return None;
+        }
+
+        let mut min = [u8::MAX; 3];
+        let mut max = [u8::MIN; 3];
+        let mut sum = [0u64; 3];
+
+        for pixel in data.chunks_exact(3) {
+            for c in 0..3 {
+                min[c] = min[c].min(pixel[c]);
+                max[c] = max[c].max(pixel[c]);
+                sum[c] += pixel[c] as u64;
+            }
+        }
+
+        let mean = sum.map(|s| s as f64 / pixels as f64);
+        let brightness = (0.2126 * mean[0] + 0.7152 * mean[1] + 0.0722 * mean[2]) / 255.0;
+
+        Some(Self {
+            min,
+            max,
+            mean,
+            brightness,
+        })
+    }
+}
+
+impl fmt::Display for RegionStats {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let [r, g, b] = self.min;
+        writeln!(f, "min: {r} {g} {b}")?;
+        let [r, g, b] = self.max;
+        writeln!(f, "max: {r} {g} {b}")?;
+        let [r, g, b] = self.mean;
+        writeln!(f, "mean: {r:.1} {g:.1} {b:.1}")?;
+        writeln!(f, "brightness: {:.1}%", self.brightness * 100.0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::RegionStats;
+
+    #[test]
+    fn stats_of_two_pixels() {
+        let stats = RegionStats::compute(&[0, 10, 255, 255, 20, 255]).unwrap();
+
+        assert_eq!(stats.min, [0, 10, 255]);
+        assert_eq!(stats.max, [255, 20, 255]);
+        assert_eq!(stats.mean, [127.5, 15.0, 255.0]);
+        assert_eq!(
+            stats.to_string(),
+            "min: 0 10 255\nmax: 255 20 255\nmean: 127.5 15.0 255.0\nbrightness: 22.1%\n"
+        );
+    }
+
+    #[test]
+    fn empty_region_has_no_stats() {
+        assert_eq!(RegionStats::compute(&[]), None);
+    }
+}
@@ -0,0 +1,489 @@
+//! Rectangles of windows, selection corners snap to their edges and focused one can be captured
+//! without selection.
+//!
+//! Wayland does not let clients see positions of other windows (`wlr-foreign-toplevel` has no
+//! geometry), so they are asked from compositor over its IPC. Every compositor is a
+//! [`WindowSource`], see [`detect`].
+
+use std::{
+    env,
+    io::{self, Read, Write},
+    os::unix::net::UnixStream,
+    path::PathBuf,
+    time::Duration,
+};
+
+use serde::Deserialize;
+
+use crate::points::{Point, Rectangle};
+
+/// Compositor may be busy, but screen is already frozen and user waits for overlay
+const IPC_TIMEOUT: Duration = Duration::from_millis(500);
+
+/// Window in global compositor coordinates.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Window {
+    pub x: i32,
+    pub y: i32,
+    pub width: u32,
+    pub height: u32,
+}
+
+impl Window {
+    /// Part of window on `width`x`height` output with top left corner at global `origin`, in
+    /// output coordinates. Returns [`None`] if window is not on output.
+    pub fn on_output(&self, origin: (i32, i32), width: u32, height: u32) -> Option<Rectangle> {
+        let clamp = |v: i64, max: u32| v.clamp(0, max as i64) as u32;
+        let (x0, y0) = (
+            clamp(self.x as i64 - origin.0 as i64, width),
+            clamp(self.y as i64 - origin.1 as i64, height),
+        );
+        let (x1, y1) = (
+            clamp(self.x as i64 + self.width as i64 - origin.0 as i64, width),
+            clamp(self.y as i64 + self.height as i64 - origin.1 as i64, height),
+        );
+        if x1 <= x0 || y1 <= y0 {
+            return None;
+        }
+
+        Some(Rectangle::new(Point::new(x0, y0), x1 - x0, y1 - y0))
+    }
+}
+
+/// Provider of window rectangles.
+pub trait WindowSource {
+    /// Name shown in logs
+    fn name(&self) -> &'static str;
+
+    /// Visible windows, bottom to top.
+    fn windows(&self) -> io::Result<Vec<Window>>;
+
+    /// Focused window and name of output it is on, [`None`] if nothing is focused.
+    fn active_window(&self) -> io::Result<Option<(Window, String)>>;
+}
+
+/// Source of running compositor, [`None`] if none of supported ones is detected.
+pub fn detect() -> Option<Box<dyn WindowSource>> {
+    if let Some(sway) = SwayIpc::from_env() {
+        return Some(Box::new(sway));
+    }
+    HyprlandIpc::from_env().map(|hyprland| Box::new(hyprland) as Box<dyn WindowSource>)
+}
+
+fn invalid_data(e: serde_json::Error) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, e)
+}
+
+/// Sway (and i3-compatible) IPC at `$SWAYSOCK`.
+pub struct SwayIpc {
+    socket: PathBuf,
+}
+
+impl SwayIpc {
+    const MAGIC: &'static [u8] = b"i3-ipc";
+    const GET_TREE: u32 = 4;
+
+    pub fn from_env() -> Option<Self> {
+        let socket = env::var_os("SWAYSOCK")?;
+        Some(Self {
+            socket: socket.into(),
+        })
+    }
+
+    /// Send message of `kind` with empty payload and read reply payload.
+    fn request(&self, kind: u32) -> io::Result<Vec<u8>> {
+        let mut stream = UnixStream::connect(&self.socket)?;
+        stream.set_read_timeout(Some(IPC_TIMEOUT))?;
+
+        let mut message = Self::MAGIC.to_vec();
+        message.extend(0u32.to_ne_bytes());
+        message.extend(kind.to_ne_bytes());
+        stream.write_all(&message)?;
+
+        let mut header = [0; 14];
+        stream.read_exact(&mut header)?;
+        if &header[..6] != Self::MAGIC {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "reply is not i3-ipc message",
+            ));
+        }
+        let len = u32::from_ne_bytes(header[6..10].try_into().expect("4 bytes"));
+        let mut payload = vec![0; len as usize];
+        stream.read_exact(&mut payload)?;
+
+        Ok(payload)
+    }
+}
+
+/// Node of sway tree, only fields needed to find windows.
+#[derive(Deserialize)]
+struct SwayNode {
+    #[serde(rename = "type", default)]
+    kind: String,
+    #[serde(default)]
+    name: Option<String>,
+    rect: SwayRect,
+    #[serde(default)]
+    focused: bool,
+    #[serde(default)]
+    visible: Option<bool>,
+    /// Set for windows only
+    #[serde(default)]
+    pid: Option<u32>,
+    #[serde(default)]
+    nodes: Vec<SwayNode>,
+    #[serde(default)]
+    floating_nodes: Vec<SwayNode>,
+}
+
+#[derive(Deserialize)]
+struct SwayRect {
+    x: i32,
+    y: i32,
+    width: u32,
+    height: u32,
+}
+
+/// Visible windows of `GET_TREE` reply, tiled ones are below floating.
+fn parse_sway_tree(tree: &[u8]) -> Result<Vec<Window>, serde_json::Error> {
+    fn walk(node: &SwayNode, tiled: &mut Vec<Window>, floating: &mut Vec<Window>, is_float: bool) {
+        if node.pid.is_some() && node.visible == Some(true) {
+            let window = node.window();
+            if is_float {
+                floating.push(window);
+            } else {
+                tiled.push(window);
+            }
+        }
+        for child in &node.nodes {
+            walk(child, tiled, floating, is_float);
+        }
+        for child in &node.floating_nodes {
+            walk(child, tiled, floating, true);
+        }
+    }
+
+    let root: SwayNode = serde_json::from_slice(tree)?;
+    let (mut tiled, mut floating) = (Vec::new(), Vec::new());
+    walk(&root, &mut tiled, &mut floating, false);
+    tiled.append(&mut floating);
+
+    Ok(tiled)
+}
+
+impl SwayNode {
+    fn window(&self) -> Window {
+        Window {
+            x: self.rect.x,
+            y: self.rect.y,
+            width: self.rect.width,
+            height: self.rect.height,
+        }
+    }
+}
+
+/// Focused window of `GET_TREE` reply and name of output it is on.
+fn parse_sway_focused(tree: &[u8]) -> Result<Option<(Window, String)>, serde_json::Error> {
+    fn walk(node: &SwayNode, output: Option<&str>) -> Option<(Window, String)> {
+        let output = match node.kind.as_str() {
+            "output" => node.name.as_deref(),
+            _ => output,
+        };
+        if node.focused && node.pid.is_some() {
+            return Some((node.window(), output?.to_owned()));
+        }
+        node.nodes
+            .iter()
+            .chain(&node.floating_nodes)
+            .find_map(|child| walk(child, output))
+    }
+
+    let root: SwayNode = serde_json::from_slice(tree)?;
+    Ok(walk(&root, None))
+}
+
+impl WindowSource for SwayIpc {
+    fn name(&self) -> &'static str {
+        "sway IPC"
+    }
+
+    fn windows(&self) -> io::Result<Vec<Window>> {
+        let tree = self.request(Self::GET_TREE)?;
+        parse_sway_tree(&tree).map_err(invalid_data)
+    }
+
+    fn active_window(&self) -> io::Result<Option<(Window, String)>> {
+        let tree = self.request(Self::GET_TREE)?;
+        parse_sway_focused(&tree).map_err(invalid_data)
+    }
+}
+
+/// Hyprland IPC at `$XDG_RUNTIME_DIR/hypr/$HYPRLAND_INSTANCE_SIGNATURE/.socket.sock`.
+pub struct HyprlandIpc {
+    socket: PathBuf,
+}
+
+impl HyprlandIpc {
+    pub fn from_env() -> Option<Self> {
+        let signature = env::var_os("HYPRLAND_INSTANCE_SIGNATURE")?;
+        let runtime = env::var_os("XDG_RUNTIME_DIR")?;
+        Some(Self {
+            socket: PathBuf::from(runtime)
+                .join("hypr")
+                .join(signature)
+                .join(".socket.sock"),
+        })
+    }
+
+    /// Send `command` and read reply until socket is closed.
+    fn request(&self, command: &str) -> io::Result<Vec<u8>> {
+        let mut stream = UnixStream::connect(&self.socket)?;
+        stream.set_read_timeout(Some(IPC_TIMEOUT))?;
+        stream.write_all(command.as_bytes())?;
+
+        let mut reply = Vec::new();
+        stream.read_to_end(&mut reply)?;
+        Ok(reply)
+    }
+}
+
+#[derive(Deserialize)]
+struct HyprlandClient {
+    at: (i32, i32),
+    size: (u32, u32),
+    mapped: bool,
+    hidden: bool,
+    floating: bool,
+    workspace: HyprlandWorkspace,
+}
+
+/// Reply to `j/activewindow`, it is empty object if nothing is focused.
+#[derive(Deserialize)]
+struct HyprlandActiveWindow {
+    at: Option<(i32, i32)>,
+    size: Option<(u32, u32)>,
+    monitor: Option<i64>,
+}
+
+#[derive(Deserialize)]
+struct HyprlandMonitor {
+    id: i64,
+    name: String,
+    #[serde(rename = "activeWorkspace")]
+    active_workspace: HyprlandWorkspace,
+}
+
+#[derive(Deserialize)]
+struct HyprlandWorkspace {
+    id: i64,
+}
+
+/// Windows on active workspaces from `j/clients` and `j/monitors` replies, tiled ones are below
+/// floating.
+fn parse_hyprland(clients: &[u8], monitors: &[u8]) -> Result<Vec<Window>, serde_json::Error> {
+    let clients: Vec<HyprlandClient> = serde_json::from_slice(clients)?;
+    let monitors: Vec<HyprlandMonitor> = serde_json::from_slice(monitors)?;
+
+    let (floating, tiled): (Vec<_>, Vec<_>) = clients
+        .into_iter()
+        .filter(|c| c.mapped && !c.hidden)
+        .filter(|c| {
+            monitors
+                .iter()
+                .any(|m| m.active_workspace.id == c.workspace.id)
+        })
+        .partition(|c| c.floating);
+
+    Ok(tiled
+        .into_iter()
+        .chain(floating)
+        .map(|c| Window {
+            x: c.at.0,
+            y: c.at.1,
+            width: c.size.0,
+            height: c.size.1,
+        })
+        .collect())
+}
+
+/// Window of `j/activewindow` reply and name of its monitor from `j/monitors` reply.
+fn parse_hyprland_active(
+    active: &[u8],
+    monitors: &[u8],
+) -> Result<Option<(Window, String)>, serde_json::Error> {
+    let active: HyprlandActiveWindow = serde_json::from_slice(active)?;
+    let monitors: Vec<HyprlandMonitor> = serde_json::from_slice(monitors)?;
+
+    let (Some(at), Some(size), Some(monitor)) = (active.at, active.size, active.monitor) else {
+        return Ok(None);
+    };
+    let Some(monitor) = monitors.into_iter().find(|m| m.id == monitor) else {
+        return Ok(None);
+    };
+
+    Ok(Some((
+        Window {
+            x: at.0,
+            y: at.1,
+            width: size.0,
+            height: size.1,
+        },
+        monitor.name,
+    )))
+}
+
+impl WindowSource for HyprlandIpc {
+    fn name(&self) -> &'static str {
+        "Hyprland IPC"
+    }
+
+    fn windows(&self) -> io::Result<Vec<Window>> {
+        let clients = self.request("j/clients")?;
+        let monitors = self.request("j/monitors")?;
+        parse_hyprland(&clients, &monitors).map_err(invalid_data)
+    }
+
+    fn active_window(&self) -> io::Result<Option<(Window, String)>> {
+        let active = self.request("j/activewindow")?;
+        let monitors = self.request("j/monitors")?;
+        parse_hyprland_active(&active, &monitors).map_err(invalid_data)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        parse_hyprland, parse_hyprland_active, parse_sway_focused, parse_sway_tree, Window,
+    };
+    use crate::points::{Point, Rectangle};
+
+    #[test]
+    fn windows_are_clipped_to_output() {
+        let window = Window {
+            x: 1900,
+            y: -10,
+            width: 100,
+            height: 50,
+        };
+
+        assert_eq!(
+            window.on_output((1920, 0), 1920, 1080),
+            Some(Rectangle::new(Point::new(0, 0), 80, 40))
+        );
+        assert_eq!(
+            window.on_output((0, 0), 1920, 1080),
+            Some(Rectangle::new(Point::new(1900, 0), 20, 40))
+        );
+        assert_eq!(window.on_output((0, 1080), 1920, 1080), None);
+    }
+
+    #[test]
+    fn sway_windows_are_found() {
+        let tree = br#"{
+            "rect": {"x": 0, "y": 0, "width": 3840, "height": 1080},
+            "nodes": [{
+                "rect": {"x": 0, "y": 0, "width": 1920, "height": 1080},
+                "nodes": [
+                    {"rect": {"x": 0, "y": 0, "width": 960, "height": 1080}, "pid": 1, "visible": true},
+                    {"rect": {"x": 960, "y": 0, "width": 960, "height": 1080}, "pid": 2, "visible": false}
+                ],
+                "floating_nodes": [
+                    {"rect": {"x": 100, "y": 100, "width": 200, "height": 100}, "pid": 3, "visible": true}
+                ]
+            }]
+        }"#;
+
+        let windows = parse_sway_tree(tree).unwrap();
+        assert_eq!(
+            windows.iter().map(|w| (w.x, w.width)).collect::<Vec<_>>(),
+            [(0, 960), (100, 200)]
+        );
+    }
+
+    #[test]
+    fn hyprland_windows_are_found() {
+        let clients = br#"[
+            {"at": [10, 20], "size": [300, 200], "mapped": true, "hidden": false, "floating": true, "workspace": {"id": 1}},
+            {"at": [0, 0], "size": [1920, 1080], "mapped": true, "hidden": false, "floating": false, "workspace": {"id": 1}},
+            {"at": [0, 0], "size": [500, 500], "mapped": true, "hidden": false, "floating": false, "workspace": {"id": 2}},
+            {"at": [0, 0], "size": [500, 500], "mapped": true, "hidden": true, "floating": false, "workspace": {"id": 1}}
+        ]"#;
+        let monitors = br#"[{"id": 0, "name": "DP-1", "activeWorkspace": {"id": 1}}]"#;
+
+        assert_eq!(
+            parse_hyprland(clients, monitors).unwrap(),
+            [
+                Window {
+                    x: 0,
+                    y: 0,
+                    width: 1920,
+                    height: 1080
+                },
+                Window {
+                    x: 10,
+                    y: 20,
+                    width: 300,
+                    height: 200
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn focused_sway_window_is_found() {
+        let tree = br#"{
+            "type": "root",
+            "name": "root",
+            "rect": {"x": 0, "y": 0, "width": 3840, "height": 1080},
+            "nodes": [
+                {
+                    "type": "output",
+                    "name": "DP-1",
+                    "rect": {"x": 0, "y": 0, "width": 1920, "height": 1080},
+                    "nodes": [{"rect": {"x": 0, "y": 0, "width": 1920, "height": 1080}, "pid": 1}]
+                },
+                {
+                    "type": "output",
+                    "name": "DP-2",
+                    "rect": {"x": 1920, "y": 0, "width": 1920, "height": 1080},
+                    "floating_nodes": [
+                        {"rect": {"x": 2000, "y": 100, "width": 200, "height": 100}, "pid": 2, "focused": true}
+                    ]
+                }
+            ]
+        }"#;
+
+        let (window, output) = parse_sway_focused(tree).unwrap().unwrap();
+        assert_eq!(
+            (window.x, window.width, output.as_str()),
+            (2000, 200, "DP-2")
+        );
+
+        let unfocused = br#"{"rect": {"x": 0, "y": 0, "width": 10, "height": 10}}"#;
+        assert!(parse_sway_focused(unfocused).unwrap().is_none());
+    }
+
+    #[test]
+    fn active_hyprland_window_is_found() {
+        let monitors = br#"[
+            {"id": 0, "name": "DP-1", "activeWorkspace": {"id": 1}},
+            {"id": 1, "name": "HDMI-A-1", "activeWorkspace": {"id": 2}}
+        ]"#;
+        let active = br#"{"at": [1930, 10], "size": [300, 200], "monitor": 1}"#;
+
+        assert_eq!(
+            parse_hyprland_active(active, monitors).unwrap(),
+            Some((
+                Window {
+                    x: 1930,
+                    y: 10,
+                    width: 300,
+                    height: 200
+                },
+                "HDMI-A-1".to_owned()
+            ))
+        );
+        assert_eq!(parse_hyprland_active(b"{}", monitors).unwrap(), None);
+    }
+}
@@ -0,0 +1,202 @@
+//! Exit codes which can be checked without wayland compositor.
+
+use std::{
+    fs,
+    path::PathBuf,
+    process::{Command, Output},
+};
+
+/// Empty directory used as `$XDG_CONFIG_HOME`, so user config does not affect tests.
+fn config_home(name: &str) -> PathBuf {
+    let path =
+        std::env::temp_dir().join(format!("prtsc-wayland-exit-{}-{name}", std::process::id()));
+    _ = fs::remove_dir_all(&path);
+    fs::create_dir_all(path.join("prtsc-wayland")).unwrap();
+    path
+}
+
+fn run(config_home: &PathBuf, args: &[&str]) -> Output {
+    run_with_env(config_home, &[], args)
+}
+
+fn run_with_env(config_home: &PathBuf, env: &[(&str, &str)], args: &[&str]) -> Output {
+    Command::new(env!("CARGO_BIN_EXE_prtsc-wayland"))
+        .args(args)
+        .env_remove("PRTSC_OUTPUT_DIR")
+        .env_remove("PRTSC_FORMAT")
+        .env_remove("PRTSC_SELECTION_FORMAT")
+        .envs(env.iter().copied())
+        .env("XDG_CONFIG_HOME", config_home)
+        // Make sure nothing reaches real compositor if arguments are accepted by mistake
+        .env("WAYLAND_DISPLAY", "prtsc-wayland-test-nonexistent")
+        .output()
+        .expect("failed to run binary")
+}
+
+#[test]
+fn invalid_geometry_is_usage_error() {
+    let home = config_home("geometry");
+    let output = run(&home, &["--geometry", "10,10"]);
+
+    assert_eq!(output.status.code(), Some(4));
+    assert!(String::from_utf8_lossy(&output.stderr).contains("X,Y WxH"));
+}
+
+#[test]
+fn invalid_config_is_usage_error() {
+    let home = config_home("config");
+    fs::write(home.join("prtsc-wayland/config.toml"), "quality = \"high\"").unwrap();
+    let output = run(&home, &[]);
+
+    assert_eq!(output.status.code(), Some(4));
+}
+
+#[test]
+fn help_is_success() {
+    let home = config_home("help");
+    let output = run(&home, &["--help"]);
+
+    assert_eq!(output.status.code(), Some(0));
+    assert!(String::from_utf8_lossy(&output.stdout).contains("Exit codes"));
+}
+
+#[test]
+fn missing_compositor_is_wayland_error() {
+    let home = config_home("wayland");
+    let output = run(&home, &["--output", "-", "--force-stdout"]);
+
+    assert_eq!(output.status.code(), Some(2));
+    // Stdout is reserved for image data
+    assert!(output.stdout.is_empty());
+}
+
+#[test]
+fn no_config_skips_config_file() {
+    let home = config_home("no-config");
+    fs::write(home.join("prtsc-wayland/config.toml"), "quality = \"high\"").unwrap();
+    let output = run(&home, &["--no-config", "--output", "-", "--force-stdout"]);
+
+    assert_eq!(output.status.code(), Some(2));
+}
+
+#[test]
+fn explicit_config_replaces_default_one() {
+    let home = config_home("explicit-config");
+    fs::write(home.join("prtsc-wayland/config.toml"), "quality = \"high\"").unwrap();
+    let explicit = home.join("explicit.toml");
+    fs::write(&explicit, "output = \"-\"\nforce_stdout = true").unwrap();
+    let output = run(
+        &home,
+        &["--config", explicit.to_str().unwrap(), "--force-stdout"],
+    );
+
+    assert_eq!(output.status.code(), Some(2));
+    assert!(String::from_utf8_lossy(&output.stderr).contains("unknown config key `force_stdout`"));
+
+    // Explicitly passed file must exist
+    let output = run(
+        &home,
+        &["--config", home.join("missing.toml").to_str().unwrap()],
+    );
+    assert_eq!(output.status.code(), Some(4));
+}
+
+#[test]
+fn env_overrides_config_and_cli_overrides_env() {
+    // Invalid format shows which of the sources is used
+    let home = config_home("env");
+    fs::write(home.join("prtsc-wayland/config.toml"), "format = \"bogus\"").unwrap();
+    let stdout = ["--output", "-", "--force-stdout"];
+
+    let output = run(&home, &stdout);
+    assert_eq!(output.status.code(), Some(4));
+
+    let output = run_with_env(&home, &[("PRTSC_FORMAT", "png")], &stdout);
+    assert_eq!(output.status.code(), Some(2));
+
+    fs::remove_file(home.join("prtsc-wayland/config.toml")).unwrap();
+    let output = run_with_env(&home, &[("PRTSC_FORMAT", "bogus")], &stdout);
+    assert_eq!(output.status.code(), Some(4));
+
+    let args = [&stdout[..], &["--format", "png"]].concat();
+    let output = run_with_env(&home, &[("PRTSC_FORMAT", "bogus")], &args);
+    assert_eq!(output.status.code(), Some(2));
+}
+
+#[test]
+fn dry_run_still_connects_to_compositor() {
+    let home = config_home("dry-run");
+    let output = run(&home, &["--dry-run", "--fullscreen"]);
+
+    assert_eq!(output.status.code(), Some(2));
+    assert!(output.stdout.is_empty());
+}
+
+#[test]
+fn regions_are_listed_and_unknown_one_is_usage_error() {
+    let home = config_home("regions");
+    fs::write(
+        home.join("prtsc-wayland/config.toml"),
+        "[regions]\nminimap = \"1600,0 320x320\"\nchat = \"0,0 400x1080\"",
+    )
+    .unwrap();
+
+    let output = run(&home, &["--list-regions"]);
+    assert_eq!(output.status.code(), Some(0));
+    assert_eq!(String::from_utf8_lossy(&output.stdout), "chat\nminimap\n");
+
+    let output = run(&home, &["--region", "inventory"]);
+    assert_eq!(output.status.code(), Some(4));
+}
+
+#[test]
+fn multi_to_stdout_is_usage_error() {
+    let home = config_home("multi");
+    let output = run(&home, &["--multi", "--output", "-"]);
+
+    assert_eq!(output.status.code(), Some(4));
+}
+
+#[test]
+fn loop_requires_selection_only() {
+    let home = config_home("loop");
+    let output = run(&home, &["--loop"]);
+    assert_eq!(output.status.code(), Some(4));
+
+    let output = run(&home, &["--selection-only", "--fresh"]);
+    assert_eq!(output.status.code(), Some(4));
+}
+
+#[test]
+fn analyze_requires_selection_only() {
+    let home = config_home("analyze");
+    let output = run(&home, &["--analyze"]);
+
+    assert_eq!(output.status.code(), Some(4));
+}
+
+#[test]
+fn pick_color_with_crop_padding_is_usage_error() {
+    let home = config_home("pick-color");
+    let output = run(&home, &["--pick-color", "--crop-padding", "2"]);
+
+    assert_eq!(output.status.code(), Some(4));
+}
+
+#[test]
+fn watch_to_stdout_is_usage_error() {
+    let home = config_home("watch");
+    let output = run(&home, &["--watch", "--output", "-"]);
+
+    assert_eq!(output.status.code(), Some(4));
+}
+
+#[test]
+fn scroll_step_out_of_range_is_usage_error() {
+    let home = config_home("scroll-step");
+    let output = run(&home, &["--scroll-step", "4294967295"]);
+    assert_eq!(output.status.code(), Some(4));
+
+    let output = run(&home, &["--scroll-step", "0"]);
+    assert_eq!(output.status.code(), Some(4));
+}